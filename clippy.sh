@@ -1,6 +1,6 @@
 #!/usr/bin/env bash
 
-cargo clippy -- \
+cargo clippy --workspace --all-targets --features ffi -- \
   --deny warnings \
   --allow clippy::new_without_default \
   --allow clippy::unneeded-field-pattern \
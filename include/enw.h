@@ -0,0 +1,48 @@
+/*
+ * C bindings for enw, built with
+ * `cargo rustc --release --lib --features ffi --crate-type cdylib`, which produces
+ * target/release/libenw.so (libenw.dylib on macOS).
+ *
+ * Strings are UTF-8 and NUL terminated. Every list returned must be released with
+ * enw_vars_free.
+ */
+#ifndef ENW_H
+#define ENW_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct enw_var {
+    char *key;
+    char *value;
+} enw_var;
+
+typedef struct enw_vars {
+    enw_var *vars;
+    size_t len;
+    /* NULL on success, otherwise a description of the error and vars is NULL */
+    char *error;
+} enw_vars;
+
+/* Parse the contents of a .env file */
+enw_vars *enw_parse(const char *text);
+
+/*
+ * Load and merge env files in order, later files taking precedence, with the format picked
+ * by extension just like the enw command. The result is sorted by key.
+ */
+enw_vars *enw_resolve(const char *const *paths, size_t len);
+
+/* Fetch variables from a provider, spec as for `enw --from`, e.g. "vercel:project/production" */
+enw_vars *enw_fetch(const char *spec);
+
+void enw_vars_free(enw_vars *vars);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
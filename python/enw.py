@@ -0,0 +1,117 @@
+"""Python bindings for enw, loading .env files with exactly the semantics of the enw command.
+
+This is a ctypes wrapper around the C interface in ``include/enw.h``, not a compiled extension
+module, so there is nothing to install with pip. The shared library has to be built first::
+
+    cargo rustc --release --lib --features ffi --crate-type cdylib
+
+The library is looked up in ``ENW_LIBRARY``, next to this module, and in the cargo target
+directories of the repository, in that order.
+
+    >>> import enw
+    >>> enw.parse("A=1\\nB='two words'")
+    {'A': '1', 'B': 'two words'}
+    >>> enw.load("tests/data/.env", "tests/.env")["MY_URL"]
+    'localhost'
+"""
+
+import ctypes
+import os
+import sys
+from pathlib import Path
+
+__all__ = ["EnwError", "parse", "load", "fetch", "merge"]
+
+
+class EnwError(Exception):
+    pass
+
+
+class _Var(ctypes.Structure):
+    _fields_ = [("key", ctypes.c_char_p), ("value", ctypes.c_char_p)]
+
+
+class _Vars(ctypes.Structure):
+    _fields_ = [
+        ("vars", ctypes.POINTER(_Var)),
+        ("len", ctypes.c_size_t),
+        ("error", ctypes.c_char_p),
+    ]
+
+
+def _library_candidates():
+    if "ENW_LIBRARY" in os.environ:
+        yield Path(os.environ["ENW_LIBRARY"])
+    name = {"darwin": "libenw.dylib", "win32": "enw.dll"}.get(sys.platform, "libenw.so")
+    here = Path(__file__).resolve().parent
+    yield here / name
+    for profile in ("release", "debug"):
+        yield here.parent / "target" / profile / name
+
+
+def _load_library():
+    for candidate in _library_candidates():
+        if candidate.is_file():
+            lib = ctypes.CDLL(str(candidate))
+            break
+    else:
+        raise EnwError(
+            "libenw not found, build it with "
+            "`cargo rustc --release --lib --features ffi --crate-type cdylib`"
+        )
+    for name, argtypes in [
+        ("enw_parse", [ctypes.c_char_p]),
+        ("enw_resolve", [ctypes.POINTER(ctypes.c_char_p), ctypes.c_size_t]),
+        ("enw_fetch", [ctypes.c_char_p]),
+    ]:
+        function = getattr(lib, name)
+        function.argtypes = argtypes
+        function.restype = ctypes.POINTER(_Vars)
+    lib.enw_vars_free.argtypes = [ctypes.POINTER(_Vars)]
+    lib.enw_vars_free.restype = None
+    return lib
+
+
+_lib = None
+
+
+def _call(name, *args):
+    global _lib
+    if _lib is None:
+        _lib = _load_library()
+    result = getattr(_lib, name)(*args)
+    try:
+        if result.contents.error is not None:
+            raise EnwError(result.contents.error.decode("utf-8", "replace"))
+        return {
+            result.contents.vars[i].key.decode(): result.contents.vars[i].value.decode()
+            for i in range(result.contents.len)
+        }
+    finally:
+        _lib.enw_vars_free(result)
+
+
+def parse(text):
+    """Parse the contents of a .env file into a dict."""
+    return _call("enw_parse", text.encode())
+
+
+def load(*paths):
+    """Load and merge env files, later files taking precedence. The format is picked by
+    extension, like ``enw -f``."""
+    encoded = [os.fsencode(path) for path in paths]
+    array = (ctypes.c_char_p * len(encoded))(*encoded)
+    return _call("enw_resolve", array, len(encoded))
+
+
+def fetch(spec):
+    """Fetch variables from a provider, e.g. ``fetch("vercel:project/production")``."""
+    return _call("enw_fetch", spec.encode())
+
+
+def merge(*envs):
+    """Merge dicts of variables with enw's precedence: later ones win."""
+    merged = {}
+    for env in envs:
+        merged.update(env)
+    return merged
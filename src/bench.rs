@@ -0,0 +1,84 @@
+//! `enw bench` resolves the environment repeatedly and prints how long each pipeline phase,
+//! provider and the command's spawn took, to find what makes wrapped commands slow to start.
+
+use std::time::Duration;
+
+/// Timings of one row over all iterations
+#[derive(Debug, Default)]
+pub struct Row {
+    pub name: String,
+    pub samples: Vec<Duration>,
+}
+
+#[derive(Debug, Default)]
+pub struct Table {
+    rows: Vec<Row>,
+}
+
+impl Table {
+    /// Add a sample to the row called `name`, which is created on first use
+    pub fn record(&mut self, name: &str, sample: Duration) {
+        match self.rows.iter_mut().find(|row| row.name == name) {
+            Some(row) => row.samples.push(sample),
+            None => self.rows.push(Row {
+                name: name.to_owned(),
+                samples: vec![sample],
+            }),
+        }
+    }
+
+    /// One line per row with min, mean and max, in the order the rows were first recorded
+    pub fn render(&self) -> String {
+        let width = self
+            .rows
+            .iter()
+            .map(|row| row.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("stage".len());
+        let mut out = format!(
+            "{:width$}  {:>10}  {:>10}  {:>10}\n",
+            "stage", "min", "mean", "max"
+        );
+        for row in &self.rows {
+            let min = row.samples.iter().min().copied().unwrap_or_default();
+            let max = row.samples.iter().max().copied().unwrap_or_default();
+            let total: Duration = row.samples.iter().sum();
+            let mean = total / row.samples.len().max(1) as u32;
+            out.push_str(&format!(
+                "{:width$}  {:>10}  {:>10}  {:>10}\n",
+                row.name,
+                millis(min),
+                millis(mean),
+                millis(max)
+            ));
+        }
+        out
+    }
+}
+
+fn millis(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut table = Table::default();
+        for ms in [1, 3] {
+            table.record("discover", Duration::from_millis(ms));
+            table.record("  vercel:web", Duration::from_micros(250 * ms));
+        }
+        assert_eq!(
+            table.render(),
+            "stage                min        mean         max\n\
+             discover         1.000ms     2.000ms     3.000ms\n  \
+             vercel:web     0.250ms     0.500ms     0.750ms\n"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! When to color human readable output: `--color`, then `NO_COLOR` and `CLICOLOR_FORCE`, then
+//! whether the output is a terminal other than a dumb one.
+
+use std::{env, ffi::OsString, io::IsTerminal, str::FromStr};
+
+use crate::BoxError;
+
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const YELLOW: &str = "33";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Choice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Choice {
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    /// Find `--color` among raw arguments, before they are parsed
+    pub fn from_args(args: &[OsString]) -> Choice {
+        let mut args = args.iter().map(|arg| arg.to_string_lossy());
+        while let Some(arg) = args.next() {
+            let value = match arg.strip_prefix("--color") {
+                Some("") => args.next().map(|value| value.into_owned()),
+                Some(rest) => rest.strip_prefix('=').map(str::to_owned),
+                None if arg == "--" => break,
+                None => continue,
+            };
+            return value.and_then(|v| v.parse().ok()).unwrap_or_default();
+        }
+        Choice::Auto
+    }
+
+    /// Whether to color output written to `stream`
+    pub fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            Choice::Always => true,
+            Choice::Never => false,
+            Choice::Auto => {
+                if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    false
+                } else if env::var_os("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0") {
+                    true
+                } else {
+                    stream.is_terminal() && env::var_os("TERM").is_some_and(|term| term != "dumb")
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Choice {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Choice::Auto),
+            "always" => Ok(Choice::Always),
+            "never" => Ok(Choice::Never),
+            _ => Err(format!("invalid color choice: {s}").into()),
+        }
+    }
+}
+
+/// Wrap `text` in the SGR `code` when `enabled`
+pub fn paint(text: &str, code: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_from_args() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(Into::into).collect() };
+        assert_eq!(
+            Choice::from_args(&args(&["enw", "--color", "never"])),
+            Choice::Never
+        );
+        assert_eq!(
+            Choice::from_args(&args(&["enw", "--color=always"])),
+            Choice::Always
+        );
+        assert_eq!(
+            Choice::from_args(&args(&["enw", "--", "--color=never"])),
+            Choice::Auto
+        );
+        assert_eq!(
+            Choice::from_args(&args(&["enw", "--colorful"])),
+            Choice::Auto
+        );
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("-", RED, true), "\x1b[31m-\x1b[0m");
+        assert_eq!(paint("-", RED, false), "-");
+    }
+}
@@ -0,0 +1,208 @@
+//! `enw compare --lhs ARGS --rhs ARGS` resolves two configurations and lists the variables
+//! that differ between them. `enw diff` does the same for env files, as a diff, a table or JSON.
+
+use std::collections::BTreeMap;
+
+use crate::{json, redact, Var};
+
+/// A variable that differs between the left side and the right, by key
+#[derive(Debug)]
+pub enum Change<'a> {
+    Added(&'a Var),
+    Removed(&'a Var),
+    Changed(&'a Var, &'a Var),
+}
+
+impl Change<'_> {
+    fn key(&self) -> &str {
+        match self {
+            Change::Added(var) | Change::Removed(var) | Change::Changed(var, _) => &var.key,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self {
+            Change::Added(_) => "added",
+            Change::Removed(_) => "removed",
+            Change::Changed(..) => "changed",
+        }
+    }
+
+    fn sides(&self) -> (Option<&Var>, Option<&Var>) {
+        match *self {
+            Change::Added(new) => (None, Some(new)),
+            Change::Removed(old) => (Some(old), None),
+            Change::Changed(old, new) => (Some(old), Some(new)),
+        }
+    }
+}
+
+/// The variables that differ, sorted by key
+pub fn changes<'a>(lhs: &'a [Var], rhs: &'a [Var]) -> Vec<Change<'a>> {
+    let mut sides: BTreeMap<&str, (Option<&Var>, Option<&Var>)> = BTreeMap::new();
+    for var in lhs {
+        sides.entry(&var.key).or_default().0 = Some(var);
+    }
+    for var in rhs {
+        sides.entry(&var.key).or_default().1 = Some(var);
+    }
+    sides
+        .into_values()
+        .filter_map(|sides| match sides {
+            (Some(old), Some(new)) if old.value == new.value => None,
+            (Some(old), Some(new)) => Some(Change::Changed(old, new)),
+            (Some(old), None) => Some(Change::Removed(old)),
+            (None, new) => new.map(Change::Added),
+        })
+        .collect()
+}
+
+/// Lines in the style of a unified diff, `-` for the left side and `+` for the right. Values of
+/// secrets are masked unless `show_secrets`, a changed secret still shows up as a removed and an
+/// added line.
+pub fn diff(lhs: &[Var], rhs: &[Var], show_secrets: bool) -> Vec<String> {
+    let mut out = Vec::new();
+    for change in changes(lhs, rhs) {
+        let (old, new) = change.sides();
+        out.extend(old.map(|var| format!("- {}={}", var.key, shown(var, show_secrets))));
+        out.extend(new.map(|var| format!("+ {}={}", var.key, shown(var, show_secrets))));
+    }
+    out
+}
+
+/// One row per change with its status, key, and the value on each side
+pub fn table(changes: &[Change], show_secrets: bool) -> String {
+    let rows: Vec<[String; 4]> = changes
+        .iter()
+        .map(|change| {
+            let (old, new) = change.sides();
+            [
+                change.status().to_owned(),
+                change.key().to_owned(),
+                old.map(|var| shown(var, show_secrets)).unwrap_or_default(),
+                new.map(|var| shown(var, show_secrets)).unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let header = ["status", "key", "old", "new"].map(str::to_owned);
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for row in [&header].into_iter().chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// An array of `{"key", "status", "old", "new"}` objects, null for a side without the variable
+pub fn json(changes: &[Change], show_secrets: bool) -> json::Value {
+    let value = |var: Option<&Var>| match var {
+        Some(var) => json::Value::String(redact::value(var, show_secrets).to_owned()),
+        None => json::Value::Null,
+    };
+    json::Value::Array(
+        changes
+            .iter()
+            .map(|change| {
+                let (old, new) = change.sides();
+                json::Value::Object(BTreeMap::from([
+                    (
+                        "key".to_owned(),
+                        json::Value::String(change.key().to_owned()),
+                    ),
+                    (
+                        "status".to_owned(),
+                        json::Value::String(change.status().to_owned()),
+                    ),
+                    ("old".to_owned(), value(old)),
+                    ("new".to_owned(), value(new)),
+                ]))
+            })
+            .collect(),
+    )
+}
+
+/// The value as shown in diffs and tables, escaped
+fn shown(var: &Var, show_secrets: bool) -> String {
+    redact::value(var, show_secrets)
+        .escape_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<Var> {
+        pairs
+            .iter()
+            .map(|(key, value)| Var::new((*key).into(), (*value).into(), Origin::CommandLine))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let lhs = vars(&[("A", "1"), ("B", "2"), ("DB_PASSWORD", "x"), ("GONE", "")]);
+        let rhs = vars(&[
+            ("A", "1"),
+            ("B", "two\n"),
+            ("DB_PASSWORD", "y"),
+            ("NEW", "3"),
+        ]);
+        assert_eq!(
+            diff(&lhs, &rhs, false),
+            vec![
+                "- B=2",
+                "+ B=two\\n",
+                "- DB_PASSWORD=****",
+                "+ DB_PASSWORD=****",
+                "- GONE=",
+                "+ NEW=3",
+            ]
+        );
+        assert_eq!(
+            &diff(&lhs, &rhs, true)[2..4],
+            ["- DB_PASSWORD=x", "+ DB_PASSWORD=y"]
+        );
+    }
+
+    #[test]
+    fn test_table_and_json() {
+        let lhs = vars(&[("A", "1"), ("API_TOKEN", "x"), ("GONE", "")]);
+        let rhs = vars(&[("A", "2"), ("API_TOKEN", "y"), ("NEW", "three")]);
+        let changes = changes(&lhs, &rhs);
+        assert_eq!(
+            table(&changes, false),
+            "status   key        old   new\n\
+             changed  A          1     2\n\
+             changed  API_TOKEN  ****  ****\n\
+             removed  GONE\n\
+             added    NEW              three\n"
+        );
+        assert_eq!(
+            json(&changes[1..], false),
+            json::parse(
+                r#"[{"key": "API_TOKEN", "status": "changed", "old": "****", "new": "****"},
+                    {"key": "GONE", "status": "removed", "old": "", "new": null},
+                    {"key": "NEW", "status": "added", "old": null, "new": "three"}]"#
+            )
+            .unwrap()
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! `enw completions SHELL` prints a completion script, generated by clap from the command line
+//! definition. On top of that, bash, zsh and fish complete `.env*` files after `-f` and the
+//! names the env files set after `-u` and as `NAME=`, by running `enw completions --names`.
+//! PowerShell completes the options only.
+
+use clap::{App, Shell};
+
+use crate::BoxError;
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+const BASH: &str = r#"
+_enw_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "$prev" in
+        -f|--file)
+            COMPREPLY=($(compgen -f -X '!*.env*' -- "$cur") $(compgen -d -- "$cur"))
+            return 0
+            ;;
+        -u|--unset)
+            COMPREPLY=($(compgen -W "$(enw completions --names 2>/dev/null)" -- "$cur"))
+            return 0
+            ;;
+    esac
+    if [[ "$cur" =~ ^[A-Z_][A-Z0-9_]*$ ]]; then
+        COMPREPLY=($(compgen -S = -W "$(enw completions --names 2>/dev/null)" -- "$cur"))
+        [[ ${#COMPREPLY[@]} -gt 0 ]] && compopt -o nospace && return 0
+    fi
+    _enw "$@"
+}
+
+complete -F _enw_dynamic -o bashdefault -o default enw
+"#;
+
+const ZSH_NAMES: &str = r#"
+_enw_names() {
+    local -a names
+    names=(${(f)"$(enw completions --names 2>/dev/null)"})
+    compadd -a names
+    compadd -S = -a names
+}
+"#;
+
+const FISH: &str = r#"
+complete -c enw -n "__fish_use_subcommand" -s f -l file -F -a "(__fish_complete_suffix .env)"
+complete -c enw -n "__fish_use_subcommand" -s u -l unset -f -a "(enw completions --names 2>/dev/null)"
+complete -c enw -n "__fish_use_subcommand" -a "(enw completions --names 2>/dev/null | string replace -r '\$' =)"
+"#;
+
+/// The completion script for `shell`
+pub fn script(mut app: App, shell: &str) -> Result<String, BoxError> {
+    let generator = match shell {
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        "fish" => Shell::Fish,
+        "powershell" => Shell::PowerShell,
+        _ => return Err(format!("unsupported shell: {shell}").into()),
+    };
+    let mut out = Vec::new();
+    app.gen_completions_to("enw", generator, &mut out);
+    let script = String::from_utf8(out)?;
+    Ok(match shell {
+        "bash" => script + BASH,
+        "zsh" => zsh(&script),
+        "fish" => script + FISH,
+        _ => script,
+    })
+}
+
+/// Give `-f` and `-u` of the top level command actions, which clap leaves empty
+fn zsh(script: &str) -> String {
+    let mut out = String::new();
+    let mut top_level = true;
+    for line in script.lines() {
+        if line.starts_with("_enw \"$@\"") {
+            out.push_str(ZSH_NAMES);
+        }
+        let action = match line {
+            _ if !top_level => None,
+            _ if line.starts_with("'*-f+[") || line.starts_with("'*--file=[") => {
+                Some(":FILE:_files -g '\\''*.env*'\\''")
+            }
+            _ if line.starts_with("'*-u+[") || line.starts_with("'*--unset=[") => {
+                Some(":NAME:_enw_names")
+            }
+            _ => None,
+        };
+        match action.zip(line.strip_suffix("]' \\")) {
+            Some((action, start)) => {
+                out.push_str(start);
+                out.push(']');
+                out.push_str(action);
+                out.push_str("' \\");
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+        if line.starts_with("&& ret=0") {
+            top_level = false;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, SubCommand};
+
+    use super::*;
+
+    fn app() -> App<'static, 'static> {
+        App::new("enw")
+            .arg(
+                Arg::with_name("file")
+                    .short("f")
+                    .long("file")
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .short("u")
+                    .long("unset")
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+            .subcommand(
+                SubCommand::with_name("diff").arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+            )
+    }
+
+    #[test]
+    fn test_script() {
+        let bash = script(app(), "bash").unwrap();
+        assert!(bash.contains("complete -F _enw_dynamic"));
+        let zsh = script(app(), "zsh").unwrap();
+        assert!(zsh.contains("'*-f+[]:FILE:_files -g '\\''*.env*'\\''' \\\n"));
+        assert!(zsh.contains("'*--unset=[]:NAME:_enw_names' \\\n"));
+        assert!(zsh.contains("'*--file=[]' \\\n"), "only the top level -f");
+        assert!(zsh.contains("_enw_names() {"));
+        assert!(zsh.ends_with("_enw \"$@\"\n"));
+        assert!(script(app(), "fish").unwrap().contains("-s u -l unset -f"));
+        assert!(script(app(), "powershell").is_ok());
+        assert!(script(app(), "tcsh").is_err());
+    }
+}
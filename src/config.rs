@@ -0,0 +1,160 @@
+//! Defaults for the command line, read from `config.toml` in the enw config directory (or the
+//! file named by `ENW_CONFIG`) and then from the `[defaults]` table of the project's
+//! `.enw.toml`. Settings of the project override the user's, and lists add to them. Environment
+//! variables like `ENW_PROFILE` and flags override both:
+//!
+//! ```toml
+//! no_implicit_env_file = true
+//! profile = "dev"
+//! # Relative to the directory of the config file
+//! files = [".env.shared"]
+//! from = ["vault:secret/app"]
+//! # Names to treat as secrets, on top of the built-in *SECRET*, *TOKEN* etc.
+//! secrets = ["*_PIN"]
+//! ```
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{config_dir, profile, toml, BoxError};
+
+/// Names the user config file, empty to not read one
+pub const CONFIG_VAR: &str = "ENW_CONFIG";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub no_implicit_env_file: Option<bool>,
+    pub profile: Option<String>,
+    /// Env files to load after the implicit ones
+    pub files: Vec<PathBuf>,
+    /// Provider specs, as given to `--from`
+    pub from: Vec<String>,
+    /// Patterns of variable names to treat as secrets
+    pub secrets: Vec<String>,
+    /// The specs of `from` that the project declares, rather than the user
+    pub project_from: Vec<String>,
+}
+
+/// The user's config merged with the `.enw.toml` in `project_dir`
+pub fn load(project_dir: &Path) -> Result<Config, BoxError> {
+    let mut config = Config::default();
+    let user = match env::var_os(CONFIG_VAR) {
+        Some(path) => Some(PathBuf::from(path)).filter(|path| !path.as_os_str().is_empty()),
+        None => config_dir().map(|dir| dir.join("config.toml")),
+    };
+    if let Some(path) = user {
+        config.merge_file(&path, "")?;
+    }
+    let user_from = config.from.len();
+    config.merge_file(&project_dir.join(profile::CONFIG_FILE_NAME), "defaults")?;
+    config.project_from = config.from[user_from..].to_vec();
+    Ok(config)
+}
+
+impl Config {
+    fn merge_file(&mut self, path: &Path, table: &str) -> Result<(), BoxError> {
+        let text = match fs::read_to_string(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            text => text?,
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        toml::parse(&text)
+            .and_then(|doc| self.merge(&doc, table, dir))
+            .map_err(|e| format!("{}: {e}", path.to_string_lossy()).into())
+    }
+
+    /// Merge in the settings of `table`, resolving files against `dir`
+    fn merge(&mut self, doc: &toml::Document, table: &str, dir: &Path) -> Result<(), BoxError> {
+        let strings = |key: &str, value: &toml::Value| {
+            value
+                .as_strings()
+                .ok_or_else(|| format!("{key} must be a list of strings"))
+        };
+        for (key, value) in doc.get(table).into_iter().flatten() {
+            match (key.as_str(), value) {
+                ("no_implicit_env_file", toml::Value::Bool(value)) => {
+                    self.no_implicit_env_file = Some(*value)
+                }
+                ("profile", toml::Value::String(name)) => {
+                    profile::check_name(name)?;
+                    self.profile = Some(name.clone());
+                }
+                ("files", value) => self
+                    .files
+                    .extend(strings(key, value)?.into_iter().map(|file| dir.join(file))),
+                ("from", value) => self.from.extend(strings(key, value)?),
+                ("secrets", value) => self.secrets.extend(strings(key, value)?),
+                ("no_implicit_env_file" | "profile", _) => {
+                    return Err(format!("{key} has the wrong type").into())
+                }
+                _ => return Err(format!("unknown setting {key}").into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let user = "
+            no_implicit_env_file = true
+            profile = \"dev\"
+            files = [\".env.shared\"]
+            secrets = [\"*_PIN\"]
+        ";
+        let project = "
+            [defaults]
+            profile = \"test\"
+            from = [\"vault:secret/app\"]
+            secrets = [\"SALT\"]
+
+            [profiles.test]
+            files = [\".env.test\"]
+        ";
+        let mut config = Config::default();
+        config
+            .merge(
+                &toml::parse(user).unwrap(),
+                "",
+                Path::new("/home/u/.config/enw"),
+            )
+            .unwrap();
+        config
+            .merge(
+                &toml::parse(project).unwrap(),
+                "defaults",
+                Path::new("/app"),
+            )
+            .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                no_implicit_env_file: Some(true),
+                profile: Some("test".to_owned()),
+                files: vec![PathBuf::from("/home/u/.config/enw/.env.shared")],
+                from: vec!["vault:secret/app".to_owned()],
+                secrets: vec!["*_PIN".to_owned(), "SALT".to_owned()],
+                project_from: Vec::new(),
+            }
+        );
+        for (text, expected) in [
+            ("profile = 1", "profile has the wrong type"),
+            ("files = \"a\"", "files must be a list of strings"),
+            ("colour = \"never\"", "unknown setting colour"),
+            ("profile = \"../x\"", "invalid profile name: \"../x\""),
+        ] {
+            let err = Config::default()
+                .merge(&toml::parse(text).unwrap(), "", Path::new(""))
+                .unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Provider tokens kept in the OS keyring, stored by `enw login PROVIDER`. The keyring is
+//! reached through `secret-tool` (libsecret) on Linux and `security` on macOS, with the token
+//! passed on stdin so it does not show up in the process list.
+
+use std::{
+    env,
+    io::{self, BufRead, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use crate::{http, BoxError};
+
+const SERVICE: &str = "enw";
+
+/// Providers `enw login` knows how to authenticate with
+pub const PROVIDERS: &[&str] = &["vault", "doppler", "vercel"];
+
+/// The provider's token: `env_var` when set, else the one stored by `enw login`
+pub fn token(provider: &str, env_var: &str) -> Result<String, BoxError> {
+    if let Ok(token) = env::var(env_var) {
+        return Ok(token);
+    }
+    lookup(provider)?.ok_or_else(|| {
+        format!("{provider}: {env_var} is not set, and no token stored by `enw login {provider}`")
+            .into()
+    })
+}
+
+/// Run the provider's authentication flow and store the resulting token. `args` are passed on
+/// to `vault login`.
+pub fn login(provider: &str, args: &[String]) -> Result<(), BoxError> {
+    let token = match provider {
+        "vault" => {
+            eprintln!("running vault login, the token will be stored in the keyring");
+            let output = Command::new("vault")
+                .args(["login", "-token-only"])
+                .args(args)
+                .stderr(Stdio::inherit())
+                .output()
+                .map_err(|e| format!("could not run vault: {e}"))?;
+            if !output.status.success() {
+                return Err("vault login failed".into());
+            }
+            String::from_utf8(output.stdout)?.trim().to_owned()
+        }
+        "doppler" => {
+            eprintln!("create a service or personal token at https://dashboard.doppler.com");
+            let token = read_secret("Doppler token: ")?;
+            let auth = format!("Bearer {token}");
+            http::get("https://api.doppler.com/v3/me", &[("Authorization", &auth)])
+                .map_err(|e| format!("doppler rejected the token: {e}"))?;
+            token
+        }
+        "vercel" => {
+            eprintln!("create a token at https://vercel.com/account/tokens");
+            let token = read_secret("Vercel token: ")?;
+            let auth = format!("Bearer {token}");
+            http::get(
+                "https://api.vercel.com/v2/user",
+                &[("Authorization", &auth)],
+            )
+            .map_err(|e| format!("vercel rejected the token: {e}"))?;
+            token
+        }
+        _ => return Err(format!("unknown provider: {provider}").into()),
+    };
+    if token.is_empty() {
+        return Err(format!("{provider}: no token received").into());
+    }
+    store(provider, &token)?;
+    eprintln!("stored {provider} token in the keyring");
+    Ok(())
+}
+
+/// Remove the stored token
+pub fn forget(provider: &str) -> Result<(), BoxError> {
+    let status = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["delete-generic-password", "-s", SERVICE, "-a", provider])
+            .stdout(Stdio::null())
+            .status()
+    } else {
+        Command::new("secret-tool")
+            .args(["clear", "service", SERVICE, "account", provider])
+            .status()
+    };
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err(format!("no {provider} token stored").into()),
+        Err(e) => Err(keyring_missing(e)),
+    }
+}
+
+fn lookup(provider: &str) -> Result<Option<String>, BoxError> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-w", "-s", SERVICE, "-a", provider])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", SERVICE, "account", provider])
+            .stderr(Stdio::null())
+            .output()
+    };
+    match output {
+        Ok(output) if output.status.success() => Ok(Some(
+            String::from_utf8(output.stdout)?
+                .trim_end_matches('\n')
+                .to_owned(),
+        )),
+        Ok(_) => Ok(None),
+        // Without a keyring nothing can have been stored
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn store(provider: &str, token: &str) -> Result<(), BoxError> {
+    let (mut cmd, input) = if cfg!(target_os = "macos") {
+        // Interactive mode reads the command, token included, from stdin
+        let mut cmd = Command::new("security");
+        cmd.arg("-i");
+        (cmd, security_command(provider, token))
+    } else {
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["store", "--label", &format!("enw {provider} token")])
+            .args(["service", SERVICE, "account", provider]);
+        (cmd, token.to_owned())
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(keyring_missing)?;
+    child
+        .stdin
+        .take()
+        .ok_or("could not open keyring stdin")?
+        .write_all(input.as_bytes())?;
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(format!("could not store the {provider} token in the keyring").into())
+    }
+}
+
+fn keyring_missing(e: io::Error) -> BoxError {
+    if e.kind() == io::ErrorKind::NotFound {
+        let tool = if cfg!(target_os = "macos") {
+            "security"
+        } else {
+            "secret-tool (libsecret-tools)"
+        };
+        format!("no keyring access, {tool} not found").into()
+    } else {
+        e.into()
+    }
+}
+
+/// An `add-generic-password` line for `security -i`, which splits words like a shell
+fn security_command(provider: &str, token: &str) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        quote(SERVICE),
+        quote(provider),
+        quote(token)
+    )
+}
+
+/// Read a line from stdin, with echo turned off when it is a terminal
+fn read_secret(prompt: &str) -> Result<String, BoxError> {
+    eprint!("{prompt}");
+    let stdin = io::stdin();
+    let mut saved = None;
+    if stdin.is_terminal() {
+        // SAFETY: termios is plain data, filled in by tcgetattr before use
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                saved = Some(termios);
+                termios.c_lflag &= !libc::ECHO;
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            }
+        }
+    }
+    let mut line = String::new();
+    let result = stdin.lock().read_line(&mut line);
+    if let Some(termios) = saved {
+        // SAFETY: restores the attributes read above
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+        eprintln!();
+    }
+    result?;
+    Ok(line.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_security_command_quotes() {
+        assert_eq!(
+            security_command("vercel", r#"a"b\c d"#),
+            "add-generic-password -U -s \"enw\" -a \"vercel\" -w \"a\\\"b\\\\c d\"\n"
+        );
+    }
+}
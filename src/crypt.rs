@@ -0,0 +1,207 @@
+//! Encrypted env files, e.g. `.env.enc`, which are decrypted when loaded. Three schemes are
+//! recognized by their contents:
+//!
+//! - age, to the recipients listed in `.enw-recipients`, decrypted with the identity in
+//!   `ENW_AGE_IDENTITY` or `age.key` in the enw config directory
+//! - age with a shared key, an age identity (`AGE-SECRET-KEY-1...`) taken from `ENW_KEY` or
+//!   the file named by `ENW_KEY_FILE`, used both to encrypt and to decrypt
+//! - sops dotenv files, decrypted by `sops` with its own key configuration
+//!
+//! Files encrypted by `openssl enc`, as shared keys once were, are refused: CBC without a MAC
+//! lets anyone who can change the file tamper with it undetected.
+
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{config_dir, secret_file, BoxError};
+
+pub const RECIPIENTS_FILE_NAME: &str = ".enw-recipients";
+pub const EXTENSION: &str = "enc";
+const AGE_HEADER: &[u8] = b"age-encryption.org/";
+const AGE_ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+const OPENSSL_HEADER: &[u8] = b"Salted__";
+const SOPS_MARKER: &[u8] = b"\nsops_mac=";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Age,
+    Key,
+}
+
+impl Method {
+    pub const NAMES: &'static [&'static str] = &["age", "key"];
+}
+
+impl std::str::FromStr for Method {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "age" => Ok(Method::Age),
+            "key" => Ok(Method::Key),
+            _ => Err(format!("unknown encryption method: {s}").into()),
+        }
+    }
+}
+
+/// Whether the file is loaded through [`decrypt`]
+pub fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+pub fn encrypt(method: Method, data: &[u8]) -> Result<Vec<u8>, BoxError> {
+    match method {
+        Method::Age => age_encrypt(data),
+        Method::Key => {
+            let key = key()?.ok_or("set ENW_KEY or ENW_KEY_FILE to an age identity")?;
+            with_identity(&key, |identity| {
+                pipe(
+                    Command::new("age").arg("--encrypt").arg("-i").arg(identity),
+                    data,
+                )
+            })
+        }
+    }
+}
+
+/// Decrypt the contents of an encrypted file, picking the scheme from its header
+pub fn decrypt(path: &Path, data: &[u8]) -> Result<Vec<u8>, BoxError> {
+    if data.starts_with(AGE_HEADER) || data.starts_with(AGE_ARMOR_HEADER) {
+        age_decrypt(data)
+    } else if data.starts_with(OPENSSL_HEADER) {
+        Err(format!(
+            "{}: encrypted with openssl enc, which is not authenticated and no longer supported; \
+             decrypt it with openssl and encrypt it again with enw encrypt",
+            path.to_string_lossy()
+        )
+        .into())
+    } else if data.windows(SOPS_MARKER.len()).any(|w| w == SOPS_MARKER) {
+        pipe(
+            Command::new("sops").args([
+                "--decrypt",
+                "--input-type",
+                "dotenv",
+                "--output-type",
+                "dotenv",
+                // The bytes already read, which may have been fetched, and were trust-hashed
+                "/dev/stdin",
+            ]),
+            data,
+        )
+    } else {
+        Err(format!(
+            "{}: not encrypted with age, openssl or sops",
+            path.to_string_lossy()
+        )
+        .into())
+    }
+}
+
+pub fn age_encrypt(data: &[u8]) -> Result<Vec<u8>, BoxError> {
+    if !Path::new(RECIPIENTS_FILE_NAME).is_file() {
+        return Err(format!(
+            "{RECIPIENTS_FILE_NAME} not found, list the team's age public keys in it"
+        )
+        .into());
+    }
+    pipe(
+        Command::new("age").args(["--encrypt", "-R", RECIPIENTS_FILE_NAME]),
+        data,
+    )
+}
+
+/// The identity is taken from `ENW_AGE_IDENTITY`, or `age.key` in the enw config directory.
+/// The shared key, when set, is tried as well.
+pub fn age_decrypt(data: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let identity = env::var_os("ENW_AGE_IDENTITY")
+        .map(PathBuf::from)
+        .or_else(|| config_dir().map(|dir| dir.join("age.key")))
+        .filter(|path| path.is_file());
+    let mut cmd = Command::new("age");
+    cmd.arg("--decrypt");
+    if let Some(identity) = &identity {
+        cmd.arg("-i").arg(identity);
+    }
+    match key()? {
+        Some(key) => with_identity(&key, |shared| pipe(cmd.arg("-i").arg(shared), data)),
+        None if identity.is_some() => pipe(&mut cmd, data),
+        None => Err(
+            "no age identity, set ENW_AGE_IDENTITY or ENW_KEY, or create age.key in the \
+                     enw config dir"
+                .into(),
+        ),
+    }
+}
+
+/// The shared key, if set
+fn key() -> Result<Option<String>, BoxError> {
+    if let Ok(key) = env::var("ENW_KEY") {
+        return Ok(Some(key));
+    }
+    let Some(path) = env::var_os("ENW_KEY_FILE") else {
+        return Ok(None);
+    };
+    let key = std::fs::read_to_string(&path)
+        .map_err(|e| format!("{}: {e}", Path::new(&path).to_string_lossy()))?;
+    Ok(Some(key.trim_end_matches('\n').to_owned()))
+}
+
+/// Call `f` with a path age can read the identity from: a pipe rather than a file, so that the
+/// key is never written to disk or shown in age's arguments
+fn with_identity<T>(key: &str, f: impl FnOnce(&str) -> Result<T, BoxError>) -> Result<T, BoxError> {
+    let fd = secret_file::write_pipe(&format!("{key}\n"))?;
+    let result = f(&format!("/dev/fd/{fd}"));
+    // SAFETY: fd was opened by write_pipe, and is not used after this
+    unsafe { libc::close(fd) };
+    result
+}
+
+/// Run `cmd` with `input` on stdin, returning its stdout
+pub fn pipe(cmd: &mut Command, input: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {program}: {e}"))?;
+    let mut stdin = child.stdin.take().ok_or("could not open stdin")?;
+    let input = input.to_vec();
+    // Written from another thread, so a child filling its stdout pipe cannot deadlock us
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().map_err(|_| "writer thread panicked")??;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "{program}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_decrypt_rejects_plain_files() {
+        assert!(is_encrypted(Path::new("config/.env.prod.enc")));
+        assert!(!is_encrypted(Path::new(".env.enc.bak")));
+        let err = decrypt(Path::new(".env.enc"), b"KEY=value\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ".env.enc: not encrypted with age, openssl or sops"
+        );
+        let err = decrypt(Path::new(".env.enc"), b"Salted__12345678").unwrap_err();
+        assert!(err.to_string().contains("not authenticated"), "{err}");
+    }
+}
@@ -0,0 +1,61 @@
+//! Variables that change how the command itself is loaded or found. An env file in a cloned
+//! repository setting these, or a profile or provider declared in its `.enw.toml`, could hijack
+//! whatever enw launches.
+
+use std::str::FromStr;
+
+use crate::{glob, BoxError};
+
+const DANGEROUS_KEYS: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "LD_AUDIT",
+    "DYLD_*",
+    "PATH",
+];
+
+/// What to do when an untrusted env file sets a dangerous variable
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl Policy {
+    pub const NAMES: &'static [&'static str] = &["allow", "warn", "deny"];
+}
+
+impl FromStr for Policy {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Policy::Allow),
+            "warn" => Ok(Policy::Warn),
+            "deny" => Ok(Policy::Deny),
+            _ => Err(format!("unknown policy: {s}").into()),
+        }
+    }
+}
+
+pub fn is_dangerous(key: &str) -> bool {
+    DANGEROUS_KEYS
+        .iter()
+        .any(|pattern| glob::matches(pattern, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_dangerous() {
+        assert!(is_dangerous("LD_PRELOAD"));
+        assert!(is_dangerous("DYLD_INSERT_LIBRARIES"));
+        assert!(is_dangerous("PATH"));
+        assert!(!is_dangerous("MY_PATH"));
+        assert!(!is_dangerous("LD_FLAGS"));
+    }
+}
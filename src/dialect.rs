@@ -0,0 +1,269 @@
+//! Parsing rules matching other tools that read env files, selected with `--dialect`:
+//! `compose` for docker compose, `dotenv` for Node's dotenv and `shell` for POSIX shells. Each
+//! module documents its rules; what they share is the `${NAME:-default}` family of expansions.
+
+pub mod compose;
+pub mod node;
+pub mod shell;
+
+use crate::BoxError;
+
+type Entries = Vec<Result<(String, String), BoxError>>;
+
+type Resolve<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// The characters of a document, counting lines from 1
+struct Cursor<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Cursor {
+            rest: text,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.next();
+        }
+    }
+
+    /// Skip past the end of the line, returning what was on it
+    fn skip_line(&mut self) -> &'a str {
+        let (line, rest) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+        if self.rest.len() > line.len() {
+            self.line += 1;
+        }
+        self.rest = rest;
+        line
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c == '_' || c.is_ascii_alphabetic()
+}
+
+fn is_name_char(c: char) -> bool {
+    c == '_' || c.is_ascii_alphanumeric()
+}
+
+/// Expand what follows a `$`: `NAME`, `{NAME}` or `{NAME<op>WORD}`, where op is one of `-`, `=`,
+/// `+` and `?`, optionally preceded by `:` to treat empty like unset. None when no name follows.
+fn expand(cursor: &mut Cursor, resolve: Resolve) -> Result<Option<String>, BoxError> {
+    match cursor.peek() {
+        Some('{') => {
+            cursor.next();
+            let mut inner = String::new();
+            let mut depth = 0;
+            loop {
+                match cursor.next() {
+                    Some('}') if depth == 0 => break,
+                    Some(c) => {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                        inner.push(c);
+                    }
+                    None => return Err(format!("unterminated ${{{inner}").into()),
+                }
+            }
+            braced(&inner, resolve).map(Some)
+        }
+        Some(c) if is_name_start(c) => {
+            let mut name = String::new();
+            while let Some(c) = cursor.peek().filter(|&c| is_name_char(c)) {
+                name.push(c);
+                cursor.next();
+            }
+            Ok(Some(resolve(&name).unwrap_or_default()))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn braced(inner: &str, resolve: Resolve) -> Result<String, BoxError> {
+    let bad = || format!("bad substitution: ${{{inner}}}");
+    let end = inner.find(|c| !is_name_char(c)).unwrap_or(inner.len());
+    let (name, rest) = inner.split_at(end);
+    if !name.starts_with(is_name_start) {
+        return Err(bad().into());
+    }
+    let value = resolve(name);
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut chars = rest.chars();
+    let Some(op) = chars.next() else {
+        return match colon {
+            true => Err(bad().into()),
+            false => Ok(value.unwrap_or_default()),
+        };
+    };
+    let word = chars.as_str();
+    let set = match colon {
+        true => value.as_deref().is_some_and(|value| !value.is_empty()),
+        false => value.is_some(),
+    };
+    match (op, set) {
+        ('-' | '=' | '?', true) => Ok(value.unwrap_or_default()),
+        ('-' | '=', false) => expand_word(word, resolve),
+        ('+', true) => expand_word(word, resolve),
+        ('+', false) => Ok(String::new()),
+        ('?', false) => match word {
+            "" => Err(format!("{name}: parameter not set").into()),
+            word => Err(format!("{name}: {}", expand_word(word, resolve)?).into()),
+        },
+        _ => Err(bad().into()),
+    }
+}
+
+/// Expand the variables in the word of a `${NAME<op>WORD}`, `\` escaping the next character
+fn expand_word(word: &str, resolve: Resolve) -> Result<String, BoxError> {
+    let mut cursor = Cursor::new(word);
+    let mut out = String::new();
+    while let Some(c) = cursor.next() {
+        match c {
+            '\\' => out.extend(cursor.next()),
+            '$' => match expand(&mut cursor, resolve)? {
+                Some(value) => out.push_str(&value),
+                None => out.push('$'),
+            },
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/u".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_braced() {
+        let cases = [
+            ("HOME", "/home/u"),
+            ("NOPE", ""),
+            ("NOPE:-a", "a"),
+            ("EMPTY:-a", "a"),
+            ("EMPTY-a", ""),
+            ("HOME:+set", "set"),
+            ("EMPTY+set", "set"),
+            ("EMPTY:+set", ""),
+            ("NOPE:-${HOME}/x", "/home/u/x"),
+            ("NOPE:-\\$HOME", "$HOME"),
+        ];
+        for (inner, expected) in cases {
+            assert_eq!(braced(inner, &lookup).unwrap(), expected, "${{{inner}}}");
+        }
+        assert_eq!(
+            braced("NOPE:?is required", &lookup)
+                .unwrap_err()
+                .to_string(),
+            "NOPE: is required"
+        );
+        for inner in ["", "1A", "HOME:", "HOME%x"] {
+            assert!(braced(inner, &lookup).is_err(), "${{{inner}}}");
+        }
+    }
+
+    /// The value of `A` read by compose, dotenv and shell, None where the dialect rejects it
+    type Expected = (
+        Option<&'static str>,
+        Option<&'static str>,
+        Option<&'static str>,
+    );
+
+    #[test]
+    fn test_conformance() {
+        #[rustfmt::skip]
+        let cases: &[(&str, Expected)] = &[
+            // document              compose             dotenv                shell
+            ("A=plain",              (Some("plain"),      Some("plain"),        Some("plain"))),
+            ("export A=1",           (Some("1"),          Some("1"),            Some("1"))),
+            ("A=",                   (Some(""),           Some(""),             Some(""))),
+            ("A=b#c",                (Some("b#c"),        Some("b"),            Some("b#c"))),
+            ("A=b #c",               (Some("b"),          Some("b"),            Some("b"))),
+            ("A=b\t#c",              (Some("b\t#c"),      Some("b"),            Some("b"))),
+            ("A=\"b #c\" # d",       (Some("b #c"),       Some("b #c"),         Some("b #c"))),
+            ("A=x y",                (Some("x y"),        Some("x y"),          None)),
+            ("A= x ",                (Some("x"),          Some("x"),            None)),
+            ("A = x",                (Some("x"),          Some("x"),            None)),
+            ("A: x",                 (Some("x"),          Some("x"),            None)),
+            ("A=\"a\\nb\"",          (Some("a\nb"),       Some("a\nb"),         Some("a\\nb"))),
+            ("A='a\\nb'",            (Some("a\\nb"),      Some("a\\nb"),        Some("a\\nb"))),
+            ("A=a\\nb",              (Some("a\\nb"),      Some("a\\nb"),        Some("anb"))),
+            ("A=a\\ b",              (Some("a\\ b"),      Some("a\\ b"),        Some("a b"))),
+            ("A=\"say \\\"hi\\\"\"", (Some("say \"hi\""), Some("say \\\"hi\\\""), Some("say \"hi\""))),
+            ("A=\"a\nb\"",           (Some("a\nb"),       Some("a\nb"),         Some("a\nb"))),
+            ("A=`tick`",             (Some("`tick`"),     Some("tick"),         None)),
+            ("A=$HOME/x",            (Some("/home/u/x"),  Some("$HOME/x"),      Some("/home/u/x"))),
+            ("A=\"${HOME}\"",        (Some("/home/u"),    Some("${HOME}"),      Some("/home/u"))),
+            ("A='$HOME'",            (Some("$HOME"),      Some("$HOME"),        Some("$HOME"))),
+            ("A=${NOPE:-dflt}",      (Some("dflt"),       Some("${NOPE:-dflt}"), Some("dflt"))),
+            ("A=${NOPE:?missing}",   (None,               Some("${NOPE:?missing}"), None)),
+            ("A=$$",                 (Some("$"),          Some("$$"),           None)),
+            ("A=5$",                 (None,               Some("5$"),           Some("5$"))),
+            ("A=x\nB=$A",            (Some("x"),          Some("x"),            Some("x"))),
+            ("A=a'b'c",              (Some("a'b'c"),      Some("a'b'c"),        Some("abc"))),
+            ("A='a'b'",              (None,              Some("a'b"),          None)),
+            ("A=~/x",                (Some("~/x"),        Some("~/x"),          Some("/home/u/x"))),
+            ("A=$(id)",              (None,               Some("$(id)"),        None)),
+            ("A=1;B=2",              (Some("1;B=2"),      Some("1;B=2"),        Some("1"))),
+        ];
+        for &(text, (compose, node, shell)) in cases {
+            let first = |entries: Entries| match entries.into_iter().collect::<Result<Vec<_>, _>>()
+            {
+                Ok(entries) => match entries.into_iter().next() {
+                    Some((key, value)) if key == "A" => Some(value),
+                    entry => panic!("{text:?}: unexpected {entry:?}"),
+                },
+                Err(_) => None,
+            };
+            assert_eq!(
+                first(compose::parse(text, &lookup)).as_deref(),
+                compose,
+                "compose: {text:?}"
+            );
+            assert_eq!(
+                first(node::parse(text)).as_deref(),
+                node,
+                "dotenv: {text:?}"
+            );
+            assert_eq!(
+                first(shell::parse(text, &lookup)).as_deref(),
+                shell,
+                "shell: {text:?}"
+            );
+        }
+    }
+}
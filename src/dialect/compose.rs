@@ -0,0 +1,220 @@
+//! Parsing rules of docker compose's `.env` and `env_file` files:
+//!
+//! * Keys may contain `.`, `-`, `[` and `]`, and be followed by `:` instead of `=`. A key alone
+//!   takes its value from the environment, and is skipped when not set there.
+//! * Unquoted values run to the end of the line, trimmed. ` #` starts a comment, `#` alone does
+//!   not, and backslashes are literal.
+//! * Single quoted values are literal. In double quoted values `\n` and `\r` are a newline and
+//!   carriage return, and `\` before any other character escapes it. Quoted values may span lines.
+//! * `$NAME`, `${NAME}` and `${NAME:-default}` style substitutions are expanded in unquoted and
+//!   double quoted values, from the environment first and then from keys defined earlier. `$$`
+//!   is a literal `$`, and any other `$` not followed by a name is an error.
+
+use std::collections::HashMap;
+
+use super::{expand, Cursor, Entries, Resolve};
+use crate::BoxError;
+
+pub fn parse(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Entries {
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut out = Vec::new();
+    let mut cursor = Cursor::new(text);
+    loop {
+        while matches!(cursor.peek(), Some(c) if c.is_whitespace()) {
+            cursor.next();
+        }
+        match cursor.peek() {
+            None => break,
+            Some('#') => {
+                cursor.skip_line();
+                continue;
+            }
+            Some(_) => {}
+        }
+        let line = cursor.line;
+        let resolve = |name: &str| lookup(name).or_else(|| defined.get(name).cloned());
+        match statement(&mut cursor, &resolve, lookup) {
+            Ok(Some((key, value))) => {
+                defined.insert(key.clone(), value.clone());
+                out.push(Ok((key, value)));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                out.push(Err(format!("line {line}: {e}").into()));
+                break;
+            }
+        }
+    }
+    out
+}
+
+/// The next assignment, None for a key alone that is not set
+fn statement(
+    cursor: &mut Cursor,
+    resolve: Resolve,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<Option<(String, String)>, BoxError> {
+    if let Some(rest) = cursor.rest.strip_prefix("export ") {
+        cursor.rest = rest.trim_start_matches([' ', '\t']);
+    }
+    let mut key = String::new();
+    let separated = loop {
+        match cursor.peek() {
+            Some('=' | ':') => {
+                cursor.next();
+                break true;
+            }
+            None | Some('\n') => break false,
+            Some(c) if c.is_whitespace() => {}
+            Some(c) if c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '[' | ']') => {
+                key.push(c)
+            }
+            Some(c) => return Err(format!("unexpected character {c:?} in variable name").into()),
+        }
+        cursor.next();
+    };
+    if !separated {
+        return Ok(lookup(&key).map(|value| (key, value)));
+    }
+    cursor.skip_blanks();
+    let value = match cursor.peek() {
+        Some('\'') => {
+            cursor.next();
+            quoted(cursor, '\'')?.to_owned()
+        }
+        Some('"') => {
+            cursor.next();
+            double_quoted(quoted(cursor, '"')?, resolve)?
+        }
+        _ => {
+            let line = cursor.skip_line();
+            let value = line.split_once(" #").map_or(line, |(value, _)| value);
+            substitute(value.trim_end(), resolve)?
+        }
+    };
+    Ok(Some((key, value)))
+}
+
+/// The raw text up to the closing quote, which is not preceded by a backslash
+fn quoted<'a>(cursor: &mut Cursor<'a>, quote: char) -> Result<&'a str, BoxError> {
+    let start = cursor.rest;
+    let mut previous = quote;
+    while let Some(c) = cursor.next() {
+        if c == quote && previous != '\\' {
+            return Ok(&start[..start.len() - cursor.rest.len() - 1]);
+        }
+        previous = c;
+    }
+    Err(format!("unterminated quoted value: {quote}{start}").into())
+}
+
+fn double_quoted(text: &str, resolve: Resolve) -> Result<String, BoxError> {
+    let mut out = String::new();
+    let mut cursor = Cursor::new(text);
+    while let Some(c) = cursor.next() {
+        match c {
+            '\\' => match cursor.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            '$' => dollar(&mut cursor, resolve, &mut out, text)?,
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn substitute(text: &str, resolve: Resolve) -> Result<String, BoxError> {
+    let mut out = String::new();
+    let mut cursor = Cursor::new(text);
+    while let Some(c) = cursor.next() {
+        match c {
+            '$' => dollar(&mut cursor, resolve, &mut out, text)?,
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn dollar(
+    cursor: &mut Cursor,
+    resolve: Resolve,
+    out: &mut String,
+    text: &str,
+) -> Result<(), BoxError> {
+    if cursor.peek() == Some('$') {
+        cursor.next();
+        out.push('$');
+        return Ok(());
+    }
+    match expand(cursor, resolve)? {
+        Some(value) => out.push_str(&value),
+        None => return Err(format!("invalid interpolation format: {text}").into()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse_ok(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+        parse(text, lookup)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let lookup = |name: &str| match name {
+            "TAG" => Some("v1".to_owned()),
+            "FROM_ENV" => Some("env".to_owned()),
+            _ => None,
+        };
+        let text = "
+            # services
+            IMAGE=app:${TAG:-latest}
+            TAG=v2
+            IMAGE_AGAIN=app:$TAG
+            spring.profiles-active[0]=dev
+            FROM_ENV
+            NOT_SET
+            MULTI=\"first
+            second\"
+            SINGLE='${TAG} \\'' # quoted
+        ";
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        assert_eq!(
+            parse_ok(text, &lookup),
+            vec![
+                owned("IMAGE", "app:v1"),
+                owned("TAG", "v2"),
+                owned("IMAGE_AGAIN", "app:v1"),
+                owned("spring.profiles-active[0]", "dev"),
+                owned("FROM_ENV", "env"),
+                owned("MULTI", "first\n            second"),
+                owned("SINGLE", "${TAG} \\'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let lookup = |_: &str| None;
+        for text in ["A=\"open", "A='open", "A=${OPEN", "A!=x", "A=${B:?}"] {
+            assert!(parse(text, &lookup)[0].is_err(), "expected err: {text}");
+        }
+        assert_eq!(
+            parse("A=1\n\nB=${:-x}", &lookup)[1]
+                .as_ref()
+                .unwrap_err()
+                .to_string(),
+            "line 3: bad substitution: ${:-x}"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Parsing rules of Node's dotenv package (v16):
+//!
+//! * Lines that are not assignments are ignored. Keys are letters, digits, `_`, `.` and `-`,
+//!   followed by `=`, or by `:` and whitespace.
+//! * `#` starts a comment anywhere in unquoted values, which are trimmed.
+//! * Values may be quoted with `'`, `"` or `` ` ``, and span lines then. Nothing but a comment
+//!   may follow the closing quote, or the value is read as unquoted, losing only an outer pair of
+//!   matching quotes.
+//! * In double quoted values `\n` and `\r` are a newline and carriage return. There are no other
+//!   escapes, and nothing is expanded.
+
+use super::Entries;
+
+pub fn parse(text: &str) -> Entries {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut out = Vec::new();
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        match assignment(rest) {
+            Some((key, value, after)) => {
+                out.push(Ok((key.to_owned(), value)));
+                rest = after;
+            }
+            None => rest = rest.split_once('\n').map_or("", |(_, after)| after),
+        }
+    }
+    out
+}
+
+/// The assignment at the start of `text`, and the text after its last line
+fn assignment(text: &str) -> Option<(&str, String, &str)> {
+    let blanks: &[char] = &[' ', '\t'];
+    let text = text.trim_start_matches(blanks);
+    let text = match text.strip_prefix("export") {
+        Some(rest) if rest.starts_with(blanks) => rest.trim_start_matches(blanks),
+        _ => text,
+    };
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(text.len());
+    let (key, rest) = text.split_at(end);
+    if key.is_empty() {
+        return None;
+    }
+    let rest = rest.trim_start_matches(blanks);
+    let rest = match rest.strip_prefix('=') {
+        Some(rest) => rest,
+        None => rest
+            .strip_prefix(':')
+            .filter(|rest| rest.starts_with(blanks))?,
+    };
+    let rest = rest.trim_start_matches(blanks);
+    if let Some((value, after)) = quoted(rest) {
+        return Some((key, value, after));
+    }
+    let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+    let value = line.split('#').next().unwrap_or_default().trim();
+    let quote = value
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '\'' | '"' | '`'));
+    let value = match quote {
+        Some(quote) if value.len() > 1 && value.ends_with(quote) => &value[1..value.len() - 1],
+        _ => value,
+    };
+    Some((key, unescape(value, quote), after))
+}
+
+/// A value in quotes followed by nothing but a comment on its last line. A backslash before
+/// the quote does not close it.
+fn quoted(text: &str) -> Option<(String, &str)> {
+    let quote = text
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '\'' | '"' | '`'))?;
+    let mut chars = text.char_indices().skip(1).peekable();
+    let end = loop {
+        match chars.next()? {
+            (_, '\\') if chars.peek().is_some_and(|&(_, c)| c == quote) => {
+                chars.next();
+            }
+            (i, c) if c == quote => break i,
+            _ => {}
+        }
+    };
+    let (line, after) = text[end + 1..]
+        .split_once('\n')
+        .unwrap_or((&text[end + 1..], ""));
+    let line = line.trim_start_matches([' ', '\t']);
+    if !(line.is_empty() || line.starts_with('#')) {
+        return None;
+    }
+    Some((unescape(&text[1..end], Some(quote)), after))
+}
+
+fn unescape(value: &str, quote: Option<char>) -> String {
+    match quote {
+        Some('"') => value.replace("\\n", "\n").replace("\\r", "\r"),
+        _ => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "
+            # ignored, as is the next line
+            not an assignment
+            BASIC=basic # comment
+            export EXPORTED=yes
+            YAML: style
+            app.name-1='single #1'
+            MULTI=\"first\\nsecond
+            third\"
+            BACKTICK=`it's \"quoted\"`
+            TRAILING=\"x\" y
+            EMPTY=
+        ";
+        let entries: Vec<_> = parse(text).into_iter().map(Result::unwrap).collect();
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        assert_eq!(
+            entries,
+            vec![
+                owned("BASIC", "basic"),
+                owned("EXPORTED", "yes"),
+                owned("YAML", "style"),
+                owned("app.name-1", "single #1"),
+                owned("MULTI", "first\nsecond\n            third"),
+                owned("BACKTICK", "it's \"quoted\""),
+                owned("TRAILING", "\"x\" y"),
+                owned("EMPTY", ""),
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Parsing rules of POSIX shells, reading the file as `sh` would read a series of assignments:
+//!
+//! * `NAME=VALUE`, with no space around `=`. Several may share a line, separated by blanks or
+//!   `;`, and `export` may precede them. Any other word is an error, as a shell would run it.
+//! * Unquoted, `\` escapes the next character and blanks end the value. `#` starts a comment only
+//!   at the start of a word.
+//! * Single quotes are literal. In double quotes `\` only escapes `$`, `` ` ``, `"`, `\` and the
+//!   newline. Quotes may span lines and be joined with other parts of the value: `a'b'"c"`.
+//! * `$NAME`, `${NAME}` and `${NAME:-default}` style expansions apply outside single quotes, from
+//!   keys defined earlier and then from `lookup`, and so does `~` at the start of the value or
+//!   after a `:`. Command substitution and special parameters like `$1` are not supported.
+
+use std::collections::HashMap;
+
+use super::{expand, Cursor, Entries, Resolve};
+use crate::BoxError;
+
+pub fn parse(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Entries {
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut out = Vec::new();
+    let mut cursor = Cursor::new(text);
+    let mut exporting = false;
+    loop {
+        while let Some(c) = cursor.peek().filter(|c| c.is_whitespace() || *c == ';') {
+            if matches!(c, '\n' | ';') {
+                exporting = false;
+            }
+            cursor.next();
+        }
+        match cursor.peek() {
+            None => break,
+            Some('#') => {
+                cursor.skip_line();
+                continue;
+            }
+            Some(_) => {}
+        }
+        let line = cursor.line;
+        let resolve = |name: &str| defined.get(name).cloned().or_else(|| lookup(name));
+        match statement(&mut cursor, &resolve, &mut exporting) {
+            Ok(Some((key, value))) => {
+                defined.insert(key.clone(), value.clone());
+                out.push(Ok((key, value)));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                out.push(Err(format!("line {line}: {e}").into()));
+                break;
+            }
+        }
+    }
+    out
+}
+
+/// The next assignment, None for `export` and the names it exports without assigning
+fn statement(
+    cursor: &mut Cursor,
+    resolve: Resolve,
+    exporting: &mut bool,
+) -> Result<Option<(String, String)>, BoxError> {
+    let mut name = String::new();
+    if cursor.peek().is_some_and(super::is_name_start) {
+        while let Some(c) = cursor.peek().filter(|&c| super::is_name_char(c)) {
+            name.push(c);
+            cursor.next();
+        }
+    }
+    match cursor.peek() {
+        Some('=') if !name.is_empty() => {
+            cursor.next();
+            Ok(Some((name, word(cursor, resolve)?)))
+        }
+        None | Some(' ' | '\t' | '\r' | '\n' | ';') if name == "export" || *exporting => {
+            *exporting = true;
+            Ok(None)
+        }
+        _ => {
+            let rest = cursor.skip_line();
+            Err(format!("expected NAME=VALUE, a shell would run {name}{rest}").into())
+        }
+    }
+}
+
+/// The value of an assignment, up to the first unquoted blank
+fn word(cursor: &mut Cursor, resolve: Resolve) -> Result<String, BoxError> {
+    let mut out = String::new();
+    let mut tilde = true;
+    loop {
+        let c = match cursor.peek() {
+            None | Some(' ' | '\t' | '\r' | '\n' | ';') => return Ok(out),
+            Some(c) => c,
+        };
+        cursor.next();
+        match c {
+            '\\' => match cursor.next() {
+                Some('\n') | None => {}
+                Some(c) => out.push(c),
+            },
+            '\'' => loop {
+                match cursor.next() {
+                    Some('\'') => break,
+                    Some(c) => out.push(c),
+                    None => return Err("unterminated single quote".into()),
+                }
+            },
+            '"' => loop {
+                match cursor.next() {
+                    Some('"') => break,
+                    Some('\\') => match cursor.peek() {
+                        Some('\n') => {
+                            cursor.next();
+                        }
+                        Some(c @ ('$' | '`' | '"' | '\\')) => {
+                            cursor.next();
+                            out.push(c);
+                        }
+                        _ => out.push('\\'),
+                    },
+                    Some('$') => dollar(cursor, resolve, &mut out)?,
+                    Some('`') => return Err("command substitution is not supported".into()),
+                    Some(c) => out.push(c),
+                    None => return Err("unterminated double quote".into()),
+                }
+            },
+            '$' => dollar(cursor, resolve, &mut out)?,
+            '~' if tilde
+                && matches!(
+                    cursor.peek(),
+                    None | Some('/' | ':' | ' ' | '\t' | '\r' | '\n' | ';')
+                ) =>
+            {
+                out.push_str(&resolve("HOME").unwrap_or_default())
+            }
+            '`' => return Err("command substitution is not supported".into()),
+            '|' | '&' | '<' | '>' | '(' | ')' => {
+                return Err(format!("unexpected {c:?}, quote it to use it in a value").into())
+            }
+            c => out.push(c),
+        }
+        tilde = c == ':';
+    }
+}
+
+fn dollar(cursor: &mut Cursor, resolve: Resolve, out: &mut String) -> Result<(), BoxError> {
+    match cursor.peek() {
+        Some('(') => return Err("command substitution is not supported".into()),
+        Some(c) if c.is_ascii_digit() || "$?!#*@-".contains(c) => {
+            return Err(format!("special parameter ${c} is not supported").into())
+        }
+        _ => {}
+    }
+    match expand(cursor, resolve)? {
+        Some(value) => out.push_str(&value),
+        None => out.push('$'),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/u".to_owned()),
+            _ => None,
+        };
+        let text = r#"
+            # sourced by the deploy script
+            export PATH_LIST=~/bin:~/.local/bin ; NAME='it'"'"'s' # comment
+            export NAME DIR=${HOME}/app
+            GREETING="hello \
+            ${NAME}, \$5 \x" URL=http://x/#top
+            LONG='a
+            b'
+        "#;
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        let entries: Vec<_> = parse(text, &lookup)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                owned("PATH_LIST", "/home/u/bin:/home/u/.local/bin"),
+                owned("NAME", "it's"),
+                owned("DIR", "/home/u/app"),
+                owned("GREETING", "hello             it's, $5 \\x"),
+                owned("URL", "http://x/#top"),
+                owned("LONG", "a\n            b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let lookup = |_: &str| None;
+        for text in [
+            "A=\"open",
+            "A='open",
+            "A=$(date)",
+            "A=`date`",
+            "A=$1",
+            "A=a|b",
+            "echo hi",
+        ] {
+            assert!(parse(text, &lookup)[0].is_err(), "expected err: {text}");
+        }
+        assert_eq!(
+            parse("A=1\nB=2 rm -rf", &lookup)[2]
+                .as_ref()
+                .unwrap_err()
+                .to_string(),
+            "line 2: expected NAME=VALUE, a shell would run rm -rf"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Recursive discovery: like direnv finds `.envrc`, `enw -r` loads the .env file of every
+//! directory from the current one up to a stop directory, the closest taking precedence.
+
+use std::path::{Path, PathBuf};
+
+use crate::BoxError;
+
+/// Where the walk up the directory tree ends. The stop directory itself is included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// The root of a git repository, a directory holding `.git`
+    Git,
+    /// The home directory
+    Home,
+}
+
+impl Stop {
+    pub const NAMES: &'static [&'static str] = &["git", "home"];
+    pub const DEFAULT: &'static str = "git,home";
+}
+
+impl std::str::FromStr for Stop {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "git" => Ok(Stop::Git),
+            "home" => Ok(Stop::Home),
+            _ => Err(format!("unknown stop condition: {s}").into()),
+        }
+    }
+}
+
+/// The directories from `dir` up to the first one meeting a stop condition, or the file
+/// system root, lowest precedence first
+pub fn env_dirs(dir: &Path, stops: &[Stop], home: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for dir in dir.ancestors() {
+        dirs.push(dir.to_owned());
+        let stop = stops.iter().any(|stop| match stop {
+            Stop::Git => dir.join(".git").exists(),
+            Stop::Home => home == Some(dir),
+        });
+        if stop {
+            break;
+        }
+    }
+    dirs.reverse();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_env_dirs() {
+        let home = env::temp_dir().join(format!("enw-discovery-test-{}", std::process::id()));
+        let repo = home.join("code/repo");
+        let dir = repo.join("src/bin");
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+
+        assert_eq!(
+            env_dirs(&dir, &[Stop::Git, Stop::Home], Some(&home)),
+            vec![repo.clone(), repo.join("src"), dir.clone()]
+        );
+        assert_eq!(
+            env_dirs(&dir, &[Stop::Home], Some(&home)),
+            vec![
+                home.clone(),
+                home.join("code"),
+                repo.clone(),
+                repo.join("src"),
+                dir.clone()
+            ]
+        );
+        assert_eq!(env_dirs(&dir, &[], None).first(), Some(&PathBuf::from("/")));
+        fs::remove_dir_all(home).unwrap();
+    }
+}
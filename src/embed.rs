@@ -0,0 +1,189 @@
+//! The library API, for programs that want enw's .env handling without going through argv:
+//!
+//! ```no_run
+//! let opts = enw::Options::new().file(".env.local").var("RUST_LOG", "debug");
+//! for (key, value) in enw::load_env(&opts).unwrap() {
+//!     println!("{key}={value}");
+//! }
+//! let code = enw::exec(&opts.command("cargo", ["test"])).unwrap();
+//! std::process::exit(code);
+//! ```
+
+use std::{env, path::PathBuf, process::Command};
+
+use crate::{
+    child_environment, exit_code, forward::Forwarding, pipeline::Pipeline, resolve, workspace,
+    BoxError, EnvFile, OptionsBuilder, DEFAULT_ENV_FILE_NAME,
+};
+
+/// What to load, built up like the command line would be
+#[derive(Clone, Debug)]
+pub struct Options {
+    files: Vec<PathBuf>,
+    vars: Vec<(String, String)>,
+    providers: Vec<String>,
+    implicit_env_file: bool,
+    ignore_env: bool,
+    expand: bool,
+    quiet: bool,
+    command: Option<(String, Vec<String>)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            files: Vec::new(),
+            vars: Vec::new(),
+            providers: Vec::new(),
+            implicit_env_file: true,
+            ignore_env: false,
+            expand: true,
+            quiet: false,
+            command: None,
+        }
+    }
+}
+
+impl Options {
+    /// Load the .env file of the current directory, like `enw` without options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load another env file, taking precedence over those added before (`-f`)
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Set a variable, taking precedence over all files (`NAME=VALUE`)
+    pub fn var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.push((key.into(), value.into()));
+        self
+    }
+
+    /// Fetch variables from a provider, e.g. `vercel:PROJECT` (`--from`)
+    pub fn provider(mut self, spec: impl Into<String>) -> Self {
+        self.providers.push(spec.into());
+        self
+    }
+
+    /// Whether to load the .env file of the current directory (`-n` turns it off)
+    pub fn implicit_env_file(mut self, load: bool) -> Self {
+        self.implicit_env_file = load;
+        self
+    }
+
+    /// Start the command with only the loaded variables (`-i`)
+    pub fn ignore_env(mut self, ignore: bool) -> Self {
+        self.ignore_env = ignore;
+        self
+    }
+
+    /// Whether to expand `$VAR` in values (`--no-expand` turns it off)
+    pub fn expand(mut self, expand: bool) -> Self {
+        self.expand = expand;
+        self
+    }
+
+    /// Don't print warnings to stderr (`-q`)
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// The command for [`exec`] to run
+    pub fn command<I, S>(mut self, program: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command = Some((program.into(), args.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    fn builder(&self) -> Result<OptionsBuilder, BoxError> {
+        let mut opt_builder = OptionsBuilder {
+            vars: self.vars.clone(),
+            providers: self.providers.clone(),
+            ignore_env: self.ignore_env,
+            no_expand: !self.expand,
+            print_warnings: !self.quiet,
+            ..Default::default()
+        };
+        if self.implicit_env_file {
+            let dirs = workspace::env_dirs(&env::current_dir()?);
+            opt_builder
+                .env_files
+                .extend(dirs.into_iter().map(|dir| EnvFile {
+                    path: dir.join(DEFAULT_ENV_FILE_NAME),
+                    is_default: true,
+                }));
+        }
+        opt_builder
+            .env_files
+            .extend(self.files.iter().map(|path| EnvFile {
+                path: path.clone(),
+                is_default: false,
+            }));
+        Ok(opt_builder)
+    }
+}
+
+/// The variables enw would set, sorted by key
+pub fn load_env(opts: &Options) -> Result<Vec<(String, String)>, BoxError> {
+    let ctx = resolve(opts.builder()?, &Pipeline::new())?;
+    Ok(ctx
+        .vars
+        .into_iter()
+        .map(|var| (var.key, var.value))
+        .collect())
+}
+
+/// Run the command set with [`Options::command`] in the loaded environment and return its exit
+/// code
+pub fn exec(opts: &Options) -> Result<i32, BoxError> {
+    let (program, args) = opts.command.as_ref().ok_or("no command to run")?;
+    let ctx = resolve(opts.builder()?, &Pipeline::new())?;
+    let child_env = child_environment(&ctx.opts, ctx.unset, &ctx.vars);
+    let forwarding = Forwarding::install(None)?;
+    let status = Command::new(program)
+        .args(args)
+        .env_clear()
+        .envs(child_env)
+        .spawn()
+        .and_then(|mut child| forwarding.wait(&mut child))
+        .map_err(|e| format!("{program}: {e}"))?;
+    Ok(exit_code(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_load_env() {
+        let path = env::temp_dir().join(format!("enw-embed-test-{}.env", std::process::id()));
+        fs::write(&path, "B=file\nC=$B-c\n").unwrap();
+        let opts = Options::new()
+            .implicit_env_file(false)
+            .file(&path)
+            .var("A", "arg");
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        assert_eq!(
+            load_env(&opts).unwrap(),
+            vec![owned("A", "arg"), owned("B", "file"), owned("C", "file-c")]
+        );
+        assert_eq!(
+            load_env(&opts.clone().expand(false)).unwrap()[2],
+            owned("C", "$B-c")
+        );
+        let code = exec(&opts.command("sh", ["-c", "test \"$C\" = file-c || exit 3"])).unwrap();
+        assert_eq!(code, 0);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Expiry dates for credentials, from `# expires: YYYY-MM-DD` annotations, either on the line
+//! before a variable or trailing it, or supplied by a provider.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::BoxError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Parse `YYYY-MM-DD`, ignoring any time that follows
+    pub fn parse(s: &str) -> Result<Self, BoxError> {
+        let invalid = || format!("invalid date, expected YYYY-MM-DD: {s}");
+        let date = s.get(..10).ok_or_else(invalid)?;
+        let mut parts = date.splitn(3, '-');
+        let mut next = || parts.next().and_then(|p| p.parse::<i64>().ok());
+        let (year, month, day) = (next(), next(), next());
+        match (year, month, day) {
+            (Some(year), Some(month @ 1..=12), Some(day @ 1..=31)) => Ok(Date {
+                year,
+                month: month as u32,
+                day: day as u32,
+            }),
+            _ => Err(invalid().into()),
+        }
+    }
+
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self::from_days(secs as i64 / 86400)
+    }
+
+    /// Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        Date { year, month, day }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Map keys in a dotenv document to their annotated expiry dates
+pub fn annotations(text: &str) -> Result<HashMap<String, Date>, BoxError> {
+    let mut out = HashMap::new();
+    let mut pending = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(date) = annotation(comment) {
+                pending = Some(Date::parse(date)?);
+            }
+            continue;
+        }
+        let Some((key, rest)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let key = key.strip_prefix("export ").unwrap_or(key).trim();
+        let inline = rest
+            .rsplit_once('#')
+            .and_then(|(_, comment)| annotation(comment));
+        if let Some(date) = inline {
+            out.insert(key.to_owned(), Date::parse(date)?);
+        } else if let Some(date) = pending {
+            out.insert(key.to_owned(), date);
+        }
+        pending = None;
+    }
+    Ok(out)
+}
+
+fn annotation(comment: &str) -> Option<&str> {
+    comment.trim().strip_prefix("expires:").map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_annotations() {
+        let text = "
+            # expires: 2025-07-01
+            API_TOKEN=abc
+            OTHER=1
+            export DB_PASSWORD=x # expires: 2030-01-31T00:00:00Z
+        ";
+        let dates = annotations(text).unwrap();
+        assert_eq!(dates.len(), 2);
+        assert_eq!(dates["API_TOKEN"].to_string(), "2025-07-01");
+        assert_eq!(dates["DB_PASSWORD"].to_string(), "2030-01-31");
+        assert!(annotations("# expires: soon\nA=1").is_err());
+    }
+
+    #[test]
+    fn test_from_days() {
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(20269).to_string(), "2025-06-30");
+        assert_eq!(Date::from_days(11016).to_string(), "2000-02-29");
+        assert!(Date::parse("2025-07-01").unwrap() > Date::from_days(20269));
+    }
+}
@@ -0,0 +1,90 @@
+//! `--verbose` and `--explain KEY` show where the variables of the command's environment come
+//! from: a line of an env file, a provider, a profile, the command line, or enw's own
+//! environment. Values of secrets are masked, unless `--show-secrets` is given.
+
+use std::{collections::BTreeMap, ffi::OsString};
+
+use crate::{profile, redact, Origin, Var};
+
+/// Where a variable was set
+pub fn origin(var: &Var) -> String {
+    match &var.origin {
+        Origin::File(path) => match var.line {
+            Some(line) => format!("{}:{line}", path.to_string_lossy()),
+            None => path.to_string_lossy().into_owned(),
+        },
+        Origin::Provider {
+            name,
+            reference: Some(reference),
+        } => format!("provider {name} ({reference})"),
+        Origin::Provider { name, .. } => format!("provider {name}"),
+        Origin::Profile(name) => format!("profile {name} in {}", profile::CONFIG_FILE_NAME),
+        Origin::CommandLine => "command line".to_owned(),
+    }
+}
+
+/// One line per variable of `env`, the environment the command gets, ordered by key
+pub fn listing(
+    env: &BTreeMap<OsString, OsString>,
+    vars: &[Var],
+    show_secrets: bool,
+) -> Vec<String> {
+    env.keys()
+        .filter_map(|key| explain(&key.to_string_lossy(), env, vars, show_secrets))
+        .collect()
+}
+
+/// `KEY=VALUE (origin)`, or None when the command does not get `key`
+pub fn explain(
+    key: &str,
+    env: &BTreeMap<OsString, OsString>,
+    vars: &[Var],
+    show_secrets: bool,
+) -> Option<String> {
+    let value = env.get(&OsString::from(key))?.to_string_lossy();
+    Some(match vars.iter().find(|var| var.key == key) {
+        Some(var) => format!(
+            "{key}={} ({})",
+            redact::value(var, show_secrets),
+            origin(var)
+        ),
+        None => format!("{key}={value} (inherited)"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_listing() {
+        let mut file_var = Var::new("A".into(), "1".into(), Origin::File(PathBuf::from(".env")));
+        file_var.line = Some(3);
+        let vars = vec![
+            file_var,
+            Var::new("API_TOKEN".into(), "hunter2".into(), Origin::CommandLine),
+        ];
+        let env: BTreeMap<OsString, OsString> =
+            [("A", "1"), ("API_TOKEN", "hunter2"), ("HOME", "/root")]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect();
+        assert_eq!(
+            listing(&env, &vars, false),
+            vec![
+                "A=1 (.env:3)",
+                "API_TOKEN=**** (command line)",
+                "HOME=/root (inherited)"
+            ]
+        );
+        assert_eq!(
+            explain("API_TOKEN", &env, &vars, true).as_deref(),
+            Some("API_TOKEN=hunter2 (command line)")
+        );
+        assert_eq!(explain("MISSING", &env, &vars, false), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! `enw export FORMAT`, or `enw export --format FORMAT`, renders the resolved variables for
+//! consumption by other tools.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{json, shell, BoxError, Origin, Var};
+
+#[derive(Clone, Debug)]
+pub struct Export {
+    pub format: Format,
+    pub key_prefix: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The `environment` and `secrets` arrays of an ECS container definition
+    Ecs,
+    /// `-e KEY=VALUE` arguments for `docker run` and `podman run`
+    DockerArgs,
+    /// An Ansible vars file, keys lowercased
+    Ansible,
+    /// `export KEY := value` lines for inclusion in a Makefile
+    Make,
+    /// `KEY=VALUE` lines for systemd's `EnvironmentFile=`
+    Systemd,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] =
+        &["ecs", "docker-args", "ansible", "make", "systemd"];
+}
+
+impl FromStr for Format {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ecs" => Ok(Format::Ecs),
+            "docker-args" => Ok(Format::DockerArgs),
+            "ansible" => Ok(Format::Ansible),
+            "make" => Ok(Format::Make),
+            "systemd" => Ok(Format::Systemd),
+            _ => Err(format!("unknown export format: {s}").into()),
+        }
+    }
+}
+
+impl Export {
+    pub fn render(&self, vars: &[Var]) -> String {
+        match self.format {
+            Format::Ecs => ecs(vars),
+            Format::DockerArgs => docker_args(vars),
+            Format::Ansible => ansible(vars, self.key_prefix.as_deref().unwrap_or("")),
+            Format::Make => make(vars),
+            Format::Systemd => systemd(vars),
+        }
+    }
+}
+
+/// Simply expanded assignments, with `$` doubled so make does not expand it. Values spanning
+/// several lines are written as `define` blocks.
+fn make(vars: &[Var]) -> String {
+    let mut out = String::new();
+    for var in vars {
+        let value = var.value.replace('$', "$$");
+        if value.contains('\n') {
+            out.push_str(&format!(
+                "define {} :=\n{value}\nendef\nexport {}\n",
+                var.key, var.key
+            ));
+        } else {
+            let value = value.replace('#', "\\#");
+            // make strips leading whitespace from the value, an empty reference protects it
+            let guard = if value.starts_with(char::is_whitespace) {
+                "$()"
+            } else {
+                ""
+            };
+            out.push_str(&format!("export {} := {guard}{value}\n", var.key));
+        }
+    }
+    out
+}
+
+/// Values are double quoted unless plain. Inside the quotes systemd only unescapes `"`, `\`,
+/// `` ` `` and `$`, and keeps newlines, so nothing else needs escaping.
+fn systemd(vars: &[Var]) -> String {
+    let mut out = String::new();
+    for var in vars {
+        let plain = var
+            .value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "%+,-./:@_".contains(c));
+        if plain {
+            out.push_str(&format!("{}={}\n", var.key, var.value));
+            continue;
+        }
+        let mut value = String::new();
+        for c in var.value.chars() {
+            if matches!(c, '"' | '\\' | '`' | '$') {
+                value.push('\\');
+            }
+            value.push(c);
+        }
+        out.push_str(&format!("{}=\"{value}\"\n", var.key));
+    }
+    out
+}
+
+fn ansible(vars: &[Var], prefix: &str) -> String {
+    let mut out = String::from("---\n");
+    for var in vars {
+        let key = format!("{prefix}{}", var.key.to_lowercase());
+        if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            out.push_str(&key);
+        } else {
+            out.push_str(&json::quote(&key));
+        }
+        // JSON string literals are valid YAML double quoted scalars
+        out.push_str(&format!(": {}\n", json::quote(&var.value)));
+    }
+    out
+}
+
+/// Secrets are passed as `--env KEY`, which makes docker read the value from its own
+/// environment instead of the command line.
+fn docker_args(vars: &[Var]) -> String {
+    let args: Vec<_> = vars
+        .iter()
+        .map(|var| {
+            if var.is_secret() {
+                format!("--env {}", shell::quote(&var.key))
+            } else {
+                format!("-e {}", shell::quote(&format!("{}={}", var.key, var.value)))
+            }
+        })
+        .collect();
+    format!("{}\n", args.join(" "))
+}
+
+fn ecs(vars: &[Var]) -> String {
+    let mut environment = Vec::new();
+    let mut secrets = Vec::new();
+    for var in vars {
+        let mut entry = BTreeMap::new();
+        entry.insert("name".to_owned(), json::Value::String(var.key.clone()));
+        match secret_reference(var) {
+            Some(arn) => {
+                entry.insert("valueFrom".to_owned(), json::Value::String(arn.to_owned()));
+                secrets.push(json::Value::Object(entry));
+            }
+            None => {
+                entry.insert("value".to_owned(), json::Value::String(var.value.clone()));
+                environment.push(json::Value::Object(entry));
+            }
+        }
+    }
+    let mut doc = BTreeMap::new();
+    doc.insert("environment".to_owned(), json::Value::Array(environment));
+    doc.insert("secrets".to_owned(), json::Value::Array(secrets));
+    format!("{}\n", json::Value::Object(doc).pretty())
+}
+
+/// The ARN ECS should resolve the value from: either supplied by the provider the variable was
+/// loaded from, or the value itself when it already is a Secrets Manager or SSM parameter ARN.
+/// References of other providers, like Vault paths, mean nothing to ECS.
+fn secret_reference(var: &Var) -> Option<&str> {
+    match &var.origin {
+        Origin::Provider {
+            reference: Some(reference),
+            ..
+        } if is_secret_arn(reference) => Some(reference),
+        _ if is_secret_arn(&var.value) => Some(&var.value),
+        _ => None,
+    }
+}
+
+fn is_secret_arn(value: &str) -> bool {
+    let mut parts = value.splitn(4, ':');
+    parts.next() == Some("arn")
+        && parts
+            .next()
+            .is_some_and(|partition| partition.starts_with("aws"))
+        && matches!(parts.next(), Some("secretsmanager") | Some("ssm"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn render(format: Format, vars: &[Var]) -> String {
+        Export {
+            format,
+            key_prefix: None,
+        }
+        .render(vars)
+    }
+
+    #[test]
+    fn test_ecs_splits_secrets() {
+        let vars = vec![
+            Var::new("A".into(), "1".into(), Origin::File(PathBuf::from(".env"))),
+            Var::new(
+                "B".into(),
+                "arn:aws:ssm:eu-west-1:123456789012:parameter/b".into(),
+                Origin::CommandLine,
+            ),
+            Var::new(
+                "C".into(),
+                "hunter2".into(),
+                Origin::Provider {
+                    name: "test".into(),
+                    reference: Some("arn:aws:secretsmanager:eu-west-1:1:secret:c".into()),
+                },
+            ),
+        ];
+        let expected = r#"{
+  "environment": [
+    {
+      "name": "A",
+      "value": "1"
+    }
+  ],
+  "secrets": [
+    {
+      "name": "B",
+      "valueFrom": "arn:aws:ssm:eu-west-1:123456789012:parameter/b"
+    },
+    {
+      "name": "C",
+      "valueFrom": "arn:aws:secretsmanager:eu-west-1:1:secret:c"
+    }
+  ]
+}
+"#;
+        assert_eq!(render(Format::Ecs, &vars), expected);
+    }
+
+    #[test]
+    fn test_docker_args() {
+        let vars = vec![
+            Var::new("A".into(), "1".into(), Origin::CommandLine),
+            Var::new("B".into(), "two words".into(), Origin::CommandLine),
+            Var::new("C".into(), "it's".into(), Origin::CommandLine),
+            Var::new("API_TOKEN".into(), "hunter2".into(), Origin::CommandLine),
+        ];
+        assert_eq!(
+            render(Format::DockerArgs, &vars),
+            "-e A=1 -e 'B=two words' -e 'C=it'\\''s' --env API_TOKEN\n"
+        );
+    }
+
+    #[test]
+    fn test_make() {
+        let vars = vec![
+            Var::new("A".into(), "$HOME/x#1".into(), Origin::CommandLine),
+            Var::new("B".into(), "  padded".into(), Origin::CommandLine),
+            Var::new("C".into(), "line 1\nline $2".into(), Origin::CommandLine),
+        ];
+        assert_eq!(
+            render(Format::Make, &vars),
+            "export A := $$HOME/x\\#1\n\
+             export B := $()  padded\n\
+             define C :=\nline 1\nline $$2\nendef\nexport C\n"
+        );
+    }
+
+    #[test]
+    fn test_systemd() {
+        let vars = vec![
+            Var::new("A".into(), "/usr/bin:x-1".into(), Origin::CommandLine),
+            Var::new("B".into(), "".into(), Origin::CommandLine),
+            Var::new("C".into(), "it's \"$5\" # \\n".into(), Origin::CommandLine),
+            Var::new("D".into(), " line 1\nline 2 ".into(), Origin::CommandLine),
+        ];
+        assert_eq!(
+            render(Format::Systemd, &vars),
+            "A=/usr/bin:x-1\n\
+             B=\n\
+             C=\"it's \\\"\\$5\\\" # \\\\n\"\n\
+             D=\" line 1\nline 2 \"\n"
+        );
+    }
+
+    #[test]
+    fn test_ansible() {
+        let vars = vec![
+            Var::new("DB_HOST".into(), "localhost".into(), Origin::CommandLine),
+            Var::new("key.1".into(), "a \"b\"\n".into(), Origin::CommandLine),
+        ];
+        let export = Export {
+            format: Format::Ansible,
+            key_prefix: Some("app_".into()),
+        };
+        assert_eq!(
+            export.render(&vars),
+            "---\napp_db_host: \"localhost\"\n\"app_key.1\": \"a \\\"b\\\"\\n\"\n"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! C bindings, enabled with the `ffi` feature. See `include/enw.h` for the interface.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr, CString},
+    fs,
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{
+    color, parse_env_doc, parse_env_file, provider, BoxError, OptionsBuilder, Origin, Var,
+};
+
+#[repr(C)]
+pub struct EnwVar {
+    key: *mut c_char,
+    value: *mut c_char,
+}
+
+#[repr(C)]
+pub struct EnwVars {
+    vars: *mut EnwVar,
+    len: usize,
+    error: *mut c_char,
+}
+
+fn c_string(s: String) -> *mut c_char {
+    // Interior NULs can not be represented, they truncate the string instead
+    let s = match CString::new(s) {
+        Ok(s) => s,
+        Err(e) => {
+            let end = e.nul_position();
+            CString::new(&e.into_vec()[..end]).unwrap_or_default()
+        }
+    };
+    s.into_raw()
+}
+
+fn into_c(result: Result<Vec<(String, String)>, BoxError>) -> *mut EnwVars {
+    let vars = match result {
+        Ok(pairs) => {
+            let vars: Box<[EnwVar]> = pairs
+                .into_iter()
+                .map(|(key, value)| EnwVar {
+                    key: c_string(key),
+                    value: c_string(value),
+                })
+                .collect();
+            let len = vars.len();
+            EnwVars {
+                vars: Box::into_raw(vars) as *mut EnwVar,
+                len,
+                error: ptr::null_mut(),
+            }
+        }
+        Err(e) => EnwVars {
+            vars: ptr::null_mut(),
+            len: 0,
+            error: c_string(e.to_string()),
+        },
+    };
+    Box::into_raw(Box::new(vars))
+}
+
+/// # Safety
+///
+/// `text` must be a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn enw_parse(text: *const c_char) -> *mut EnwVars {
+    if text.is_null() {
+        return into_c(Err("text is NULL".into()));
+    }
+    let text = CStr::from_ptr(text).to_string_lossy();
+    into_c(parse_env_doc(&text).into_iter().collect())
+}
+
+/// # Safety
+///
+/// `paths` must point to `len` valid NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn enw_resolve(paths: *const *const c_char, len: usize) -> *mut EnwVars {
+    if paths.is_null() && len > 0 {
+        return into_c(Err("paths is NULL".into()));
+    }
+    let paths: Vec<PathBuf> = (0..len)
+        .map(|i| PathBuf::from(&*CStr::from_ptr(*paths.add(i)).to_string_lossy()))
+        .collect();
+    into_c(resolve(&paths))
+}
+
+fn resolve(paths: &[PathBuf]) -> Result<Vec<(String, String)>, BoxError> {
+    let opts = OptionsBuilder::default();
+    let mut env_vars: HashMap<String, Var> = HashMap::new();
+    for path in paths {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        for entry in parse_env_file(path, &text, &opts, &env_vars) {
+            let (key, value) = entry?;
+            env_vars.insert(
+                key.clone(),
+                Var::new(key, value, Origin::File(path.clone())),
+            );
+        }
+    }
+    let mut pairs: Vec<_> = env_vars
+        .into_values()
+        .map(|var| (var.key, var.value))
+        .collect();
+    pairs.sort();
+    Ok(pairs)
+}
+
+/// # Safety
+///
+/// `spec` must be a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn enw_fetch(spec: *const c_char) -> *mut EnwVars {
+    if spec.is_null() {
+        return into_c(Err("spec is NULL".into()));
+    }
+    let spec = CStr::from_ptr(spec).to_string_lossy();
+    into_c(
+        provider::from_spec(&spec, false, color::Choice::Never)
+            .and_then(|provider| provider.fetch())
+            .map(|vars| vars.into_iter().map(|var| (var.key, var.value)).collect()),
+    )
+}
+
+/// # Safety
+///
+/// `vars` must have been returned by one of the functions above, and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn enw_vars_free(vars: *mut EnwVars) {
+    if vars.is_null() {
+        return;
+    }
+    let vars = Box::from_raw(vars);
+    if !vars.vars.is_null() {
+        let list = Box::from_raw(ptr::slice_from_raw_parts_mut(vars.vars, vars.len));
+        for var in list.iter() {
+            drop(CString::from_raw(var.key));
+            drop(CString::from_raw(var.value));
+        }
+    }
+    if !vars.error.is_null() {
+        drop(CString::from_raw(vars.error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    unsafe fn pairs(vars: *mut EnwVars) -> Result<Vec<(String, String)>, String> {
+        let result = if (*vars).error.is_null() {
+            Ok((0..(*vars).len)
+                .map(|i| {
+                    let var = &*(*vars).vars.add(i);
+                    (
+                        CStr::from_ptr(var.key).to_string_lossy().into_owned(),
+                        CStr::from_ptr(var.value).to_string_lossy().into_owned(),
+                    )
+                })
+                .collect())
+        } else {
+            Err(CStr::from_ptr((*vars).error).to_string_lossy().into_owned())
+        };
+        enw_vars_free(vars);
+        result
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = CString::new("A=1\nB=\"two words\" # comment\n").unwrap();
+        let actual = unsafe { pairs(enw_parse(text.as_ptr())) };
+        assert_eq!(
+            actual,
+            Ok(vec![
+                ("A".to_owned(), "1".to_owned()),
+                ("B".to_owned(), "two words".to_owned())
+            ])
+        );
+        let text = CString::new("A=\"open").unwrap();
+        assert!(unsafe { pairs(enw_parse(text.as_ptr())) }.is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let paths = [
+            CString::new("tests/data/.env").unwrap(),
+            CString::new("tests/.env").unwrap(),
+        ];
+        let ptrs: Vec<_> = paths.iter().map(|p| p.as_ptr()).collect();
+        let actual = unsafe { pairs(enw_resolve(ptrs.as_ptr(), ptrs.len())) }.unwrap();
+        assert_eq!(actual.len(), 6);
+        assert_eq!(actual[0], ("MY_URL".to_owned(), "localhost".to_owned()));
+    }
+}
@@ -0,0 +1,285 @@
+//! Usually enw replaces itself with the command, which then receives signals directly. When enw
+//! stays around as its parent instead, e.g. to write a `--report`, signals sent to enw are
+//! passed on to the command, so that it is not orphaned. Signals generated by the terminal are
+//! not passed on, the command is in the same foreground process group and already got them,
+//! unless it runs in a terminal of its own.
+
+use std::{
+    io, mem,
+    process::{Child, ExitStatus},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use libc::c_int;
+
+pub const SIGNALS: &[c_int] = &[
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+static CHILD: AtomicI32 = AtomicI32::new(0);
+/// A signal that arrived before the child was spawned
+static PENDING: AtomicI32 = AtomicI32::new(0);
+/// The last signal received, whether forwarded or not
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+static KILL_TIMEOUT: AtomicU32 = AtomicU32::new(0);
+static ARMED: AtomicBool = AtomicBool::new(false);
+/// Pass on signals from the terminal as well, the command being in another session
+static FROM_TERMINAL: AtomicBool = AtomicBool::new(false);
+/// Signal handlers are process wide, so commands are waited for one at a time
+static WAITING: Mutex<()> = Mutex::new(());
+/// How often a command with a timeout is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type Handler = extern "C" fn(c_int, *mut libc::siginfo_t, *mut libc::c_void);
+
+/// Signal handlers installed for the lifetime of the value, the previous ones restored on drop
+pub struct Forwarding {
+    saved: Vec<(c_int, libc::sigaction)>,
+    _waiting: MutexGuard<'static, ()>,
+}
+
+impl Forwarding {
+    /// Install before spawning the command, so that enw is not killed before it can forward.
+    /// With a `kill_timeout` in seconds, the command is killed that long after the first
+    /// forwarded signal.
+    pub fn install(kill_timeout: Option<u32>) -> io::Result<Self> {
+        let waiting = WAITING.lock().unwrap_or_else(|e| e.into_inner());
+        CHILD.store(0, Ordering::SeqCst);
+        PENDING.store(0, Ordering::SeqCst);
+        RECEIVED.store(0, Ordering::SeqCst);
+        ARMED.store(false, Ordering::SeqCst);
+        FROM_TERMINAL.store(false, Ordering::SeqCst);
+        KILL_TIMEOUT.store(kill_timeout.unwrap_or(0), Ordering::SeqCst);
+        let mut forwarding = Forwarding {
+            saved: Vec::new(),
+            _waiting: waiting,
+        };
+        for &signal in SIGNALS {
+            forwarding.saved.push((signal, handle(signal, forward)?));
+        }
+        if kill_timeout.is_some() {
+            forwarding
+                .saved
+                .push((libc::SIGALRM, handle(libc::SIGALRM, kill_child)?));
+        }
+        Ok(forwarding)
+    }
+
+    pub fn wait(self, child: &mut Child) -> io::Result<ExitStatus> {
+        self.attach(child);
+        child.wait()
+    }
+
+    /// Like [`Forwarding::wait`], sending the command SIGTERM once `timeout` has elapsed, and
+    /// SIGKILL `kill_after` later if it is still running. Also tells whether the timeout elapsed.
+    pub fn wait_timeout(
+        self,
+        child: &mut Child,
+        timeout: Duration,
+        kill_after: Option<Duration>,
+    ) -> io::Result<(ExitStatus, bool)> {
+        self.attach(child);
+        let started = Instant::now();
+        let mut terminated: Option<Instant> = None;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, terminated.is_some()));
+            }
+            match terminated {
+                None if started.elapsed() >= timeout => {
+                    // SAFETY: no preconditions
+                    unsafe { libc::kill(child.id() as i32, libc::SIGTERM) };
+                    terminated = Some(Instant::now());
+                }
+                Some(at) if kill_after.is_some_and(|kill_after| at.elapsed() >= kill_after) => {
+                    child.kill()?;
+                    return Ok((child.wait()?, true));
+                }
+                _ => {}
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Like [`Forwarding::wait`], for commands running side by side: signals are passed on to
+    /// each of them that is still running, and the kill timeout applies to all of them. The
+    /// statuses are returned in the order of `children`.
+    pub fn wait_all(self, children: &mut [Child]) -> io::Result<Vec<ExitStatus>> {
+        // No child is attached, so the handler leaves signals in PENDING for this loop
+        CHILD.store(0, Ordering::SeqCst);
+        let kill_timeout = Duration::from_secs(KILL_TIMEOUT.load(Ordering::SeqCst).into());
+        let mut statuses: Vec<Option<ExitStatus>> = vec![None; children.len()];
+        let mut forwarded: Option<Instant> = None;
+        loop {
+            for (child, status) in children.iter_mut().zip(&mut statuses) {
+                if status.is_none() {
+                    *status = child.try_wait()?;
+                }
+            }
+            if statuses.iter().all(Option::is_some) {
+                return Ok(statuses.into_iter().flatten().collect());
+            }
+            let signal = PENDING.swap(0, Ordering::SeqCst);
+            if signal != 0 {
+                forwarded.get_or_insert_with(Instant::now);
+            }
+            let kill =
+                forwarded.is_some_and(|at| !kill_timeout.is_zero() && at.elapsed() >= kill_timeout);
+            for (child, status) in children.iter_mut().zip(&statuses) {
+                if status.is_some() {
+                    continue;
+                }
+                if kill {
+                    child.kill()?;
+                } else if signal != 0 {
+                    // SAFETY: no preconditions
+                    unsafe { libc::kill(child.id() as i32, signal) };
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Forward signals to `child` from now on, for callers that wait for it themselves
+    pub fn attach(&self, child: &Child) {
+        let pid = child.id() as i32;
+        CHILD.store(pid, Ordering::SeqCst);
+        ARMED.store(false, Ordering::SeqCst);
+        let pending = PENDING.swap(0, Ordering::SeqCst);
+        if pending != 0 {
+            // SAFETY: no preconditions
+            unsafe { libc::kill(pid, pending) };
+        }
+    }
+
+    /// Hold signals for the next command once `attach`ed, for callers that spawn another one
+    /// after this one exited, so that none are sent to a pid that may have been reused
+    pub fn detach(&self) {
+        CHILD.store(0, Ordering::SeqCst);
+    }
+
+    /// Also pass on signals from enw's terminal, for a command that does not share it
+    pub fn forward_terminal_signals(&self) {
+        FROM_TERMINAL.store(true, Ordering::SeqCst);
+    }
+
+    /// The last signal enw received, including those from the terminal
+    pub fn received(&self) -> Option<c_int> {
+        Some(RECEIVED.load(Ordering::SeqCst)).filter(|&signal| signal != 0)
+    }
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        // SAFETY: cancels the kill timer, and restores handlers read by sigaction
+        unsafe {
+            libc::alarm(0);
+            for (signal, action) in &self.saved {
+                libc::sigaction(*signal, action, ptr::null_mut());
+            }
+        }
+        CHILD.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Install `handler`, returning the previous action
+fn handle(signal: c_int, handler: Handler) -> io::Result<libc::sigaction> {
+    // SAFETY: sigaction is plain data, fully initialized before use
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old: libc::sigaction = mem::zeroed();
+        if libc::sigaction(signal, &action, &mut old) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(old)
+    }
+}
+
+/// Only makes async-signal-safe calls
+extern "C" fn forward(signal: c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    RECEIVED.store(signal, Ordering::SeqCst);
+    // SAFETY: the kernel passes valid siginfo to SA_SIGINFO handlers
+    let sender = unsafe { (*info).si_pid() };
+    if sender == 0 && !FROM_TERMINAL.load(Ordering::SeqCst) {
+        // From the terminal
+        return;
+    }
+    let pid = CHILD.load(Ordering::SeqCst);
+    if pid <= 0 {
+        PENDING.store(signal, Ordering::SeqCst);
+        return;
+    }
+    // SAFETY: kill and alarm are async-signal-safe
+    unsafe {
+        libc::kill(pid, signal);
+        let timeout = KILL_TIMEOUT.load(Ordering::SeqCst);
+        if timeout > 0 && !ARMED.swap(true, Ordering::SeqCst) {
+            libc::alarm(timeout);
+        }
+    }
+}
+
+extern "C" fn kill_child(_: c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    let pid = CHILD.load(Ordering::SeqCst);
+    if pid > 0 {
+        // SAFETY: kill is async-signal-safe
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Command, thread, time::Duration};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_forward_and_kill() {
+        let forwarding = Forwarding::install(Some(1)).unwrap();
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 5"])
+            .spawn()
+            .unwrap();
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(200));
+            // SAFETY: no preconditions
+            unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+        });
+        let status = forwarding.wait(&mut child).unwrap();
+        assert_eq!(crate::exit_code(status), 128 + libc::SIGKILL);
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let run = |script: &str, kill_after: Option<Duration>| {
+            let forwarding = Forwarding::install(None).unwrap();
+            let mut child = Command::new("sh").args(["-c", script]).spawn().unwrap();
+            let timeout = Duration::from_millis(100);
+            let (status, timed_out) = forwarding
+                .wait_timeout(&mut child, timeout, kill_after)
+                .unwrap();
+            (crate::exit_code(status), timed_out)
+        };
+        assert_eq!(run("exit 3", None), (3, false));
+        assert_eq!(run("sleep 5", None), (128 + libc::SIGTERM, true));
+        let stubborn = "trap '' TERM; sleep 5";
+        let kill_after = Some(Duration::from_millis(100));
+        assert_eq!(run(stubborn, kill_after), (128 + libc::SIGKILL, true));
+    }
+}
@@ -0,0 +1,70 @@
+//! Checks whether env files holding secrets are, or could easily end up, committed to git.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exposure {
+    /// Committed, or staged for the next commit
+    Tracked,
+    /// Untracked but not ignored, so `git add .` would pick it up
+    NotIgnored,
+}
+
+/// How `path` is exposed by the git repository it is in. None when it is ignored, not in a
+/// repository, or git is not installed.
+pub fn exposure(path: &Path) -> Option<Exposure> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file = path.file_name()?;
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .arg("--")
+            .arg(file)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok()
+            .and_then(|status| status.code())
+    };
+    if git(&["ls-files", "--error-unmatch"])? == 0 {
+        return Some(Exposure::Tracked);
+    }
+    // 0 ignored, 1 not ignored, 128 not in a repository
+    (git(&["check-ignore", "-q"])? == 1).then_some(Exposure::NotIgnored)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_exposure() {
+        let dir = env::temp_dir().join(format!("enw-git-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| Command::new("git").arg("-C").arg(&dir).args(args).output();
+        if run(&["init", "-q"]).is_err() {
+            return;
+        }
+        fs::write(dir.join(".gitignore"), ".env.local\n").unwrap();
+        for name in [".env", ".env.local", ".env.prod"] {
+            fs::write(dir.join(name), "A=1\n").unwrap();
+        }
+        run(&["add", ".env"]).unwrap();
+        assert_eq!(exposure(&dir.join(".env")), Some(Exposure::Tracked));
+        assert_eq!(exposure(&dir.join(".env.prod")), Some(Exposure::NotIgnored));
+        assert_eq!(exposure(&dir.join(".env.local")), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
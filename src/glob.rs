@@ -0,0 +1,144 @@
+//! Shell style wildcard matching for variable names and paths: `*` matches any run of
+//! characters and `?` matches exactly one.
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::BoxError;
+
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text position it was tried against
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?'])
+}
+
+/// The files matching a path pattern, e.g. `conf.d/*.env`, sorted lexicographically so that
+/// drop-ins are loaded in a predictable order. Wildcards don't match a leading dot, and a
+/// pattern matching no files is an error, like a missing file would be.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>, BoxError> {
+    if !is_pattern(pattern) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let part = match component {
+            Component::Normal(part) => part.to_string_lossy(),
+            _ => {
+                for path in &mut paths {
+                    path.push(component);
+                }
+                continue;
+            }
+        };
+        if !is_pattern(&part) {
+            for path in &mut paths {
+                path.push(&*part);
+            }
+            continue;
+        }
+        let mut next = Vec::new();
+        for dir in &paths {
+            let entries = match fs::read_dir(if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') && !part.starts_with('.') {
+                    continue;
+                }
+                if matches(&part, &name) {
+                    next.push(dir.join(name));
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("{pattern}: no files match").into());
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("AWS_*", "AWS_PROFILE"));
+        assert!(matches("*_SECRET*", "DB_SECRET_KEY"));
+        assert!(matches("*", ""));
+        assert!(matches("a?c", "abc"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("AWS_*", "MY_AWS_PROFILE"));
+        assert!(!matches("a?c", "ac"));
+        assert!(!matches("*_TOKEN", "TOKEN_ID"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = std::env::temp_dir().join(format!("enw-glob-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("conf.d/sub")).unwrap();
+        for name in [
+            "20-b.env",
+            "10-a.env",
+            ".hidden.env",
+            "notes.txt",
+            "sub/30-c.env",
+        ] {
+            fs::write(dir.join("conf.d").join(name), "").unwrap();
+        }
+        let pattern = dir.join("conf.d/*.env").to_string_lossy().into_owned();
+        assert_eq!(
+            expand(&pattern).unwrap(),
+            vec![dir.join("conf.d/10-a.env"), dir.join("conf.d/20-b.env")]
+        );
+        let pattern = dir.join("*/*/??-c.env").to_string_lossy().into_owned();
+        assert_eq!(
+            expand(&pattern).unwrap(),
+            vec![dir.join("conf.d/sub/30-c.env")]
+        );
+        assert!(expand(&dir.join("*.yaml").to_string_lossy()).is_err());
+        assert_eq!(
+            expand("plain.env").unwrap(),
+            vec![PathBuf::from("plain.env")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! Loading the nearest .env into an interactive shell, like direnv. `enw hook SHELL` prints a
+//! snippet for the shell's rc file which runs `enw export SHELL` before every prompt. That
+//! prints the commands bringing the shell up to date: when the nearest .env changed, or the
+//! shell moved to a directory with another one, the variables set before are reverted and the
+//! new ones applied. What was applied, and the values it replaced, is kept in the shell's
+//! `ENW_HOOK_STATE` variable.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    json::{self, Value},
+    pipeline::Pipeline,
+    prompt, resolve, shell, BoxError, EnvFile, OptionsBuilder, Var, DEFAULT_ENV_FILE_NAME,
+};
+
+pub const STATE: &str = "ENW_HOOK_STATE";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const NAMES: &'static [&'static str] = &["bash", "zsh", "fish"];
+
+    /// The snippet to add to the shell's rc file
+    pub fn hook(self, enw: &Path) -> String {
+        let enw = shell::quote(&enw.to_string_lossy());
+        match self {
+            Shell::Bash => format!(
+                "_enw_hook() {{\n  \
+                   local status=$?\n  \
+                   eval \"$({enw} export bash)\"\n  \
+                   return $status\n\
+                 }}\n\
+                 if [[ \";${{PROMPT_COMMAND[*]:-}};\" != *\";_enw_hook;\"* ]]; then\n  \
+                   PROMPT_COMMAND=\"_enw_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\"\n\
+                 fi\n"
+            ),
+            Shell::Zsh => format!(
+                "_enw_hook() {{\n  \
+                   eval \"$({enw} export zsh)\"\n\
+                 }}\n\
+                 typeset -ag precmd_functions\n\
+                 if (( ! ${{precmd_functions[(I)_enw_hook]}} )); then\n  \
+                   precmd_functions=(_enw_hook $precmd_functions)\n\
+                 fi\n"
+            ),
+            Shell::Fish => format!(
+                "function _enw_hook --on-event fish_prompt\n    \
+                   {enw} export fish | source\n\
+                 end\n"
+            ),
+        }
+    }
+
+    fn set(self, key: &str, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {key}={};", shell::quote(value)),
+            Shell::Fish => format!("set -gx {key} {};", fish_quote(value)),
+        }
+    }
+
+    fn unset(self, key: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("unset {key};"),
+            Shell::Fish => format!("set -e {key};"),
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell: {s}").into()),
+        }
+    }
+}
+
+/// What the hook applied: the loaded file with its modification stamp, and the values of the
+/// variables it set from before, None for those that were unset
+#[derive(Debug, Default, PartialEq)]
+struct State {
+    file: Option<(PathBuf, String)>,
+    saved: BTreeMap<String, Option<String>>,
+}
+
+impl State {
+    fn parse(text: &str) -> Result<Self, BoxError> {
+        let value = json::parse(text)?;
+        let file = match (value.get("file"), value.get("stamp")) {
+            (Some(Value::String(path)), Some(Value::String(stamp))) => {
+                Some((PathBuf::from(path), stamp.clone()))
+            }
+            _ => None,
+        };
+        let mut saved = BTreeMap::new();
+        if let Some(Value::Object(map)) = value.get("saved") {
+            for (key, value) in map {
+                saved.insert(key.clone(), value.as_str().map(str::to_owned));
+            }
+        }
+        Ok(State { file, saved })
+    }
+
+    fn render(&self) -> String {
+        let mut map = BTreeMap::new();
+        if let Some((path, stamp)) = &self.file {
+            let path = path.to_string_lossy().into_owned();
+            map.insert("file".to_owned(), Value::String(path));
+            map.insert("stamp".to_owned(), Value::String(stamp.clone()));
+        }
+        let saved = self
+            .saved
+            .iter()
+            .map(|(key, value)| {
+                let value = value.clone().map_or(Value::Null, Value::String);
+                (key.clone(), value)
+            })
+            .collect();
+        map.insert("saved".to_owned(), Value::Object(saved));
+        Value::Object(map).to_string()
+    }
+}
+
+/// The commands bringing the shell's environment in line with the nearest .env
+pub fn export(shell: Shell) -> Result<String, BoxError> {
+    let state = match env::var(STATE) {
+        Ok(text) => State::parse(&text).map_err(|e| format!("{STATE}: {e}"))?,
+        Err(_) => State::default(),
+    };
+    let file = nearest(&env::current_dir()?).map(|path| {
+        let stamp = stamp(&path);
+        (path, stamp)
+    });
+    if file == state.file {
+        return Ok(String::new());
+    }
+    let mut out = Vec::new();
+    // Revert in enw's own environment too, so that values referring to the variables they
+    // replace, like PATH=$PATH:bin, expand to the same on every reload
+    for (key, value) in &state.saved {
+        match value {
+            Some(value) => {
+                env::set_var(key, value);
+                out.push(shell.set(key, value));
+            }
+            None => {
+                env::remove_var(key);
+                out.push(shell.unset(key));
+            }
+        }
+    }
+    let mut next = State {
+        file: file.clone(),
+        saved: BTreeMap::new(),
+    };
+    if let Some((path, _)) = &file {
+        match load(path) {
+            Ok(vars) => {
+                eprintln!("enw: loading {}", path.to_string_lossy());
+                for var in vars {
+                    next.saved.insert(var.key.clone(), env::var(&var.key).ok());
+                    out.push(shell.set(&var.key, &var.value));
+                }
+            }
+            // Reported once, the state records the file as handled until it changes
+            Err(e) => eprintln!("enw: {}: {e}", path.to_string_lossy()),
+        }
+    } else if state.file.is_some() {
+        eprintln!("enw: unloading");
+    }
+    out.push(match next.file {
+        Some(_) => shell.set(STATE, &next.render()),
+        None => shell.unset(STATE),
+    });
+    Ok(out.join("\n") + "\n")
+}
+
+/// The .env file of the directory, or of the closest ancestor having one
+fn nearest(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(DEFAULT_ENV_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+fn stamp(path: &Path) -> String {
+    let Ok(metadata) = fs::metadata(path) else {
+        return String::new();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "{}.{:09}:{}",
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        metadata.len()
+    )
+}
+
+/// The variables to apply, including those telling prompts what is loaded
+fn load(path: &Path) -> Result<Vec<Var>, BoxError> {
+    let opt_builder = OptionsBuilder {
+        env_files: vec![EnvFile {
+            path: path.to_owned(),
+            is_default: false,
+        }],
+        print_warnings: true,
+        ..Default::default()
+    };
+    let mut vars = resolve(opt_builder, &Pipeline::new())?.vars;
+    vars.extend(prompt::vars(None, &[path.to_owned()]));
+    Ok(vars)
+}
+
+/// Quote for fish, which only knows `\'` and `\\` in single quotes
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let state = State {
+            file: Some((PathBuf::from("/app/.env"), "1.000000002:30".to_owned())),
+            saved: [
+                ("PATH".to_owned(), Some("/bin".to_owned())),
+                ("NEW".to_owned(), None),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(State::parse(&state.render()).unwrap(), state);
+    }
+
+    #[test]
+    fn test_set_and_unset() {
+        assert_eq!(Shell::Bash.set("A", "it's"), "export A='it'\\''s';");
+        assert_eq!(Shell::Fish.set("A", "it's \\"), "set -gx A 'it\\'s \\\\';");
+        assert_eq!(Shell::Zsh.unset("A"), "unset A;");
+        assert_eq!(Shell::Fish.unset("A"), "set -e A;");
+    }
+}
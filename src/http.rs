@@ -0,0 +1,407 @@
+//! HTTP access for remote sources. Requests are delegated to `curl`, which keeps TLS and proxy
+//! handling out of this crate. Transient failures are retried with exponential backoff,
+//! honoring `Retry-After` and rate-limit headers.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{color, print_warnings, sha256, BoxError};
+
+/// curl exit codes worth retrying: connection refused, timeouts, TLS handshake and dropped
+/// connections
+const TRANSIENT_CURL_CODES: &[i32] = &[7, 28, 35, 52, 55, 56];
+const RETRY_STATUS: &[u16] = &[429, 500, 502, 503, 504];
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Per-source settings, given after the source spec as `;key=value` pairs, e.g.
+/// `--from 'vercel:web;proxy=http://proxy:3128;cacert=/etc/ssl/corp.pem'`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// Attempts after the first
+    pub retries: u32,
+    pub proxy: Option<String>,
+    /// CA bundle to verify the server with
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate and key, for mutual TLS
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub insecure: bool,
+    /// Keep responses, mode 0600, in the user's cache directory, and revalidate them with
+    /// `If-None-Match`. Off by default as responses may hold secrets.
+    pub cache: bool,
+    /// Limit on each attempt, in seconds
+    pub timeout: Option<u32>,
+    /// Whether to warn about retries, unless `-q`, and how
+    pub print_warnings: bool,
+    pub color: color::Choice,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            retries: 3,
+            proxy: None,
+            ca_cert: None,
+            cert: None,
+            key: None,
+            insecure: false,
+            cache: false,
+            timeout: None,
+            print_warnings: true,
+            color: color::Choice::Auto,
+        }
+    }
+}
+
+impl Options {
+    /// Split the options off a source spec
+    pub fn split_spec(spec: &str) -> Result<(&str, Options), BoxError> {
+        let mut parts = spec.split(';');
+        let spec = parts.next().unwrap_or_default();
+        let mut opts = Options::default();
+        for part in parts {
+            let (key, value) = part.split_once('=').unwrap_or((part, ""));
+            match key {
+                "retries" => opts.retries = value.parse()?,
+                "proxy" => opts.proxy = Some(value.to_owned()),
+                "cacert" => opts.ca_cert = Some(value.into()),
+                "cert" => opts.cert = Some(value.into()),
+                "key" => opts.key = Some(value.into()),
+                "insecure" => opts.insecure = true,
+                "cache" => opts.cache = true,
+                "timeout" => opts.timeout = Some(value.parse()?),
+                _ => return Err(format!("unknown source option: {key}").into()),
+            }
+        }
+        Ok((spec, opts))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Whether a `--file` is to be fetched rather than read
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Percent-encode `part` for use as a single path segment or query value
+pub fn encode(part: &str) -> String {
+    part.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Perform a GET request and return the response body. Headers are passed to curl on stdin so
+/// that tokens do not show up in the process list.
+pub fn get(url: &str, headers: &[(&str, &str)]) -> Result<String, BoxError> {
+    get_with(url, headers, &Options::default())
+}
+
+pub fn get_with(url: &str, headers: &[(&str, &str)], opts: &Options) -> Result<String, BoxError> {
+    Ok(String::from_utf8(get_bytes_with(url, headers, opts)?)?)
+}
+
+/// Like [`get_with`], for binary responses
+pub fn get_bytes_with(
+    url: &str,
+    headers: &[(&str, &str)],
+    opts: &Options,
+) -> Result<Vec<u8>, BoxError> {
+    let cache_path = opts.cache.then(|| cache_path(url, headers)).flatten();
+    let cached = cache_path
+        .as_ref()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| {
+            let end = data.iter().position(|&b| b == b'\n')?;
+            let etag = String::from_utf8(data[..end].to_vec()).ok()?;
+            Some((etag, data[end + 1..].to_vec()))
+        });
+    let mut headers = headers.to_vec();
+    if let Some((etag, _)) = &cached {
+        headers.push(("If-None-Match", etag));
+    }
+    let mut attempt = 0;
+    let response = loop {
+        let (reason, delay) = match request(url, &headers, opts)? {
+            Ok(response)
+                if !RETRY_STATUS.contains(&response.status) && !rate_limited(&response) =>
+            {
+                break response
+            }
+            Ok(response) if attempt >= opts.retries => break response,
+            Ok(response) => (
+                format!("HTTP {}", response.status),
+                retry_delay(attempt, &response, now()),
+            ),
+            Err(reason) if attempt >= opts.retries => {
+                return Err(format!("request to {url} failed: {reason}").into())
+            }
+            Err(reason) => (reason, retry_delay(attempt, &Response::default(), now())),
+        };
+        let warning = format!(
+            "request to {url} failed ({reason}), retrying in {:.1}s",
+            delay.as_secs_f64()
+        );
+        print_warnings(opts.print_warnings, opts.color, vec![warning]);
+        thread::sleep(delay);
+        attempt += 1;
+    };
+    match (response.status, cached) {
+        (304, Some((_, body))) => Ok(body),
+        (200..=299, _) => {
+            if let (Some(path), Some(etag)) = (cache_path, response.header("ETag")) {
+                // The cache is only an optimization, failing to write it is not an error
+                let _ = write_private(&path, &[etag.as_bytes(), b"\n", &response.body].concat());
+            }
+            Ok(response.body)
+        }
+        (status, _) => Err(format!(
+            "request to {url} failed: HTTP {status}: {}",
+            String::from_utf8_lossy(&response.body).trim()
+        )
+        .into()),
+    }
+}
+
+/// Run curl once. The outer error is fatal, the inner one a transient failure.
+fn request(
+    url: &str,
+    headers: &[(&str, &str)],
+    opts: &Options,
+) -> Result<Result<Response, String>, BoxError> {
+    let mut cmd = Command::new("curl");
+    cmd.args([
+        "--silent",
+        "--show-error",
+        "--location",
+        "--dump-header",
+        "-",
+    ]);
+    if let Some(proxy) = &opts.proxy {
+        cmd.args(["--proxy", proxy]);
+    }
+    if let Some(ca_cert) = &opts.ca_cert {
+        cmd.arg("--cacert").arg(ca_cert);
+    }
+    if let Some(cert) = &opts.cert {
+        cmd.arg("--cert").arg(cert);
+    }
+    if let Some(key) = &opts.key {
+        cmd.arg("--key").arg(key);
+    }
+    if opts.insecure {
+        cmd.arg("--insecure");
+    }
+    if let Some(timeout) = opts.timeout {
+        cmd.args(["--max-time", &timeout.to_string()]);
+    }
+    let mut child = cmd
+        .args(["--header", "@-", "--", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run curl: {e}"))?;
+    {
+        let mut stdin = child.stdin.take().ok_or("could not open curl stdin")?;
+        for (name, value) in headers {
+            writeln!(stdin, "{name}: {value}")?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    match output.status.code() {
+        Some(0) => Ok(Ok(parse_response(&output.stdout)?)),
+        Some(code) if TRANSIENT_CURL_CODES.contains(&code) => Ok(Err(stderr)),
+        _ => Err(format!("request to {url} failed: {stderr}").into()),
+    }
+}
+
+/// Split curl's `--dump-header -` output. Redirects and `100 Continue` produce several header
+/// blocks, the last one belongs to the body.
+fn parse_response(mut out: &[u8]) -> Result<Response, BoxError> {
+    let mut response = Response::default();
+    while out.starts_with(b"HTTP/") {
+        let end = out
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or("truncated response headers")?;
+        let block = String::from_utf8_lossy(&out[..end]);
+        let mut lines = block.split("\r\n");
+        response.status = lines
+            .next()
+            .and_then(|status| status.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or("invalid status line")?;
+        response.headers = lines
+            .filter_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                Some((name.trim().to_owned(), value.trim().to_owned()))
+            })
+            .collect();
+        out = &out[end + 4..];
+    }
+    response.body = out.to_vec();
+    Ok(response)
+}
+
+/// GitHub style rate limiting answers 403 with no requests remaining
+fn rate_limited(response: &Response) -> bool {
+    response.status == 403 && response.header("X-RateLimit-Remaining") == Some("0")
+}
+
+/// `Retry-After` seconds, the rate limit reset time, or else exponential backoff from half a
+/// second with some jitter
+fn retry_delay(attempt: u32, response: &Response, now: Duration) -> Duration {
+    let requested = response
+        .header("Retry-After")
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .or_else(|| {
+            let reset: u64 = response.header("X-RateLimit-Reset")?.parse().ok()?;
+            Some(Duration::from_secs(reset).saturating_sub(now))
+        });
+    let delay = requested.unwrap_or_else(|| {
+        let backoff = Duration::from_millis(500) * 2u32.saturating_pow(attempt);
+        backoff + Duration::from_millis(u64::from(now.subsec_millis()) % 250)
+    });
+    delay.min(MAX_DELAY)
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Keyed by the URL and headers, so that responses for different tokens are kept apart
+fn cache_path(url: &str, headers: &[(&str, &str)]) -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?
+        .join("enw")
+        .join("http");
+    let mut key = url.to_owned();
+    for (name, value) in headers {
+        key.push_str(&format!("\n{name}: {value}"));
+    }
+    Some(dir.join(sha256::hex_digest(key.as_bytes())))
+}
+
+fn write_private(path: &Path, data: &[u8]) -> Result<(), BoxError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_response_takes_last_block() {
+        let out = b"HTTP/1.1 301 Moved\r\nLocation: /b\r\n\r\n\
+                    HTTP/2 200\r\netag: \"abc\"\r\ncontent-type: text/plain\r\n\r\nbody\r\n";
+        let response = parse_response(out).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("ETag"), Some("\"abc\""));
+        assert_eq!(response.body, b"body\r\n");
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let now = Duration::from_secs(1_000);
+        let response = |headers: &[(&str, &str)]| Response {
+            status: 429,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: Vec::new(),
+        };
+        assert_eq!(
+            retry_delay(0, &response(&[("retry-after", "7")]), now),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            retry_delay(0, &response(&[("X-RateLimit-Reset", "1012")]), now),
+            Duration::from_secs(12)
+        );
+        assert_eq!(
+            retry_delay(0, &response(&[("Retry-After", "3600")]), now),
+            MAX_DELAY
+        );
+        assert_eq!(retry_delay(2, &response(&[]), now), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_split_spec() {
+        let (spec, opts) = Options::split_spec(
+            "web/production;proxy=http://p:3128;cacert=/ca.pem;cache;timeout=5",
+        )
+        .unwrap();
+        assert_eq!(spec, "web/production");
+        assert_eq!(
+            opts,
+            Options {
+                proxy: Some("http://p:3128".into()),
+                ca_cert: Some("/ca.pem".into()),
+                cache: true,
+                timeout: Some(5),
+                ..Options::default()
+            }
+        );
+        assert!(Options::split_spec("web;bogus=1").is_err());
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url(Path::new("https://config.internal/app/.env")));
+        assert!(!is_url(Path::new("http/.env")));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("my-app_1.0~"), "my-app_1.0~");
+        assert_eq!(encode("a/b?c&d=é"), "a%2Fb%3Fc%26d%3D%C3%A9");
+    }
+}
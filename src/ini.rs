@@ -0,0 +1,216 @@
+//! INI and TOML files as an env source. Keys in a section are either taken as is, when a single
+//! section is selected with `--section`, or prefixed with the section name: `[database] url=…`
+//! becomes `DATABASE_URL`. Nested TOML tables are joined the same way, `[database.pool]` giving
+//! `DATABASE_POOL_…`, and array items are numbered, `HOSTS_0`.
+
+use crate::{key_is_valid, toml, BoxError};
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Only load keys from this section
+    pub section: Option<String>,
+    /// Prefix keys in the selected section with the section name
+    pub section_prefix: bool,
+}
+
+pub fn parse(text: &str, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    let mut section: Option<&str> = None;
+    let mut out = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.strip_suffix(']') {
+                Some(name) => section = Some(name.trim()),
+                None => out.push(Err(
+                    format!("line {}: unterminated section header", i + 1).into()
+                )),
+            }
+            continue;
+        }
+        if let Some(selected) = &opts.section {
+            if section != Some(selected.as_str()) {
+                continue;
+            }
+        }
+        let Some((key, value)) = line.split_once(['=', ':']) else {
+            out.push(Err(format!("line {}: expected KEY=VALUE", i + 1).into()));
+            continue;
+        };
+        let key = key.trim();
+        let key = match section {
+            Some(section) if opts.section.is_none() || opts.section_prefix => {
+                format!("{section}_{key}").to_uppercase()
+            }
+            _ => key.to_owned(),
+        };
+        if !key_is_valid(&key) {
+            out.push(Err(format!("KEY contains invalid characters: {key}").into()));
+            continue;
+        }
+        out.push(Ok((key, unquote(value.trim()).to_owned())));
+    }
+    out
+}
+
+pub fn parse_toml(text: &str, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    let doc = match toml::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => return vec![Err(e)],
+    };
+    let mut out = Vec::new();
+    for (table, values) in &doc {
+        if opts
+            .section
+            .as_ref()
+            .is_some_and(|selected| selected != table)
+        {
+            continue;
+        }
+        let section = (!table.is_empty() && (opts.section.is_none() || opts.section_prefix))
+            .then(|| table.replace('.', "_"));
+        for (key, value) in values {
+            let key = match &section {
+                Some(section) => format!("{section}_{key}").to_uppercase(),
+                None => key.clone(),
+            };
+            push_toml(key, value, &mut out);
+        }
+    }
+    out
+}
+
+fn push_toml(key: String, value: &toml::Value, out: &mut Vec<Result<(String, String), BoxError>>) {
+    let value = match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Bool(b) => b.to_string(),
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                push_toml(format!("{key}_{i}"), item, out);
+            }
+            return;
+        }
+    };
+    if key_is_valid(&key) {
+        out.push(Ok((key, value)));
+    } else {
+        out.push(Err(format!("KEY contains invalid characters: {key}").into()));
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const INI: &str = "
+        ; global settings
+        debug = true
+        [database]
+        host = localhost
+        port: 5432
+        # a comment
+        password = \"se;cret\"
+        [cache]
+        host = redis
+    ";
+
+    fn parse_ok(opts: &Options) -> Vec<(String, String)> {
+        parse(INI, opts)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn owned(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_all_sections_are_prefixed() {
+        assert_eq!(
+            parse_ok(&Options::default()),
+            owned(&[
+                ("debug", "true"),
+                ("DATABASE_HOST", "localhost"),
+                ("DATABASE_PORT", "5432"),
+                ("DATABASE_PASSWORD", "se;cret"),
+                ("CACHE_HOST", "redis"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_section_selection() {
+        let mut opts = Options {
+            section: Some("database".to_owned()),
+            section_prefix: false,
+        };
+        assert_eq!(
+            parse_ok(&opts),
+            owned(&[
+                ("host", "localhost"),
+                ("port", "5432"),
+                ("password", "se;cret")
+            ])
+        );
+        opts.section_prefix = true;
+        assert_eq!(
+            parse_ok(&opts)[0],
+            ("DATABASE_HOST".to_owned(), "localhost".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_toml() {
+        let text = "
+            debug = true
+            [database]
+            url = \"postgres://db\"
+            hosts = [\"a\", \"b\"]
+            [database.pool]
+            size = 5
+        ";
+        let pairs = |opts: &Options| -> Vec<(String, String)> {
+            parse_toml(text, opts)
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(
+            pairs(&Options::default()),
+            owned(&[
+                ("debug", "true"),
+                ("DATABASE_HOSTS_0", "a"),
+                ("DATABASE_HOSTS_1", "b"),
+                ("DATABASE_URL", "postgres://db"),
+                ("DATABASE_POOL_SIZE", "5"),
+            ])
+        );
+        let opts = Options {
+            section: Some("database.pool".to_owned()),
+            section_prefix: false,
+        };
+        assert_eq!(pairs(&opts), owned(&[("size", "5")]));
+        assert!(parse_toml("[a]\n[a]\n", &Options::default())[0].is_err());
+    }
+}
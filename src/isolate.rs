@@ -0,0 +1,142 @@
+//! `--isolate net,pid,...` runs the command in fresh Linux namespaces, e.g. without network
+//! access or with a private /tmp. Unprivileged users get a user namespace as well, mapping
+//! their own ids.
+
+use std::{ffi::CString, io, ptr};
+
+use libc::c_int;
+
+use crate::BoxError;
+
+#[derive(Debug)]
+pub struct Isolation {
+    flags: c_int,
+    /// Mount a fresh tmpfs on /tmp
+    private_tmp: bool,
+    /// Paths and contents to write after creating the user namespace, prepared before fork
+    id_maps: Vec<(CString, CString)>,
+}
+
+impl Isolation {
+    pub fn parse(values: &[String]) -> Result<Self, BoxError> {
+        let mut flags = 0;
+        let mut private_tmp = false;
+        for value in values {
+            flags |= match value.as_str() {
+                "net" => libc::CLONE_NEWNET,
+                "ipc" => libc::CLONE_NEWIPC,
+                "uts" => libc::CLONE_NEWUTS,
+                "mount" => libc::CLONE_NEWNS,
+                // A new pid namespace needs its own /proc to be of any use
+                "pid" => libc::CLONE_NEWPID | libc::CLONE_NEWNS,
+                "tmp" => {
+                    private_tmp = true;
+                    libc::CLONE_NEWNS
+                }
+                _ => return Err(format!("unknown namespace: {value}").into()),
+            };
+        }
+        let mut id_maps = Vec::new();
+        // SAFETY: no preconditions
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        if uid != 0 {
+            flags |= libc::CLONE_NEWUSER;
+            for (path, content) in [
+                ("/proc/self/setgroups", "deny".to_owned()),
+                ("/proc/self/uid_map", format!("{uid} {uid} 1")),
+                ("/proc/self/gid_map", format!("{gid} {gid} 1")),
+            ] {
+                id_maps.push((CString::new(path)?, CString::new(content)?));
+            }
+        }
+        Ok(Isolation {
+            flags,
+            private_tmp,
+            id_maps,
+        })
+    }
+
+    /// Enter the namespaces. Runs between fork and exec, so it only makes async-signal-safe
+    /// calls. A pid namespace only applies to children, so this forks once more and waits in
+    /// the parent, which exits with the status of the command.
+    pub fn apply(&self) -> io::Result<()> {
+        // SAFETY: all pointers passed are valid NUL terminated strings or null where allowed
+        unsafe {
+            check(libc::unshare(self.flags))?;
+            for (path, content) in &self.id_maps {
+                let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY))?;
+                let len = content.as_bytes().len();
+                let written = libc::write(fd, content.as_ptr().cast(), len);
+                libc::close(fd);
+                if written != len as isize {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            if self.flags & libc::CLONE_NEWNS != 0 {
+                // Keep mounts from propagating back to the host
+                check(libc::mount(
+                    ptr::null(),
+                    c"/".as_ptr(),
+                    ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    ptr::null(),
+                ))?;
+            }
+            if self.private_tmp {
+                check(libc::mount(
+                    c"tmpfs".as_ptr(),
+                    c"/tmp".as_ptr(),
+                    c"tmpfs".as_ptr(),
+                    0,
+                    ptr::null(),
+                ))?;
+            }
+            if self.flags & libc::CLONE_NEWPID != 0 {
+                let pid = check(libc::fork())?;
+                if pid != 0 {
+                    let mut status = 0;
+                    while libc::waitpid(pid, &mut status, 0) == -1 {}
+                    libc::_exit(if libc::WIFEXITED(status) {
+                        libc::WEXITSTATUS(status)
+                    } else {
+                        128 + libc::WTERMSIG(status)
+                    });
+                }
+                check(libc::mount(
+                    c"proc".as_ptr(),
+                    c"/proc".as_ptr(),
+                    c"proc".as_ptr(),
+                    0,
+                    ptr::null(),
+                ))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let isolation = Isolation::parse(&["net".into(), "tmp".into()]).unwrap();
+        assert_eq!(
+            isolation.flags & !libc::CLONE_NEWUSER,
+            libc::CLONE_NEWNET | libc::CLONE_NEWNS
+        );
+        assert!(isolation.private_tmp);
+        assert!(Isolation::parse(&["cgroup".into()]).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! Minimal JSON support, just enough to talk to provider APIs and emit machine readable
+//! output without pulling in a serialization framework.
+
+use std::{collections::BTreeMap, fmt, iter::Peekable, str::Chars};
+
+use crate::BoxError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, BoxError> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("error parsing json: trailing character: {c}").into()),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, BoxError> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut map = BTreeMap::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Value::Object(map));
+            }
+            loop {
+                skip_whitespace(chars);
+                if chars.next() != Some('"') {
+                    return Err("error parsing json: expected object key".into());
+                }
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return Err("error parsing json: expected ':'".into());
+                }
+                map.insert(key, parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Object(map)),
+                    _ => return Err("error parsing json: expected ',' or '}'".into()),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("error parsing json: expected ',' or ']'".into()),
+                }
+            }
+        }
+        Some('"') => {
+            chars.next();
+            Ok(Value::String(parse_string(chars)?))
+        }
+        Some('t') => parse_literal(chars, "true", Value::Bool(true)),
+        Some('f') => parse_literal(chars, "false", Value::Bool(false)),
+        Some('n') => parse_literal(chars, "null", Value::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            number
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| format!("error parsing json: invalid number: {number}").into())
+        }
+        Some(c) => Err(format!("error parsing json: unexpected character: {c}").into()),
+        None => Err("error parsing json: unexpected end of input".into()),
+    }
+}
+
+fn parse_literal(chars: &mut Peekable<Chars>, word: &str, value: Value) -> Result<Value, BoxError> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("error parsing json: expected {word}").into());
+        }
+    }
+    Ok(value)
+}
+
+/// Parse the remainder of a string, the opening quote already consumed
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, BoxError> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('/') => out.push('/'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let high = parse_hex4(chars)?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("error parsing json: unpaired surrogate".into());
+                        }
+                        let low = parse_hex4(chars)?;
+                        0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                    } else {
+                        high
+                    };
+                    out.push(char::from_u32(code).ok_or("error parsing json: invalid escape")?);
+                }
+                _ => return Err("error parsing json: invalid escape".into()),
+            },
+            Some(c) => out.push(c),
+            None => return Err("error parsing json: unterminated string".into()),
+        }
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, BoxError> {
+    let hex: String = chars.take(4).collect();
+    u32::from_str_radix(&hex, 16)
+        .map_err(|_| format!("error parsing json: invalid unicode escape: {hex}").into())
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Quote and escape a string as a JSON string literal
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "{}", quote(s)),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{value}", quote(key))?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Render with two space indentation, one element per line
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) {
+        let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+        match self {
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    indent(out, depth + 1);
+                    item.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push(']');
+            }
+            Value::Object(map) if !map.is_empty() => {
+                out.push_str("{\n");
+                for (i, (key, value)) in map.iter().enumerate() {
+                    indent(out, depth + 1);
+                    out.push_str(&quote(key));
+                    out.push_str(": ");
+                    value.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push('}');
+            }
+            _ => out.push_str(&self.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_nested() {
+        let value =
+            parse(r#" {"envs": [{"key": "A", "value": "x\"yå", "n": -1.5e2, "t": true}, null]} "#)
+                .unwrap();
+        let envs = value.get("envs").and_then(Value::as_array).unwrap();
+        assert_eq!(envs[0].get("key").and_then(Value::as_str), Some("A"));
+        assert_eq!(envs[0].get("value").and_then(Value::as_str), Some("x\"yå"));
+        assert_eq!(envs[0].get("n"), Some(&Value::Number(-150.0)));
+        assert_eq!(envs[0].get("t"), Some(&Value::Bool(true)));
+        assert_eq!(envs[1], Value::Null);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in &["", "{", r#"{"a" 1}"#, "[1,]", r#""abc"#, "tru", "{} x"] {
+            assert!(parse(input).is_err(), "expected err: {input}");
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let input = r#"{"a":["line\nbreak","tab\t",null,false],"b":{}}"#;
+        assert_eq!(parse(input).unwrap().to_string(), input);
+    }
+}
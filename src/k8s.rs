@@ -0,0 +1,170 @@
+//! `enw k8s --name NAME` writes the resolved variables as Kubernetes manifests: secrets in a
+//! Secret and the rest in a ConfigMap, or all of them in one with `--secret` or `--configmap`.
+
+use crate::{json, BoxError, Var};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Secret,
+    ConfigMap,
+}
+
+pub struct Manifest<'a> {
+    pub name: &'a str,
+    pub namespace: Option<&'a str>,
+    /// Put every variable in this kind of object, instead of telling them apart
+    pub kind: Option<Kind>,
+}
+
+impl Manifest<'_> {
+    /// The YAML documents, a ConfigMap and then a Secret, each left out when it would be empty
+    /// unless everything goes in it
+    pub fn render(&self, vars: &[Var]) -> Result<String, BoxError> {
+        for name in [Some(self.name), self.namespace].into_iter().flatten() {
+            let valid = !name.is_empty()
+                && name.len() <= 253
+                && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+                && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+                && name.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.')
+                });
+            if !valid {
+                return Err(format!("not a valid Kubernetes name: {name}").into());
+            }
+        }
+        if let Some(var) = vars.iter().find(|var| {
+            !var.key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        }) {
+            return Err(format!("{} is not a valid ConfigMap or Secret key", var.key).into());
+        }
+        let (secrets, config): (Vec<&Var>, Vec<&Var>) = match self.kind {
+            Some(Kind::Secret) => (vars.iter().collect(), Vec::new()),
+            Some(Kind::ConfigMap) => (Vec::new(), vars.iter().collect()),
+            None => vars.iter().partition(|var| var.is_secret()),
+        };
+        let mut docs = Vec::new();
+        if !config.is_empty() || self.kind == Some(Kind::ConfigMap) {
+            docs.push(self.document(Kind::ConfigMap, &config));
+        }
+        if !secrets.is_empty() || self.kind == Some(Kind::Secret) {
+            docs.push(self.document(Kind::Secret, &secrets));
+        }
+        Ok(docs.join("---\n"))
+    }
+
+    fn document(&self, kind: Kind, vars: &[&Var]) -> String {
+        let mut out = String::from("apiVersion: v1\n");
+        out.push_str(match kind {
+            Kind::Secret => "kind: Secret\n",
+            Kind::ConfigMap => "kind: ConfigMap\n",
+        });
+        out.push_str(&format!("metadata:\n  name: {}\n", self.name));
+        if let Some(namespace) = self.namespace {
+            out.push_str(&format!("  namespace: {namespace}\n"));
+        }
+        if kind == Kind::Secret {
+            out.push_str("type: Opaque\n");
+        }
+        if vars.is_empty() {
+            out.push_str("data: {}\n");
+            return out;
+        }
+        out.push_str("data:\n");
+        for var in vars {
+            // JSON string literals are valid YAML double quoted scalars
+            let value = match kind {
+                Kind::Secret => base64(var.value.as_bytes()),
+                Kind::ConfigMap => json::quote(&var.value),
+            };
+            out.push_str(&format!("  {}: {value}\n", var.key));
+        }
+        out
+    }
+}
+
+/// Standard base64 with padding, as Secret data is encoded
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    #[test]
+    fn test_base64() {
+        for (data, expected) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foobar", "Zm9vYmFy"),
+            ("a\nb", "YQpi"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), expected);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let vars: Vec<Var> = [("DB_PASSWORD", "hunter2"), ("PORT", "80")]
+            .iter()
+            .map(|(key, value)| Var::new((*key).into(), (*value).into(), Origin::CommandLine))
+            .collect();
+        let manifest = Manifest {
+            name: "myapp",
+            namespace: Some("prod"),
+            kind: None,
+        };
+        assert_eq!(
+            manifest.render(&vars).unwrap(),
+            "apiVersion: v1\n\
+             kind: ConfigMap\n\
+             metadata:\n  name: myapp\n  namespace: prod\n\
+             data:\n  PORT: \"80\"\n\
+             ---\n\
+             apiVersion: v1\n\
+             kind: Secret\n\
+             metadata:\n  name: myapp\n  namespace: prod\n\
+             type: Opaque\n\
+             data:\n  DB_PASSWORD: aHVudGVyMg==\n"
+        );
+        let manifest = Manifest {
+            name: "myapp",
+            namespace: None,
+            kind: Some(Kind::Secret),
+        };
+        assert_eq!(
+            manifest.render(&vars[1..]).unwrap(),
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: myapp\ntype: Opaque\ndata:\n  PORT: ODA=\n"
+        );
+        let manifest = Manifest {
+            name: "My_App",
+            namespace: None,
+            kind: None,
+        };
+        assert!(manifest.render(&vars).is_err());
+    }
+}
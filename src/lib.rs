@@ -1,16 +1,115 @@
 use std::{
-    collections::HashMap, env, ffi::OsString, fs, os::unix::process::CommandExt, path::PathBuf,
-    process::Command,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    ffi::{OsStr, OsString},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::OpenOptionsExt,
+        process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{self, Command, ExitStatus, Stdio},
+    time::{Duration, Instant},
 };
 
-use clap::{App, AppSettings, Arg, ArgMatches};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use pipeline::Pipeline;
+
+mod bench;
+mod color;
+mod compare;
+mod completions;
+mod config;
+mod credentials;
+mod crypt;
+mod danger;
+mod dialect;
+mod discovery;
+mod embed;
+mod expiry;
+mod explain;
+mod export;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod forward;
+mod git;
+mod glob;
+mod hook;
+mod http;
+mod ini;
+#[cfg(target_os = "linux")]
+mod isolate;
+mod json;
+mod k8s;
+mod limits;
+mod lock;
+mod lookup;
+mod lsp;
+mod php;
+pub mod pipeline;
+mod placeholder;
+mod preset;
+mod print;
+mod profile;
+mod prompt;
+mod properties;
+mod provider;
+mod pty;
+mod redact;
+mod reference;
+mod render;
+mod report;
+#[cfg(target_os = "linux")]
+mod sandbox;
+mod scan;
+mod schema;
+mod secret_file;
+mod sha256;
+mod shell;
+mod signal;
+mod snapshot;
+mod structured;
+mod sync;
+mod systemd;
+mod toml;
+mod trace;
+mod trust;
+mod update;
+mod verify;
+mod watch;
+mod workspace;
+mod yaml;
+
+pub use embed::{exec, load_env, Options};
 
 pub type BoxError = Box<dyn std::error::Error>;
+/// Looks up the value of a variable referenced from another value
+type Resolve<'a> = &'a dyn Fn(&str) -> Option<String>;
+/// Runs the command of a `$(command)` substitution, returning its output
+type Substitute<'a> = &'a dyn Fn(&str) -> Result<String, BoxError>;
 
 const ABOUT: &str =
     "Similar to the GNU env command, but will automatically load an .env file, if found.";
-const USAGE: &str = "enw [OPTION]... [-] [NAME=VALUE] [COMMAND [ARGS]...]";
+const USAGE: &str = "enw [OPTION]... [-] [NAME=VALUE]... [--] [COMMAND [ARGS]...]";
 const DEFAULT_ENV_FILE_NAME: &str = ".env";
+/// Bearer token for env files fetched from URLs, kept out of the process list
+const FILE_AUTH_VAR: &str = "ENW_FILE_AUTH";
+/// Set to 0 to make --no-override the default
+const OVERRIDE_VAR: &str = "ENW_OVERRIDE";
+/// Variable names matching any of these (case insensitively) are treated as secrets
+const SECRET_KEY_PATTERNS: &[&str] = &[
+    "*SECRET*",
+    "*TOKEN*",
+    "*PASSWORD*",
+    "*PASSWD*",
+    "*CREDENTIAL*",
+    "*PRIVATE*",
+    "*_KEY",
+];
 
 #[derive(Debug)]
 struct EnvFile {
@@ -22,142 +121,2398 @@ struct EnvFile {
 struct OptionsBuilder {
     env_files: Vec<EnvFile>,
     vars: Vec<(String, String)>,
-    command: Option<String>,
-    args: Vec<String>,
+    providers: Vec<String>,
+    /// Patterns of names to treat as secrets besides `SECRET_KEY_PATTERNS`, from the config
+    secret_patterns: Vec<String>,
+    show_secrets: bool,
+    /// Bearer token and HTTP settings for env files given as URLs
+    file_auth: Option<String>,
+    remote: http::Options,
+    /// NAME=VALUE pairs from the command line that are not valid UTF-8, passed on as they are
+    os_vars: Vec<(OsString, OsString)>,
+    command: Option<OsString>,
+    args: Vec<OsString>,
+    /// The environment of `--from-snapshot`, in place of the inherited one
+    snapshot: Option<snapshot::Environment>,
+    /// Commands to run after COMMAND, separated from it by `--and`
+    then: Vec<(OsString, Vec<OsString>)>,
+    parallel: bool,
+    /// Run COMMAND in a pseudo-terminal of its own
+    pty: bool,
     ignore_env: bool,
     load_implicit_env_file: bool,
     print_warnings: bool,
+    export: Option<export::Export>,
+    print: Option<print::Format>,
+    ini: ini::Options,
+    properties: properties::Options,
+    structured: structured::Options,
+    format: Option<FileFormat>,
+    compat: Option<Compat>,
+    allow_placeholders: bool,
+    secret_via_file: Vec<String>,
+    secret_via_fd: Vec<String>,
+    dangerous_vars: danger::Policy,
+    verify: Option<verify::Keys>,
+    strict_expiry: bool,
+    /// Report keys that env files define more than once, failing with `strict_duplicates`
+    warn_duplicates: bool,
+    strict_duplicates: bool,
+    empty_is_unset: bool,
+    unset: Vec<OsString>,
+    no_override: bool,
+    inherit_prefixes: Vec<String>,
+    only: Vec<String>,
+    except: Vec<String>,
+    /// Only load variables whose name starts with this, and remove it from their names
+    prefix: Option<String>,
+    strip_prefix: bool,
+    report: Option<PathBuf>,
+    signals: signal::Dispositions,
+    search_path: Option<OsString>,
+    color: color::Choice,
+    isolate: Vec<String>,
+    sandbox: Vec<String>,
+    systemd_run: Option<systemd::Unit>,
+    systemd_properties: Vec<String>,
+    frozen: Option<PathBuf>,
+    strict_secrets: bool,
+    profile: Option<String>,
+    profile_vars: Vec<(String, String)>,
+    /// The project's `.enw.toml`, which has to be trusted for the profile variables and the
+    /// providers it declares to set dangerous variables
+    project_config: Option<PathBuf>,
+    /// The providers named in `project_config`
+    project_providers: Vec<String>,
+    no_expand: bool,
+    raw_values: bool,
+    strict: bool,
+    verbose: bool,
+    explain: Option<String>,
+    require_schema: bool,
+    shell: bool,
+    chdir: Option<PathBuf>,
+    kill_timeout: Option<u32>,
+    timeout: Option<Duration>,
+    kill_after: Option<Duration>,
+    fork: bool,
+    allow_file_refs: bool,
+    allow_aws_refs: bool,
+    allow_cmd_refs: bool,
+    allow_exec: bool,
+    /// End output lines, and --stdin input, with NUL instead of newline
+    null: bool,
+    /// Output of `$(command)` substitutions, so that each command runs once per load
+    command_outputs: RefCell<HashMap<String, String>>,
+}
+
+/// Parsing rules of another dotenv implementation to follow for dotenv files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compat {
+    Php,
+    Compose,
+    Node,
+    Shell,
+}
+
+/// The format of an env file, by default told by its extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FileFormat {
+    Dotenv,
+    Ini,
+    Properties,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl FileFormat {
+    const NAMES: &'static [&'static str] = &["dotenv", "ini", "properties", "json", "yaml", "toml"];
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "ini" => Some(FileFormat::Ini),
+            "properties" => Some(FileFormat::Properties),
+            "json" => Some(FileFormat::Json),
+            "yaml" | "yml" => Some(FileFormat::Yaml),
+            "toml" => Some(FileFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// A resolved variable together with where it came from
+#[derive(Clone, Debug)]
+pub struct Var {
+    pub key: String,
+    pub value: String,
+    pub origin: Origin,
+    pub(crate) expires: Option<expiry::Date>,
+    /// The line of the env file setting it, from 1
+    pub(crate) line: Option<usize>,
+    /// Named a secret by the config
+    pub(crate) secret: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    File(PathBuf),
+    /// A provider, optionally with a reference (e.g. an ARN) naming where the value is stored
+    Provider {
+        name: String,
+        reference: Option<String>,
+    },
+    /// A profile in `.enw.toml`
+    Profile(String),
+    CommandLine,
+}
+
+impl Var {
+    pub fn new(key: String, value: String, origin: Origin) -> Self {
+        Var {
+            key,
+            value,
+            origin,
+            expires: None,
+            line: None,
+            secret: false,
+        }
+    }
+
+    /// Whether the value should be kept out of command lines and logs, either because a
+    /// provider stores it as a secret or because the name looks like one.
+    pub fn is_secret(&self) -> bool {
+        let key = self.key.to_ascii_uppercase();
+        self.secret
+            || matches!(
+                self.origin,
+                Origin::Provider {
+                    reference: Some(_),
+                    ..
+                }
+            )
+            || SECRET_KEY_PATTERNS
+                .iter()
+                .any(|pattern| glob::matches(pattern, &key))
+    }
+}
+
+/// Run enw and return the exit code to exit with: COMMAND's, if it ran
+pub fn run(args: impl Iterator<Item = impl Into<OsString> + Clone>) -> Result<i32, BoxError> {
+    run_with(args, &Pipeline::new())
+}
+
+/// Exit codes of GNU env, for enw's own errors and for a command which cannot be run
+pub const EXIT_FAILURE: i32 = 125;
+pub const EXIT_CANNOT_EXECUTE: i32 = 126;
+pub const EXIT_NOT_FOUND: i32 = 127;
+/// Exit code of GNU timeout, for a command stopped by `--timeout`
+pub const EXIT_TIMEOUT: i32 = 124;
+
+/// Failing to run the command, with the exit code to report it with
+pub struct CommandError {
+    pub code: i32,
+    message: String,
+}
+
+impl CommandError {
+    fn not_found(message: String) -> Self {
+        CommandError {
+            code: EXIT_NOT_FOUND,
+            message,
+        }
+    }
+
+    /// Classify a failed exec or spawn
+    fn io(command: &OsStr, e: io::Error) -> Self {
+        let code = match e.kind() {
+            io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+            _ => EXIT_CANNOT_EXECUTE,
+        };
+        CommandError {
+            code,
+            message: format!("{}: {e}", command.to_string_lossy()),
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// Printed by main like the other errors, which are strings
+impl std::fmt::Debug for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// The exit code to report an error of [`run`] with
+pub fn error_exit_code(e: &BoxError) -> i32 {
+    e.downcast_ref::<CommandError>()
+        .map_or(EXIT_FAILURE, |e| e.code)
+}
+
+/// The exit code a shell would report for the status: the code, or 128 plus the signal number
+/// for a command killed by a signal
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default())
+}
+
+/// Like [`run`], with extra stages added to the resolution pipeline
+pub fn run_with(
+    args: impl Iterator<Item = impl Into<OsString> + Clone>,
+    pipeline: &Pipeline,
+) -> Result<i32, BoxError> {
+    let started = Instant::now();
+    let matches = parse_arguments(args);
+    if matches.is_present("chdir_first") {
+        let dir = matches.value_of_os("chdir").unwrap_or_default();
+        env::set_current_dir(dir)
+            .map_err(|e| format!("cannot change directory to {}: {e}", dir.to_string_lossy()))?;
+    }
+    if let Some(trust) = matches.subcommand_matches("trust") {
+        let paths: Vec<PathBuf> = trust
+            .values_of_os("file")
+            .map(|files| files.map(PathBuf::from).collect())
+            .unwrap_or_else(|| vec![PathBuf::from(DEFAULT_ENV_FILE_NAME)]);
+        trust::trust(&paths)?;
+        return Ok(0);
+    }
+    if let Some(bench) = matches.subcommand_matches("bench") {
+        let iterations: u32 = bench.value_of("iterations").unwrap_or_default().parse()?;
+        let command = values_of_os(bench, "command");
+        let mut table = bench::Table::default();
+        for i in 0..iterations {
+            let started = Instant::now();
+            let opt_builder = OptionsBuilder::with_arg_matches(matches.clone())?;
+            let (print, color) = (opt_builder.print_warnings, opt_builder.color);
+            let mut ctx = pipeline::Context::new(opt_builder);
+            let result = pipeline.run(&mut ctx);
+            // The same warnings come up on every iteration
+            print_warnings(print && i == 0, color, mem::take(&mut ctx.warnings));
+            result?;
+            for (phase, time) in &ctx.phase_timings {
+                table.record(phase.name(), *time);
+                if *phase == pipeline::Phase::Parse {
+                    for (spec, time) in &ctx.provider_timings {
+                        table.record(&format!("  {spec}"), *time);
+                    }
+                }
+            }
+            if let Some((program, args)) = command.split_first() {
+                let child_env = child_environment(&ctx.opts, ctx.unset.clone(), &ctx.vars);
+                let spawned = Instant::now();
+                let mut child = Command::new(program)
+                    .args(args)
+                    .env_clear()
+                    .envs(child_env)
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .map_err(|e| format!("{}: {e}", program.to_string_lossy()))?;
+                table.record("spawn", spawned.elapsed());
+                child.wait()?;
+                table.record("command", spawned.elapsed());
+            }
+            table.record("total", started.elapsed());
+        }
+        print!("{}", table.render());
+        return Ok(0);
+    }
+    if let Some(completions) = matches.subcommand_matches("completions") {
+        if completions.is_present("names") {
+            let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+            let names: BTreeSet<&str> = ctx.vars.iter().map(|var| var.key.as_str()).collect();
+            for name in names {
+                println!("{name}");
+            }
+            return Ok(0);
+        }
+        let shell = completions.value_of("shell").unwrap_or_default();
+        print!(
+            "{}",
+            completions::script(app(AppSettings::ColorNever), shell)?
+        );
+        return Ok(0);
+    }
+    if let Some(login) = matches.subcommand_matches("login") {
+        let provider = login.value_of("provider").unwrap_or_default();
+        if login.is_present("forget") {
+            credentials::forget(provider)?;
+        } else {
+            credentials::login(provider, &login.values_of_lossy("args").unwrap_or_default())?;
+        }
+        return Ok(0);
+    }
+    if matches.subcommand_matches("lsp").is_some() {
+        lsp::serve()?;
+        return Ok(0);
+    }
+    if let Some(prompt) = matches.subcommand_matches("prompt") {
+        let format = prompt.value_of("format").unwrap_or(prompt::DEFAULT_FORMAT);
+        if let Some(line) = prompt::format(format, |key| env::var(key).ok()) {
+            println!("{line}");
+        }
+        return Ok(0);
+    }
+    if matches.subcommand_matches("check").is_some() {
+        let ctx = resolve(OptionsBuilder::with_arg_matches(matches)?, pipeline)?;
+        pipeline::check_schema(&ctx)?;
+        println!("ok");
+        return Ok(0);
+    }
+    if let Some(scan) = matches.subcommand_matches("scan") {
+        let root = Path::new(scan.value_of_os("dir").unwrap_or(".".as_ref()));
+        let declared: Vec<String> = fs::read_to_string(root.join(placeholder::EXAMPLE_FILE_NAME))
+            .map(|text| {
+                parse_env_doc(&text)
+                    .into_iter()
+                    .flatten()
+                    .map(|(key, _)| key)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let report = scan::report(&scan::scan(root)?, &declared);
+        for line in &report {
+            println!("{line}");
+        }
+        return if report.is_empty() {
+            Ok(0)
+        } else {
+            Err("code and declared variables disagree".into())
+        };
+    }
+    if let Some(update) = matches.subcommand_matches("self-update") {
+        update::self_update(
+            update.value_of("channel").unwrap_or_default().parse()?,
+            update.is_present("check"),
+            update.value_of_os("key").map(Path::new),
+        )?;
+        return Ok(0);
+    }
+    if let Some(encrypt) = matches.subcommand_matches("encrypt") {
+        let path = Path::new(encrypt.value_of_os("file").unwrap_or_default());
+        let output = encrypt.value_of_os("output").map_or_else(
+            || {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".{}", crypt::EXTENSION));
+                PathBuf::from(name)
+            },
+            PathBuf::from,
+        );
+        let method = encrypt.value_of("method").unwrap_or_default().parse()?;
+        fs::write(&output, crypt::encrypt(method, &fs::read(path)?)?)?;
+        eprintln!("wrote {}", output.to_string_lossy());
+        return Ok(0);
+    }
+    if let Some(decrypt) = matches.subcommand_matches("decrypt") {
+        let path = Path::new(decrypt.value_of_os("file").unwrap_or_default());
+        let plain = crypt::decrypt(path, &fs::read(path)?)?;
+        match decrypt.value_of_os("output") {
+            Some(output) => OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(output)?
+                .write_all(&plain)?,
+            None => io::stdout().write_all(&plain)?,
+        }
+        return Ok(0);
+    }
+    if let Some(hook) = matches.subcommand_matches("hook") {
+        let shell: hook::Shell = hook.value_of("shell").unwrap_or_default().parse()?;
+        print!("{}", shell.hook(&env::current_exe()?));
+        return Ok(0);
+    }
+    if let Some(export) = matches.subcommand_matches("export") {
+        if let Ok(shell) = export_format(export).parse() {
+            print!("{}", hook::export(shell)?);
+            return Ok(0);
+        }
+    }
+    if let Some(render) = matches.subcommand_matches("render") {
+        let template = match render.value_of_os("template").unwrap_or_default() {
+            path if path == "-" => io::read_to_string(io::stdin())?,
+            path => {
+                fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?
+            }
+        };
+        let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+        let child_env = child_environment(&ctx.opts, ctx.unset, &ctx.vars);
+        let lookup = |key: &str| {
+            let value = child_env.get(OsStr::new(key))?;
+            Some(value.to_string_lossy().into_owned())
+        };
+        let output = render::render(&template, &lookup)?;
+        match render.value_of_os("output") {
+            Some(path) => fs::write(path, output)?,
+            None => io::stdout().write_all(output.as_bytes())?,
+        }
+        return Ok(0);
+    }
+    if let Some(sync) = matches.subcommand_matches("sync") {
+        let remote = sync::Remote::parse(sync.value_of("remote").unwrap_or_default())?;
+        let force = sync.is_present("force");
+        if sync.value_of("action") == Some("push") {
+            let files: Vec<PathBuf> = sync
+                .values_of_os("file")
+                .map(|files| files.map(PathBuf::from).collect())
+                .unwrap_or_else(|| vec![PathBuf::from(DEFAULT_ENV_FILE_NAME)]);
+            sync::push(&remote, &files, force)?;
+        } else {
+            let version = sync.value_of("version").map(str::parse).transpose()?;
+            sync::pull(&remote, version, force)?;
+        }
+        return Ok(0);
+    }
+    if let Some(trace) = matches.subcommand_matches("trace-usage") {
+        let argv = values_of_os(trace, "command");
+        let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+        let child_env = child_environment(&ctx.opts, ctx.unset.clone(), &ctx.vars);
+        return trace::run(&ctx.vars, child_env, &argv);
+    }
+    if matches.subcommand_matches("status").is_some() {
+        let opt_builder = OptionsBuilder::with_arg_matches(matches)?;
+        if let Some(workspace) = workspace::find(&env::current_dir()?) {
+            println!("workspace: {}", workspace.root.to_string_lossy());
+            println!("package: {}", workspace.package.to_string_lossy());
+        }
+        println!("env files, lowest precedence first:");
+        for EnvFile { path, .. } in opt_builder.env_files {
+            let path = if path.is_dir() {
+                path.join(DEFAULT_ENV_FILE_NAME)
+            } else {
+                path
+            };
+            let missing = if path.is_file() { "" } else { " (not found)" };
+            println!("  {}{missing}", path.to_string_lossy());
+        }
+        if let Some(name) = &opt_builder.profile {
+            let chain = match profile::load(&env::current_dir()?, name)? {
+                Some(profile) => profile.chain,
+                None => vec![name.clone()],
+            };
+            println!("profile: {}", chain.join(" -> "));
+            let vars: BTreeMap<_, _> = opt_builder.profile_vars.into_iter().collect();
+            for (key, value) in vars {
+                println!("  {}", format_var(&key, &value));
+            }
+        }
+        return Ok(0);
+    }
+    if let Some(k8s) = matches.subcommand_matches("k8s") {
+        let manifest = k8s::Manifest {
+            name: k8s.value_of("name").unwrap_or_default(),
+            namespace: k8s.value_of("namespace"),
+            kind: if k8s.is_present("secret") {
+                Some(k8s::Kind::Secret)
+            } else if k8s.is_present("configmap") {
+                Some(k8s::Kind::ConfigMap)
+            } else {
+                None
+            },
+        };
+        let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+        print!("{}", manifest.render(&ctx.vars)?);
+        return Ok(0);
+    }
+    if let Some(freeze) = matches.subcommand_matches("freeze") {
+        let path = freeze
+            .value_of_os("file")
+            .map_or_else(|| PathBuf::from(lock::FILE_NAME), PathBuf::from);
+        let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+        lock::write(&path, &ctx.vars)?;
+        return Ok(0);
+    }
+    if let Some(snapshot) = matches.subcommand_matches("snapshot") {
+        if let Some(save) = snapshot.subcommand_matches("save") {
+            let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+            let env = child_environment(&ctx.opts, ctx.unset.clone(), &ctx.vars);
+            snapshot::save(save.value_of("name").unwrap_or_default(), &env)?;
+        } else {
+            for name in snapshot::list()? {
+                println!("{name}");
+            }
+        }
+        return Ok(0);
+    }
+    if let Some(compare) = matches.subcommand_matches("compare") {
+        let lhs = resolve_args(compare.value_of("lhs").unwrap_or_default(), pipeline)?;
+        let rhs = resolve_args(compare.value_of("rhs").unwrap_or_default(), pipeline)?;
+        let diff = compare::diff(&lhs, &rhs, matches.is_present("show_secrets"));
+        print_diff(&diff, &matches)?;
+        return if diff.is_empty() {
+            Ok(0)
+        } else {
+            Err("configurations differ".into())
+        };
+    }
+    if let Some(diff) = matches.subcommand_matches("diff") {
+        let file_vars = |path: &str| resolve_args(&format!("-f {}", shell::quote(path)), pipeline);
+        let files: Vec<&str> = diff
+            .values_of("file")
+            .into_iter()
+            .flatten()
+            .chain(diff.value_of("against"))
+            .collect();
+        let (lhs, rhs) = match files[..] {
+            [lhs, rhs] => (file_vars(lhs)?, file_vars(rhs)?),
+            [file] => {
+                let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+                (ctx.vars, file_vars(file)?)
+            }
+            _ => return Err("diff compares one or two files".into()),
+        };
+        let changes = compare::changes(&lhs, &rhs);
+        let show_secrets = matches.is_present("show_secrets");
+        match diff.value_of("format") {
+            Some("table") => print!("{}", compare::table(&changes, show_secrets)),
+            Some("json") => println!("{}", compare::json(&changes, show_secrets).pretty()),
+            _ => print_diff(&compare::diff(&lhs, &rhs, show_secrets), &matches)?,
+        }
+        return Ok(if changes.is_empty() { 0 } else { 1 });
+    }
+    if matches.is_present("watch") {
+        return watch::run(&matches, pipeline);
+    }
+    let pipeline::Context {
+        opts: opt_builder,
+        files,
+        vars: env_vars,
+        unset,
+        provider_timings,
+        ..
+    } = resolve(OptionsBuilder::with_arg_matches(matches)?, pipeline)?;
+    // Not dropped when enw replaces itself with the command, which is then left the files
+    let _secret_files = secret_file::Cleanup;
+    if opt_builder.verbose || opt_builder.explain.is_some() {
+        let child_env = child_environment(&opt_builder, unset.clone(), &env_vars);
+        if let Some(key) = &opt_builder.explain {
+            let line = explain::explain(key, &child_env, &env_vars, opt_builder.show_secrets)
+                .ok_or_else(|| format!("{key} is not set"))?;
+            println!("{line}");
+            return Ok(0);
+        }
+        for line in explain::listing(&child_env, &env_vars, opt_builder.show_secrets) {
+            eprintln!("{line}");
+        }
+    }
+    if let Some(export) = opt_builder.export {
+        print!("{}", export.render(&env_vars));
+        Ok(0)
+    } else if let Some(format) = opt_builder.print {
+        let mut child_env = child_environment(&opt_builder, unset, &env_vars);
+        if format.is_for_people() {
+            redact::env(&mut child_env, &env_vars, opt_builder.show_secrets);
+        } else {
+            let printed = env_vars
+                .iter()
+                .filter(|var| child_env.contains_key(OsStr::new(&var.key)));
+            redact::refuse_masked(printed, opt_builder.show_secrets)?;
+        }
+        io::stdout().write_all(&print::render(format, &child_env))?;
+        Ok(0)
+    } else if let Some(command) = opt_builder.command.clone() {
+        let var_count = env_vars.len();
+        if !opt_builder.then.is_empty() {
+            return run_commands(&opt_builder, &env_vars, unset);
+        }
+        let mut cmd = prepare_command(&opt_builder, &command, &opt_builder.args, &env_vars, unset)?;
+        // enw replaces itself with the command, keeping the PID, unless it has to stay around as
+        // the parent: to write the report after the command exits, to enforce the timeout, to
+        // copy to and from its terminal, or when asked to
+        if !opt_builder.fork
+            && !opt_builder.pty
+            && opt_builder.report.is_none()
+            && opt_builder.timeout.is_none()
+        {
+            return Err(CommandError::io(&command, cmd.exec()).into());
+        }
+        let pty = match opt_builder.pty {
+            true => Some(pty::Pty::open()?),
+            false => None,
+        };
+        if let Some(pty) = &pty {
+            pty.attach(&mut cmd)?;
+        }
+        let forwarding = forward::Forwarding::install(opt_builder.kill_timeout)?;
+        if pty.is_some() {
+            forwarding.forward_terminal_signals();
+        }
+        let mut child = cmd.spawn().map_err(|e| CommandError::io(&command, e))?;
+        let spawn_time = started.elapsed();
+        // Closes the command's end of the terminal, so that its output ends when it exits
+        drop(cmd);
+        let relay = pty.map(pty::Pty::relay).transpose()?;
+        let (status, timed_out) = match opt_builder.timeout {
+            Some(timeout) => {
+                forwarding.wait_timeout(&mut child, timeout, opt_builder.kill_after)?
+            }
+            None => (forwarding.wait(&mut child)?, false),
+        };
+        if let Some(relay) = relay {
+            relay.finish()?;
+        }
+        // Like GNU timeout, a command that had to be killed exits with 137
+        let code = match timed_out && status.signal() != Some(libc::SIGKILL) {
+            true => EXIT_TIMEOUT,
+            false => exit_code(status),
+        };
+        let Some(report_path) = opt_builder.report else {
+            return Ok(code);
+        };
+        let report = report::Report {
+            sources: files
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .chain(opt_builder.providers)
+                .collect(),
+            var_count,
+            provider_timings,
+            spawn_time,
+            wall_time: started.elapsed(),
+            status,
+        };
+        report.write(&report_path)?;
+        Ok(code)
+    } else if opt_builder.null {
+        let printed = env_vars
+            .iter()
+            .filter(|var| opt_builder.passes_filters(&var.key));
+        redact::refuse_masked(printed, opt_builder.show_secrets)?;
+        let mut out = io::stdout().lock();
+        for Var { key, value, .. } in env_vars {
+            if opt_builder.passes_filters(&key) {
+                write!(out, "{key}={value}\0")?;
+            }
+        }
+        for (key, value) in &opt_builder.os_vars {
+            if opt_builder.passes_filters(&key.to_string_lossy()) {
+                out.write_all(&[key.as_bytes(), b"=", value.as_bytes(), b"\0"].concat())?;
+            }
+        }
+        Ok(0)
+    } else {
+        for var in &env_vars {
+            if opt_builder.passes_filters(&var.key) {
+                let value = redact::value(var, opt_builder.show_secrets);
+                println!("{}", format_var(&var.key, value));
+            }
+        }
+        for (key, value) in &opt_builder.os_vars {
+            let key = key.to_string_lossy();
+            if opt_builder.passes_filters(&key) {
+                println!("{}", format_var(&key, &value.to_string_lossy()));
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Run COMMAND and the ones given after `--and` one after the other, stopping at the first to
+/// fail, or with `--parallel` all at once. Exits with the code of the first to fail. Signals are
+/// passed on to the running commands.
+fn run_commands(
+    opt_builder: &OptionsBuilder,
+    env_vars: &[Var],
+    unset: Vec<OsString>,
+) -> Result<i32, BoxError> {
+    let commands: Vec<(&OsString, &[OsString])> = opt_builder
+        .command
+        .iter()
+        .map(|command| (command, &opt_builder.args[..]))
+        .chain(
+            opt_builder
+                .then
+                .iter()
+                .map(|(command, args)| (command, &args[..])),
+        )
+        .collect();
+    if !opt_builder.parallel {
+        for (command, args) in commands {
+            let mut cmd = prepare_command(opt_builder, command, args, env_vars, unset.clone())?;
+            let forwarding = forward::Forwarding::install(opt_builder.kill_timeout)?;
+            let mut child = cmd.spawn().map_err(|e| CommandError::io(command, e))?;
+            let code = exit_code(forwarding.wait(&mut child)?);
+            if code != 0 {
+                return Ok(code);
+            }
+        }
+        return Ok(0);
+    }
+    // Prepared up front, so that a command which is not found fails the run before any starts
+    let mut prepared = Vec::new();
+    for (command, args) in commands {
+        let cmd = prepare_command(opt_builder, command, args, env_vars, unset.clone())?;
+        prepared.push((command, cmd));
+    }
+    let forwarding = forward::Forwarding::install(opt_builder.kill_timeout)?;
+    let mut children = Vec::new();
+    for (command, cmd) in &mut prepared {
+        children.push(cmd.spawn().map_err(|e| CommandError::io(command, e))?);
+    }
+    let statuses = forwarding.wait_all(&mut children)?;
+    let mut failed = 0;
+    for ((command, _), status) in prepared.iter().zip(statuses) {
+        let code = exit_code(status);
+        if code != 0 {
+            eprintln!("enw: {} exited with {code}", command.to_string_lossy());
+            if failed == 0 {
+                failed = code;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// The command to run, with its environment, working directory and restrictions set up
+fn prepare_command(
+    opt_builder: &OptionsBuilder,
+    command: &OsStr,
+    args: &[OsString],
+    env_vars: &[Var],
+    unset: Vec<OsString>,
+) -> Result<Command, BoxError> {
+    let (command, args) = match opt_builder.systemd_run {
+        Some(unit) => {
+            // SAFETY: no preconditions
+            let user = unsafe { libc::geteuid() } != 0;
+            let args = systemd::args(
+                unit,
+                user,
+                &opt_builder.systemd_properties,
+                env_vars,
+                command,
+                args,
+            );
+            (OsString::from("systemd-run"), args)
+        }
+        None => (command.to_owned(), args.to_vec()),
+    };
+    // Look the command up in the PATH it will see, unless told otherwise
+    let search_path = opt_builder
+        .search_path
+        .clone()
+        .or_else(|| {
+            let path = env_vars.iter().find(|var| var.key == "PATH")?;
+            Some(OsString::from(&path.value))
+        })
+        .or_else(|| env::var_os("PATH"))
+        .unwrap_or_else(|| OsString::from("/bin:/usr/bin"));
+    let mut program = lookup::find(&command, &search_path)?;
+    if let Some(dir) = &opt_builder.chdir {
+        // A relative path names a command in the new working directory
+        if program.is_relative() {
+            program = dir.join(program);
+        }
+    }
+    let mut cmd = Command::new(program);
+    cmd.arg0(&command);
+    if let Some(dir) = &opt_builder.chdir {
+        cmd.current_dir(dir);
+    }
+    let child_env = child_environment(opt_builder, unset, env_vars);
+    let argv: Vec<&OsStr> = std::iter::once(command.as_os_str())
+        .chain(args.iter().map(OsString::as_os_str))
+        .collect();
+    if let Some(warning) = limits::check(&child_env, &argv, env_vars)? {
+        print_warnings(opt_builder.print_warnings, opt_builder.color, vec![warning]);
+    }
+    if !opt_builder.signals.is_empty() {
+        let signals = opt_builder.signals.clone();
+        // SAFETY: apply only makes async-signal-safe calls
+        unsafe { cmd.pre_exec(move || signals.apply()) };
+    }
+    if !opt_builder.isolate.is_empty() {
+        isolate(&mut cmd, &opt_builder.isolate)?;
+    }
+    if !opt_builder.sandbox.is_empty() {
+        sandbox(&mut cmd, &opt_builder.sandbox)?;
+    }
+    cmd.env_clear().envs(child_env).args(args);
+    Ok(cmd)
+}
+
+/// The command's environment: the inherited one, unless ignored, with the resolved variables
+/// on top, narrowed down by `--only` and `--except`. Ordered by key, like the variables
+/// themselves.
+fn child_environment(
+    opts: &OptionsBuilder,
+    unset: Vec<OsString>,
+    env_vars: &[Var],
+) -> BTreeMap<OsString, OsString> {
+    let OptionsBuilder {
+        ignore_env,
+        inherit_prefixes,
+        os_vars,
+        ..
+    } = opts;
+    let inherited: Vec<(OsString, OsString)> = match &opts.snapshot {
+        Some(snapshot) => snapshot.clone().into_iter().collect(),
+        None => env::vars_os().collect(),
+    };
+    let mut child_env: BTreeMap<OsString, OsString> = inherited
+        .into_iter()
+        .filter(|(key, _)| {
+            let key = key.to_string_lossy();
+            !*ignore_env
+                || inherit_prefixes
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix.as_str()))
+        })
+        .collect();
+    for key in unset {
+        child_env.remove(&key);
+    }
+    for var in env_vars {
+        child_env.insert(var.key.clone().into(), var.value.clone().into());
+    }
+    for (key, value) in os_vars {
+        child_env.insert(key.clone(), value.clone());
+    }
+    child_env.retain(|key, _| opts.passes_filters(&key.to_string_lossy()));
+    child_env
+}
+
+/// `KEY=value` as printed when no command is given, the value quoted unless it is plain
+fn format_var(key: &str, value: &str) -> String {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    {
+        format!("{}={}", key, value.escape_default())
+    } else {
+        format!("{}=\"{}\"", key, value.escape_default())
+    }
+}
+
+/// Run the pipeline and print its warnings
+fn resolve(
+    opt_builder: OptionsBuilder,
+    pipeline: &Pipeline,
+) -> Result<pipeline::Context, BoxError> {
+    let mut ctx = pipeline::Context::new(opt_builder);
+    let result = pipeline.run(&mut ctx);
+    print_warnings(
+        ctx.opts.print_warnings,
+        ctx.opts.color,
+        mem::take(&mut ctx.warnings),
+    );
+    result?;
+    Ok(ctx)
+}
+
+fn print_warnings(enabled: bool, color: color::Choice, warnings: Vec<String>) {
+    if enabled {
+        let label = color::paint("warning:", color::YELLOW, color.enabled(&io::stderr()));
+        for warning in warnings {
+            eprintln!("{label} {warning}");
+        }
+    }
+}
+
+/// Resolve the variables for a command line given as a single string, without running anything
+fn resolve_args(line: &str, pipeline: &Pipeline) -> Result<Vec<Var>, BoxError> {
+    let args = std::iter::once("enw".to_owned()).chain(shell::split(line)?);
+    let opt_builder = OptionsBuilder::with_arg_matches(parse_arguments(args))?;
+    let ctx = resolve(opt_builder, pipeline)?;
+    if ctx.opts.command.is_some() || ctx.opts.export.is_some() {
+        return Err(format!("expected only options and NAME=VALUE pairs: {line}").into());
+    }
+    Ok(ctx.vars)
+}
+
+/// Print the lines of a diff, removed ones red and added ones green
+fn print_diff(diff: &[String], matches: &ArgMatches) -> Result<(), BoxError> {
+    let color = matches
+        .value_of("color")
+        .unwrap_or_default()
+        .parse::<color::Choice>()?
+        .enabled(&io::stdout());
+    for line in diff {
+        let code = if line.starts_with('-') {
+            color::RED
+        } else {
+            color::GREEN
+        };
+        println!("{}", color::paint(line, code, color));
+    }
+    Ok(())
+}
+
+/// The format given to `enw export`, as FORMAT or with --format
+fn export_format<'a>(export: &'a ArgMatches) -> &'a str {
+    export
+        .value_of("format")
+        .or_else(|| export.value_of("format_option"))
+        .unwrap_or_default()
+}
+
+/// All values of an argument, as given
+fn values_of_os(matches: &ArgMatches, name: &str) -> Vec<OsString> {
+    matches
+        .values_of_os(name)
+        .map(|values| values.map(OsStr::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Whether a positional argument is a NAME=VALUE pair: like env, one with a `=`, but only when
+/// what precedes it is a valid name, so that `./run=1` and `'a b=c'` are taken as commands
+fn is_assignment(arg: &OsStr) -> bool {
+    let bytes = arg.as_bytes();
+    bytes
+        .iter()
+        .position(|&b| b == b'=')
+        .and_then(|i| std::str::from_utf8(&bytes[..i]).ok())
+        .is_some_and(key_is_valid)
+}
+
+/// Split a NAME=VALUE pair that is not valid UTF-8 at its first `=`, keeping the bytes of the
+/// value as they are. The name must still be valid.
+fn split_os_pair(pair: &OsStr) -> Result<(OsString, OsString), BoxError> {
+    let bytes = pair.as_bytes();
+    let i = bytes
+        .iter()
+        .position(|&b| b == b'=')
+        .ok_or_else(|| format!("expected NAME=VALUE: {}", pair.to_string_lossy()))?;
+    let key = OsStr::from_bytes(&bytes[..i]);
+    match key.to_str() {
+        Some(name) if key_is_valid(name) => Ok((
+            key.to_owned(),
+            OsStr::from_bytes(&bytes[i + 1..]).to_owned(),
+        )),
+        _ => Err(format!("KEY contains invalid characters: {}", key.to_string_lossy()).into()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn isolate(cmd: &mut Command, namespaces: &[String]) -> Result<(), BoxError> {
+    let isolation = isolate::Isolation::parse(namespaces)?;
+    // SAFETY: apply only makes async-signal-safe calls
+    unsafe { cmd.pre_exec(move || isolation.apply()) };
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isolate(_: &mut Command, _: &[String]) -> Result<(), BoxError> {
+    Err("--isolate is only supported on Linux".into())
+}
+
+#[cfg(target_os = "linux")]
+fn sandbox(cmd: &mut Command, specs: &[String]) -> Result<(), BoxError> {
+    let sandbox = sandbox::Sandbox::new(specs)?;
+    // SAFETY: apply only makes async-signal-safe calls
+    unsafe { cmd.pre_exec(move || sandbox.apply()) };
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sandbox(_: &mut Command, _: &[String]) -> Result<(), BoxError> {
+    Err("--sandbox is only supported on Linux".into())
+}
+
+/// A duration in seconds, or in minutes, hours or days with an `m`, `h` or `d` suffix, like
+/// GNU timeout takes them
+fn parse_duration(s: &str) -> Result<Duration, BoxError> {
+    let (number, unit) = match s.strip_suffix(['s', 'm', 'h', 'd']) {
+        Some(number) => (number, &s[number.len()..]),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        _ => 1.0,
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * seconds).ok())
+        .ok_or_else(|| format!("invalid duration: {s}").into())
+}
+
+/// `--NAME[=SIG]`, where leaving out SIG means all signals
+fn signal_arg(name: &'static str, long: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(long)
+        .value_name("SIG")
+        .help(help)
+        .takes_value(true)
+        .min_values(0)
+        .require_equals(true)
+        .use_delimiter(true)
+        .multiple(true)
+}
+
+/// Per-user configuration directory, `$XDG_CONFIG_HOME/enw` or `~/.config/enw`
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("enw"))
+}
+
+fn parse_arguments(args: impl Iterator<Item = impl Into<OsString> + Clone>) -> ArgMatches<'static> {
+    let mut args: Vec<OsString> = args.map(Into::into).collect();
+    // clap prints its own errors, so the color choice is needed before parsing
+    let color = if color::Choice::from_args(&args).enabled(&io::stderr()) {
+        AppSettings::ColorAlways
+    } else {
+        AppSettings::ColorNever
+    };
+    loop {
+        let matches = app(color)
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| match e.kind {
+                clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => e.exit(),
+                _ => {
+                    eprintln!("{}", e.message);
+                    process::exit(EXIT_FAILURE)
+                }
+            });
+        let Some(string) = matches.value_of_os("split_string") else {
+            return matches;
+        };
+        let rest_len = matches.values_of_os("rest").map_or(0, |rest| rest.len());
+        if let Err(e) = split_string(&mut args, string, rest_len) {
+            eprintln!("enw: -S: {e}");
+            process::exit(EXIT_FAILURE)
+        }
+    }
+}
+
+/// Replace `-S STRING` among the options, those before the last `rest_len` arguments, with the
+/// words of STRING, like GNU env does. A `#!` line passes everything after the interpreter as
+/// one argument, so this is how it gives enw several: `#!/usr/bin/enw -S -f .env.prod python3`.
+fn split_string(args: &mut Vec<OsString>, string: &OsStr, rest_len: usize) -> Result<(), BoxError> {
+    let words = shell::split(string.to_str().ok_or("not valid UTF-8")?)?;
+    let words = words.into_iter().map(OsString::from);
+    let string = string.as_bytes();
+    let options = 1..args.len().saturating_sub(rest_len);
+    for i in options {
+        let arg = args[i].as_bytes();
+        if matches!(arg, b"-S" | b"--split-string")
+            && args
+                .get(i + 1)
+                .is_some_and(|next| next.as_bytes() == string)
+        {
+            args.splice(i..i + 2, words);
+            return Ok(());
+        }
+        if arg.strip_prefix(b"--split-string=") == Some(string) {
+            args.splice(i..i + 1, words);
+            return Ok(());
+        }
+        // `-S...` or a cluster of flags ending in it, like `-iS...`
+        let flags = arg
+            .strip_suffix(string)
+            .and_then(|flags| flags.strip_suffix(b"S"))
+            .filter(|flags| flags.starts_with(b"-") && !flags.starts_with(b"--"));
+        if let Some(flags) = flags {
+            let flags = (flags.len() > 1).then(|| OsStr::from_bytes(flags).to_owned());
+            args.splice(i..i + 1, flags.into_iter().chain(words));
+            return Ok(());
+        }
+    }
+    Err("not found among the options".into())
+}
+
+/// The command line interface, also used to generate shell completions
+fn app(color: AppSettings) -> App<'static, 'static> {
+    App::new("enw")
+        .about(ABOUT)
+        .version(env!("CARGO_PKG_VERSION"))
+        .usage(USAGE)
+        .setting(AppSettings::TrailingVarArg)
+        .setting(color)
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help("color warnings and diffs")
+                .takes_value(true)
+                .possible_values(color::Choice::NAMES)
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("env_file")
+                .short("f")
+                .long("file")
+                .value_name("FILE")
+                .help(
+                    ".env file, a pattern like 'conf.d/*.env' loading the matches in order, an \
+                     http(s) URL to fetch, or - for stdin",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("vault")
+                .long("vault")
+                .value_name("PATH[#KEY]")
+                .help("load a secret from Vault, short for --from vault:PATH")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("aws_ssm")
+                .long("aws-ssm")
+                .value_name("PATH")
+                .help("load the SSM parameters under a path, short for --from aws-ssm:PATH")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("allow_file_refs")
+                .long("allow-file-refs")
+                .help("let env files read other files with @file: references"),
+        )
+        .arg(
+            Arg::with_name("allow_aws_refs")
+                .long("allow-aws-refs")
+                .help("let env files read AWS Secrets Manager with secretsmanager:// references"),
+        )
+        .arg(
+            Arg::with_name("allow_cmd_refs")
+                .long("allow-cmd-refs")
+                .help("let env files run commands with @cmd: references"),
+        )
+        .arg(
+            Arg::with_name("allow_exec")
+                .long("allow-exec")
+                .help("substitute the output of $(command) in env file values, which are kept as is otherwise"),
+        )
+        .arg(
+            Arg::with_name("file_auth")
+                .long("file-auth")
+                .value_name("TOKEN")
+                .help("bearer token for env files fetched from URLs [default: $ENW_FILE_AUTH]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("file_timeout")
+                .long("file-timeout")
+                .value_name("SECS")
+                .help("give up fetching an env file from a URL after this long")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache")
+                .long("cache")
+                .help("keep env files fetched from URLs in ~/.cache/enw, revalidating them by ETag"),
+        )
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .value_name("PROVIDER:SPEC")
+                .help(
+                    "load variables from a provider, e.g. vercel:project/production or \
+                     vault://secret/data/app",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .value_name("NAME=VALUE")
+                .help("set NAME to VALUE, taken literally")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("ignore_env")
+                .short("i")
+                .long("ignore-env")
+                .help("start with an empty environment"),
+        )
+        .arg(
+            Arg::with_name("inherit_prefix")
+                .long("inherit-prefix")
+                .value_name("PREFIX,...")
+                .help("with -i, still inherit variables starting with any of the prefixes")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true),
+        )
+        .arg(
+            Arg::with_name("prefix")
+                .long("prefix")
+                .value_name("PREFIX")
+                .help("only load variables starting with PREFIX from env files and providers")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strip_prefix")
+                .long("strip-prefix")
+                .requires("prefix")
+                .help("remove the --prefix from the names of the variables loaded"),
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .value_name("PATTERN")
+                .help("pass COMMAND only the variables matching a pattern like 'AWS_*', inherited or loaded")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("except")
+                .long("except")
+                .value_name("PATTERN")
+                .help("withhold the variables matching a pattern like '*_SECRET*' from COMMAND")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("no_implicit_env_file")
+                .short("n")
+                .long("no-env-file")
+                .help("don't implicitly load the .env file from current dir"),
+        )
+        .arg(
+            Arg::with_name("fork")
+                .long("fork")
+                .help("run COMMAND as a child and wait for it, instead of replacing enw with it"),
+        )
+        .arg(
+            Arg::with_name("pty")
+                .long("pty")
+                .help("run COMMAND in a pseudo-terminal, so that it sees a terminal even when the output is not one"),
+        )
+        .arg(
+            Arg::with_name("parallel")
+                .long("parallel")
+                .help("run the commands separated by --and at the same time, instead of one after the other"),
+        )
+        .arg(
+            Arg::with_name("kill_timeout")
+                .long("kill-timeout")
+                .value_name("SECS")
+                .help("kill COMMAND this long after passing on a signal, while enw waits for it")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .help(
+                    "send COMMAND SIGTERM if it runs longer than DURATION, e.g. 30, 1.5m, 2h, \
+                     and exit with 124",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("kill_after")
+                .long("kill-after")
+                .value_name("DURATION")
+                .help("send SIGKILL if COMMAND is still running this long after the timeout")
+                .takes_value(true)
+                .requires("timeout"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .conflicts_with_all(&["print", "report", "timeout", "pty"])
+                .help("restart COMMAND with the new environment when a loaded env file changes"),
+        )
+        .arg(
+            Arg::with_name("watch_path")
+                .long("watch-path")
+                .value_name("PATH")
+                .help("also restart COMMAND when PATH changes")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("watch"),
+        )
+        .arg(
+            Arg::with_name("watch_strategy")
+                .long("watch-strategy")
+                .value_name("STRATEGY")
+                .help("on changes, restart COMMAND (the default), send it --watch-signal, or queue the restart until it exits")
+                .takes_value(true)
+                .possible_values(watch::STRATEGIES)
+                .requires("watch"),
+        )
+        .arg(
+            Arg::with_name("on_change")
+                .long("on-change")
+                .value_name("HOOK")
+                .help("on changes, run the shell command HOOK with the new environment instead of restarting COMMAND")
+                .takes_value(true)
+                .conflicts_with("watch_strategy")
+                .requires("watch"),
+        )
+        .arg(
+            Arg::with_name("watch_signal")
+                .long("watch-signal")
+                .value_name("SIG")
+                .help("signal to send with --watch-strategy signal")
+                .takes_value(true)
+                .default_value("HUP"),
+        )
+        .arg(
+            Arg::with_name("watch_ignore")
+                .long("watch-ignore")
+                .value_name("PATTERN")
+                .help("don't watch files or directories whose name or path matches PATTERN, e.g. '*.tmp'")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("watch"),
+        )
+        .arg(
+            Arg::with_name("chdir")
+                .short("C")
+                .long("chdir")
+                .value_name("DIR")
+                .help("change working directory to DIR before running COMMAND")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chdir_first")
+                .long("chdir-first")
+                .help("change directory before looking for env files too")
+                .requires("chdir"),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .short("r")
+                .long("recursive")
+                .help("also load the .env files of parent directories, the closest taking precedence"),
+        )
+        .arg(
+            Arg::with_name("stop_at")
+                .long("stop-at")
+                .value_name("STOP")
+                .help("where --recursive stops looking: the root of a git repository, the home directory")
+                .takes_value(true)
+                .use_delimiter(true)
+                .default_value(discovery::Stop::DEFAULT)
+                .possible_values(discovery::Stop::NAMES),
+        )
+        .arg(
+            Arg::with_name("preset")
+                .long("preset")
+                .value_name("PRESET")
+                .help("load the files a framework's own dotenv loader would, in its order")
+                .takes_value(true)
+                .possible_values(preset::Preset::NAMES),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help(
+                    "overlay .env.NAME and .env.NAME.local on .env, and load the files and \
+                     variables of profile NAME in .enw.toml",
+                )
+                .takes_value(true)
+                .env(prompt::PROFILE)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("section")
+                .long("section")
+                .value_name("SECTION")
+                .help("only load keys from SECTION of .ini files, or the table of .toml files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("section_prefix")
+                .long("section-prefix")
+                .requires("section")
+                .help("prefix keys from the selected section with the section name"),
+        )
+        .arg(
+            Arg::with_name("normalize_keys")
+                .long("normalize-keys")
+                .help("upcase keys from .properties files and turn dots into underscores"),
+        )
+        .arg(
+            Arg::with_name("file_format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("parse all env files as FORMAT instead of telling it by their extension")
+                .takes_value(true)
+                .possible_values(FileFormat::NAMES),
+        )
+        .arg(
+            Arg::with_name("key_separator")
+                .long("key-separator")
+                .value_name("SEP")
+                .help(
+                    "join nested keys of .json and .yaml files with SEP, keeping their case, \
+                     instead of upcasing them and joining with _",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("compat")
+                .long("compat")
+                .value_name("IMPL")
+                .help("parse .env files like another dotenv implementation")
+                .takes_value(true)
+                .possible_values(&["php"]),
+        )
+        .arg(
+            Arg::with_name("dialect")
+                .long("dialect")
+                .value_name("DIALECT")
+                .help(
+                    "parse .env files exactly like docker compose, Node's dotenv or a POSIX \
+                     shell",
+                )
+                .takes_value(true)
+                .possible_values(&["compose", "dotenv", "shell"])
+                .conflicts_with("compat"),
+        )
+        .arg(
+            Arg::with_name("allow_placeholders")
+                .long("allow-placeholders")
+                .help("run even when values look like unfilled placeholders"),
+        )
+        .arg(
+            Arg::with_name("secret_via_file")
+                .long("secret-via-file")
+                .value_name("KEY")
+                .help("pass KEY in a private file, named by KEY_FILE, instead of the environment")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("secret_via_fd")
+                .long("secret-via-fd")
+                .value_name("KEY")
+                .help("pass KEY through an inherited pipe, numbered by KEY_FD, instead of the environment")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("dangerous_vars")
+                .long("dangerous-vars")
+                .value_name("POLICY")
+                .help("what to do when untrusted env files set LD_PRELOAD, PATH and the like")
+                .takes_value(true)
+                .possible_values(danger::Policy::NAMES)
+                .default_value("warn"),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .help("refuse to load env files without a valid signature from a trusted key"),
+        )
+        .arg(
+            Arg::with_name("allowed_signers")
+                .long("allowed-signers")
+                .value_name("FILE")
+                .help("ssh allowed signers file for --verify")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("minisign_key")
+                .long("minisign-key")
+                .value_name("FILE")
+                .help("minisign public key for --verify")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strict_expiry")
+                .long("strict-expiry")
+                .help("fail instead of warning when a value is past its `# expires:` date"),
+        )
+        .arg(
+            Arg::with_name("warn_duplicates")
+                .long("warn-duplicates")
+                .help("warn about keys defined more than once, within an env file or across them"),
+        )
+        .arg(
+            Arg::with_name("strict_duplicates")
+                .long("strict-duplicates")
+                .help("fail instead of warning about keys defined more than once"),
+        )
+        .arg(
+            Arg::with_name("no_positional_vars")
+                .long("no-positional-vars")
+                .help("treat the first positional argument as COMMAND, even if it contains a '='"),
+        )
+        .arg(
+            Arg::with_name("empty_is_unset")
+                .long("empty-is-unset")
+                .help("NAME= unsets NAME instead of setting it to an empty string"),
+        )
+        .arg(
+            Arg::with_name("no_override")
+                .long("no-override")
+                .overrides_with("override")
+                .help("keep variables already set in the environment over those in env files"),
+        )
+        .arg(
+            Arg::with_name("override")
+                .long("override")
+                .overrides_with("no_override")
+                .help("let env files override the environment, even when ENW_OVERRIDE=0"),
+        )
+        .arg(
+            Arg::with_name("unset")
+                .short("u")
+                .long("unset")
+                .value_name("NAME")
+                .help("remove NAME from the environment, even when set in an env file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("split_string")
+                .short("S")
+                .long("split-string")
+                .value_name("S")
+                .help("split S into separate arguments, for passing options in #! lines")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("from_snapshot")
+                .long("from-snapshot")
+                .value_name("NAME")
+                .help("give COMMAND the environment saved by `enw snapshot save NAME` instead of the inherited one, without loading .env")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("via_shell")
+                .long("shell")
+                .help("run COMMAND and its arguments as a $SHELL -c script, allowing pipes and globs"),
+        )
+        .arg(
+            Arg::with_name("rest")
+                .value_name("REST")
+                .takes_value(true)
+                .hidden(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("don't print any warnings"),
+        )
+        .args(&[
+            signal_arg("default_signal", "default-signal", "reset handling of SIG to the default, all signals without SIG"),
+            signal_arg("ignore_signal", "ignore-signal", "ignore SIG, all signals without SIG"),
+            signal_arg("block_signal", "block-signal", "block delivery of SIG, all signals without SIG"),
+        ])
+        .arg(
+            Arg::with_name("isolate")
+                .long("isolate")
+                .value_name("NAMESPACE,...")
+                .help("run COMMAND in new Linux namespaces, tmp gives it a private /tmp")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .possible_values(&["net", "pid", "mount", "ipc", "uts", "tmp"]),
+        )
+        .arg(
+            Arg::with_name("sandbox")
+                .long("sandbox")
+                .value_name("ro|rw:PATH")
+                .help("only let COMMAND read, or read and write, below PATH (Landlock)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("print")
+                .long("print")
+                .value_name("FORMAT")
+                .help("print the environment COMMAND would get instead of running one [default: env]")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(print::Format::NAMES),
+        )
+        .arg(
+            Arg::with_name("show_secrets")
+                .long("show-secrets")
+                .help("show the values of secrets in listings, --verbose, --explain, --print and diffs instead of ****, and let -0 and --print=export|json|null print them")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("null")
+                .short("0")
+                .long("null")
+                .help("end each output line with NUL, not newline, and read NUL delimited --stdin"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .help("read an env file from stdin, like -f -, or NUL delimited NAME=VALUE pairs with -0"),
+        )
+        .arg(
+            Arg::with_name("systemd_run")
+                .long("systemd-run")
+                .value_name("UNIT")
+                .help("run COMMAND as a transient systemd scope (default) or service")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(systemd::Unit::NAMES),
+        )
+        .arg(
+            Arg::with_name("systemd_property")
+                .long("systemd-property")
+                .value_name("NAME=VALUE")
+                .help("set a property on the --systemd-run unit, e.g. MemoryMax=1G")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("frozen")
+                .long("frozen")
+                .value_name("FILE")
+                .help("refuse to run unless the variables match the lock file [default: .env.lock]")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("strict_secrets")
+                .long("strict-secrets")
+                .help("fail instead of warn when env files holding secrets are not ignored by git"),
+        )
+        .arg(
+            Arg::with_name("no_expand")
+                .long("no-expand")
+                .help("take $VAR and ${VAR} in env file values literally"),
+        )
+        .arg(
+            Arg::with_name("raw_values")
+                .long("raw-values")
+                .help(
+                    "take everything after the first = of a line as the value, as is: no \
+                     trimming, quotes, escapes, comments or expansion",
+                )
+                .conflicts_with_all(&["compat", "dialect"]),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("list the variables COMMAND gets and where each comes from on stderr"),
+        )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .value_name("KEY")
+                .help("show the value of KEY and where it comes from, instead of running COMMAND")
+                .takes_value(true)
+                .conflicts_with_all(&["print", "watch"]),
+        )
+        .arg(
+            Arg::with_name("require_schema")
+                .long("require-schema")
+                .help("fail unless the variables match .env.schema, or else .env.example"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("fail on .env lines that are not KEY=VALUE, instead of skipping them"),
+        )
+        .arg(
+            Arg::with_name("search_path")
+                .long("search-path")
+                .value_name("DIRS")
+                .help("colon separated directories to look for COMMAND in, instead of PATH")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .value_name("PATH")
+                .help(
+                    "after COMMAND exits, write a JSON report of sources, timings and exit \
+                     status to PATH (- for stderr)",
+                )
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("print the resolved variables in another tool's format")
+                .arg(
+                    Arg::with_name("format")
+                        .value_name("FORMAT")
+                        .help("output format, or a shell to update from `enw hook`")
+                        .required_unless("format_option")
+                        .possible_values(export::Format::NAMES)
+                        .possible_values(hook::Shell::NAMES),
+                )
+                .arg(
+                    Arg::with_name("format_option")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("same as the FORMAT argument")
+                        .takes_value(true)
+                        .conflicts_with("format")
+                        .possible_values(export::Format::NAMES)
+                        .possible_values(hook::Shell::NAMES),
+                )
+                .arg(
+                    Arg::with_name("key_prefix")
+                        .long("key-prefix")
+                        .value_name("PREFIX")
+                        .help("prepend PREFIX to every exported key (ansible)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("k8s")
+                .about("print the resolved variables as a Kubernetes ConfigMap, and a Secret for secrets")
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("name of the ConfigMap and Secret")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .long("namespace")
+                        .value_name("NAMESPACE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("secret")
+                        .long("secret")
+                        .conflicts_with("configmap")
+                        .help("put every variable in the Secret"),
+                )
+                .arg(
+                    Arg::with_name("configmap")
+                        .long("configmap")
+                        .help("put every variable in the ConfigMap"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("hook")
+                .about("print a snippet for your shell's rc file loading the nearest .env on every prompt")
+                .arg(
+                    Arg::with_name("shell")
+                        .value_name("SHELL")
+                        .required(true)
+                        .possible_values(hook::Shell::NAMES),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("time each resolution phase, provider and the spawn of COMMAND")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("iterations")
+                        .short("n")
+                        .long("iterations")
+                        .value_name("N")
+                        .help("number of runs")
+                        .takes_value(true)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("command")
+                        .value_name("COMMAND")
+                        .help("command to spawn after each resolution, its stdout discarded")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("resolve two configurations and print the variables that differ")
+                .arg(
+                    Arg::with_name("lhs")
+                        .long("lhs")
+                        .value_name("ARGS")
+                        .help("options of the first configuration, e.g. '-f .env.staging'")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("rhs")
+                        .long("rhs")
+                        .value_name("ARGS")
+                        .help("options of the second configuration")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about(
+                    "show the variables added, removed and changed between two env files, or \
+                     between the environment enw resolves and one env file",
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .value_name("FILE")
+                        .help("env file to compare, given once or twice")
+                        .takes_value(true)
+                        .number_of_values(1)
+                        .multiple(true)
+                        .max_values(2)
+                        .required_unless("against"),
+                )
+                .arg(
+                    Arg::with_name("against")
+                        .long("against")
+                        .value_name("FILE")
+                        .help("compare the environment enw resolves with FILE")
+                        .takes_value(true)
+                        .conflicts_with("file"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["diff", "table", "json"])
+                        .default_value("diff"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("freeze")
+                .about("record the resolved variables, secrets as digests, for --frozen")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("lock file to write [default: .env.lock]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("save the environment a command would get, for --from-snapshot")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("save")
+                        .about("save the environment, inherited variables included, as NAME")
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                )
+                .subcommand(SubCommand::with_name("list").about("list the saved snapshots")),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("print a completion script for SHELL")
+                .arg(
+                    Arg::with_name("shell")
+                        .value_name("SHELL")
+                        .possible_values(completions::SHELLS)
+                        .required_unless("names"),
+                )
+                .arg(
+                    Arg::with_name("names")
+                        .long("names")
+                        .hidden(true)
+                        .help("list the names set by the env files, for completion scripts"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("login")
+                .about("authenticate with a provider and keep its token in the OS keyring")
+                .arg(
+                    Arg::with_name("provider")
+                        .value_name("PROVIDER")
+                        .required(true)
+                        .possible_values(credentials::PROVIDERS),
+                )
+                .arg(
+                    Arg::with_name("args")
+                        .value_name("ARGS")
+                        .help("passed on to `vault login`, e.g. -- -method=oidc")
+                        .multiple(true)
+                        .last(true),
+                )
+                .arg(
+                    Arg::with_name("forget")
+                        .long("forget")
+                        .help("remove the stored token instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lsp")
+                .about("run a language server for env files, speaking LSP on stdin and stdout"),
+        )
+        .subcommand(
+            SubCommand::with_name("prompt")
+                .about("print which environment the current shell has loaded, for use in PS1")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("template with {name}, {profile} and {files} [default: enw:{name}]")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check").about(
+                "check the variables COMMAND would get against .env.schema, or else .env.example",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("compare the variables code reads with those declared in .env.example")
+                .arg(
+                    Arg::with_name("dir")
+                        .value_name("DIR")
+                        .help("project directory [default: .]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("self-update")
+                .about("replace this binary with the latest release, after verifying its signature")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("only report whether an update is available"),
+                )
+                .arg(
+                    Arg::with_name("channel")
+                        .long("channel")
+                        .value_name("CHANNEL")
+                        .help("release channel, beta includes pre-releases")
+                        .takes_value(true)
+                        .default_value("stable")
+                        .possible_values(update::Channel::NAMES),
+                )
+                .arg(
+                    Arg::with_name("key")
+                        .long("key")
+                        .value_name("FILE")
+                        .help("minisign public key to verify the release with")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("shell")
+                .about(
+                    "start $SHELL with the resolved variables, and ENW_ACTIVE, ENW_PROFILE and \
+                     ENW_FILES set for prompts",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("show the workspace and the chain of env files that would be loaded"),
+        )
+        .subcommand(
+            SubCommand::with_name("encrypt")
+                .about("encrypt an env file to FILE.enc, which is decrypted when loaded")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("file to write [default: FILE.enc]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("method")
+                        .long("method")
+                        .value_name("METHOD")
+                        .help("age to the recipients in .enw-recipients, or to the age identity in ENW_KEY or ENW_KEY_FILE")
+                        .takes_value(true)
+                        .default_value("age")
+                        .possible_values(crypt::Method::NAMES),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("decrypt")
+                .about("decrypt an encrypted env file")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("file to write, only readable by you [default: stdout]")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("replace ${VAR} and $VAR in a template with the environment COMMAND would get")
+                .arg(
+                    Arg::with_name("template")
+                        .value_name("TEMPLATE")
+                        .help("template file, - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("file to write [default: stdout]")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("share env files, encrypted with age, through an S3 or GCS bucket")
+                .arg(
+                    Arg::with_name("action")
+                        .value_name("ACTION")
+                        .required(true)
+                        .possible_values(&["push", "pull"]),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("env files to push [default: .env]")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("remote")
+                        .long("remote")
+                        .value_name("URL")
+                        .help("s3://bucket/prefix, gs://bucket/prefix or file://dir")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("version")
+                        .long("version")
+                        .value_name("N")
+                        .help("pull version N instead of the latest")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("overwrite changes made on the other side since the last sync"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("trace-usage")
+                .about("run COMMAND and report which variables it looked up (Linux, glibc)")
+                .after_help(
+                    "Needs libenw_trace.so next to the enw executable, or named by \
+                     ENW_TRACE_LIBRARY. `cargo install enw` does not install it: build it in a \
+                     checkout of enw with `cargo build --release -p enw-trace` and copy \
+                     target/release/libenw_trace.so next to enw.",
+                )
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("command")
+                        .value_name("COMMAND")
+                        .help("command and arguments to run")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("trust")
+                .about("allow env files to set dangerous variables, until they change")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("env files to trust [default: .env]")
+                        .multiple(true),
+                ),
+        )
 }
 
-pub fn run(args: impl Iterator<Item = impl Into<OsString> + Clone>) -> Result<(), BoxError> {
-    let matches = parse_arguments(args);
-    let opt_builder = OptionsBuilder::with_arg_matches(matches)?;
-    let mut warnings = Vec::new();
-    let env_files: Vec<_> = opt_builder
-        .env_files
-        .into_iter()
-        .filter_map(|env_file| {
-            let EnvFile { path, is_default } = env_file;
-            if path.is_dir() {
-                let file_path = path.join(DEFAULT_ENV_FILE_NAME);
-                if file_path.is_file() {
-                    Some(file_path)
-                } else {
-                    if !is_default {
-                        warnings.push(format!(
-                            "no {DEFAULT_ENV_FILE_NAME} file found in {}",
-                            path.to_string_lossy()
-                        ));
+/// Parse a file according to its format, which is picked by extension
+fn parse_env_file(
+    path: &Path,
+    text: &str,
+    opts: &OptionsBuilder,
+    defined: &HashMap<String, Var>,
+) -> Vec<Result<(String, String), BoxError>> {
+    let lookup = |key: &str| {
+        defined
+            .get(key)
+            .map(|var| var.value.clone())
+            .or_else(|| env::var(key).ok())
+    };
+    let substitute = |command: &str| {
+        if let Some(output) = opts.command_outputs.borrow().get(command) {
+            return Ok(output.clone());
+        }
+        let output = run_substitution(command)?;
+        opts.command_outputs
+            .borrow_mut()
+            .insert(command.to_owned(), output.clone());
+        Ok(output)
+    };
+    // The format of `secrets.ini.enc` is that of `secrets.ini`
+    let format_path = match crypt::is_encrypted(path) {
+        true => path.with_extension(""),
+        false => path.to_owned(),
+    };
+    let format = opts.format.or_else(|| {
+        format_path
+            .extension()
+            .and_then(|ext| FileFormat::from_extension(&ext.to_string_lossy()))
+    });
+    match format.unwrap_or(FileFormat::Dotenv) {
+        FileFormat::Ini => ini::parse(text, &opts.ini),
+        FileFormat::Toml => ini::parse_toml(text, &opts.ini),
+        FileFormat::Properties => properties::parse(text, &opts.properties),
+        FileFormat::Json => structured::parse_json(text, &opts.structured),
+        FileFormat::Yaml => structured::parse_yaml(text, &opts.structured),
+        FileFormat::Dotenv if opts.raw_values => parse_env_doc_raw(text),
+        FileFormat::Dotenv => match opts.compat {
+            Some(Compat::Php) => php::parse(text, &lookup),
+            Some(Compat::Compose) => dialect::compose::parse(text, &lookup),
+            Some(Compat::Node) => dialect::node::parse(text),
+            Some(Compat::Shell) => dialect::shell::parse(text, &lookup),
+            None => {
+                if opts.strict {
+                    if let Err(e) = check_syntax(text) {
+                        return vec![Err(format!("{}:{e}", path.to_string_lossy()).into())];
                     }
-                    None
                 }
-            } else if path.is_file() {
-                Some(path)
-            } else {
-                if !is_default {
-                    warnings.push(format!("{} does not exist", path.to_string_lossy()));
+                if opts.no_expand {
+                    parse_env_doc(text)
+                } else {
+                    let substitute: Option<Substitute> = match opts.allow_exec {
+                        true => Some(&substitute),
+                        false => None,
+                    };
+                    parse_env_doc_expanding(text, &lookup, substitute)
                 }
-                None
             }
+        },
+    }
+}
+
+fn parse_env_doc(text: &str) -> Vec<Result<(String, String), BoxError>> {
+    assignments(text)
+        .into_iter()
+        .map(|(_, assignment)| parse_env_line(assignment.trim_start()))
+        .collect()
+}
+
+/// For `--raw-values`: the value is everything after the first `=` of a line. Blank lines and
+/// lines starting with `#` are skipped.
+fn parse_env_doc_raw(text: &str) -> Vec<Result<(String, String), BoxError>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE: {line}"))?;
+            let key = key.trim_start();
+            let key = match key.strip_prefix("export") {
+                Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+                _ => key,
+            };
+            let key = key.trim();
+            if !key_is_valid(key) {
+                return Err(format!("KEY contains invalid characters: {key}").into());
+            }
+            Ok((key.to_owned(), value.to_owned()))
         })
-        .map(fs::read_to_string)
-        .collect::<Result<_, _>>()?;
-    let mut env_vars: HashMap<_, _> = env_files
-        .iter()
-        .flat_map(|text| parse_env_doc(text))
-        .collect::<Result<_, _>>()?;
-    env_vars.extend(opt_builder.vars.into_iter());
-    let mut env_vars: Vec<_> = env_vars.into_iter().collect();
-    env_vars.sort();
-    if opt_builder.print_warnings {
-        for warning in warnings {
-            eprintln!("warning: {warning}");
+        .collect()
+}
+
+/// A quoted value left open at the end of a line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Open {
+    DoubleQuote,
+    /// A `"""` block
+    Block,
+}
+
+/// The `KEY=VALUE` assignments of a document, with the line each starts on. An assignment
+/// continues onto the following lines while a double quoted value or a `"""` block is open.
+pub(crate) fn assignments(text: &str) -> Vec<(usize, &str)> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut out = Vec::new();
+    let mut offset = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        let (number, start) = (i, offset);
+        let line = lines[i];
+        offset += line.len();
+        i += 1;
+        let Some((_, value)) = split_assignment(line) else {
+            continue;
+        };
+        let mut open = open_quote(value, None);
+        while let (Some(quote), Some(line)) = (open, lines.get(i)) {
+            open = open_quote(line, Some(quote));
+            offset += line.len();
+            i += 1;
         }
+        out.push((number, &text[start..offset]));
     }
-    if let Some(command) = opt_builder.command {
-        let mut cmd = Command::new(command);
-        if opt_builder.ignore_env {
-            cmd.env_clear();
+    out
+}
+
+/// The quote still open after `chunk`, given the one open before it
+fn open_quote(chunk: &str, open: Option<Open>) -> Option<Open> {
+    const BLOCK: &str = "\"\"\"";
+    match open {
+        Some(Open::Block) => return (!chunk.contains(BLOCK)).then_some(Open::Block),
+        None if chunk.trim_start().starts_with(BLOCK) => {
+            let rest = &chunk.trim_start()[BLOCK.len()..];
+            return (!rest.contains(BLOCK)).then_some(Open::Block);
         }
-        cmd.envs(env_vars).args(opt_builder.args);
-        Err(cmd.exec().into())
-    } else {
-        for (key, value) in env_vars {
-            if value
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
-            {
-                println!("{}={}", key, value.escape_default());
-            } else {
-                println!("{}=\"{}\"", key, value.escape_default());
-            }
+        _ => {}
+    }
+    let (mut double, mut single, mut escape) = (open.is_some(), false, false);
+    for c in chunk.chars() {
+        match c {
+            _ if escape => escape = false,
+            '\\' => escape = true,
+            '"' if !single => double = !double,
+            '\'' if !double => single = !single,
+            '#' if !double && !single => break,
+            _ => {}
         }
-        Ok(())
     }
+    double.then_some(Open::DoubleQuote)
 }
 
-fn parse_arguments(args: impl Iterator<Item = impl Into<OsString> + Clone>) -> ArgMatches<'static> {
-    App::new("enw")
-        .about(ABOUT)
-        .version(env!("CARGO_PKG_VERSION"))
-        .usage(USAGE)
-        .setting(AppSettings::TrailingVarArg)
-        .arg(
-            Arg::with_name("env_file")
-                .short("f")
-                .long("file")
-                .value_name("FILE")
-                .help(".env file")
-                .takes_value(true)
-                .multiple(true)
-                .number_of_values(1),
-        )
-        .arg(
-            Arg::with_name("ignore_env")
-                .short("i")
-                .long("ignore-env")
-                .help("start with an empty environment"),
-        )
-        .arg(
-            Arg::with_name("no_implicit_env_file")
-                .short("n")
-                .long("no-env-file")
-                .help("don't implicitly load the .env file from current dir"),
-        )
-        .arg(
-            Arg::with_name("rest")
-                .value_name("REST")
-                .takes_value(true)
-                .hidden(true)
-                .multiple(true),
-        )
-        .arg(
-            Arg::with_name("quiet")
-                .short("q")
-                .long("quiet")
-                .help("don't print any warnings"),
-        )
-        .get_matches_from(args)
+/// For `--strict`: the first line that is neither blank, a comment nor a valid assignment, as
+/// `LINE:COLUMN: message`
+fn check_syntax(text: &str) -> Result<(), String> {
+    let assignments: HashMap<usize, &str> = assignments(text).into_iter().collect();
+    let continued: Vec<usize> = assignments
+        .iter()
+        .flat_map(|(&line, raw)| line + 1..line + raw.lines().count())
+        .collect();
+    for (i, raw) in text.lines().enumerate() {
+        // Columns count characters, from 1, of `part` which lies within `raw`
+        let error = |part: &str, message: String| {
+            let offset = part.as_ptr() as usize - raw.as_ptr() as usize;
+            let column = raw.get(..offset).map_or(1, |s| s.chars().count() + 1);
+            Err(format!("{}:{column}: {message}", i + 1))
+        };
+        let trimmed = raw.trim_start();
+        if continued.contains(&i) || trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = assignments.get(&i).and_then(|a| split_assignment(a)) else {
+            return error(trimmed, "expected KEY=VALUE".to_owned());
+        };
+        if !key_is_valid(key) {
+            return error(key, format!("KEY contains invalid characters: {key}"));
+        }
+        if let Err(e) = parse_value(value, None, None) {
+            return error(value, e.to_string());
+        }
+    }
+    Ok(())
 }
 
-fn parse_env_doc(text: &str) -> Vec<Result<(String, String), BoxError>> {
-    text.lines()
-        .map(|line| line.trim_start())
-        .filter(|line| line.contains('=') && !line.starts_with('#'))
-        .map(parse_env_line)
-        .collect()
+/// Like [`parse_env_doc`], expanding `${VAR}` and `$VAR` in unquoted and double quoted values
+/// from keys defined earlier in the file, then from `lookup`, and `$(command)` with `substitute`
+/// when given
+fn parse_env_doc_expanding(
+    text: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    substitute: Option<Substitute>,
+) -> Vec<Result<(String, String), BoxError>> {
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut out = Vec::new();
+    for (_, assignment) in assignments(text) {
+        let resolve = |name: &str| defined.get(name).cloned().or_else(|| lookup(name));
+        let entry = parse_env_line_with(assignment.trim_start(), Some(&resolve), substitute);
+        if let Ok((key, value)) = &entry {
+            defined.insert(key.clone(), value.clone());
+        }
+        out.push(entry);
+    }
+    out
 }
 
 fn parse_env_line(line: &str) -> Result<(String, String), BoxError> {
-    let mut parts = line.splitn(2, '=').map(str::trim);
-    let key = parts.next().ok_or("KEY missing")?;
+    parse_env_line_with(line, None, None)
+}
+
+fn parse_env_line_with(
+    line: &str,
+    resolve: Option<Resolve>,
+    substitute: Option<Substitute>,
+) -> Result<(String, String), BoxError> {
+    let (key, value) = split_assignment(line).unwrap_or((line.trim(), ""));
     if !key_is_valid(key) {
         return Err(format!("KEY contains invalid characters: {}", key).into());
     }
-    let value = parse_value(parts.next().unwrap_or(""))?;
+    let value = parse_value(value, resolve, substitute)?;
     Ok((key.to_owned(), value))
 }
 
+/// The key and the raw value of an assignment. Besides `KEY=VALUE`, the shell forms
+/// `export KEY=VALUE` and fish's `set -x KEY VALUE` are accepted, so that the same file can be
+/// sourced.
+pub(crate) fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    if line.starts_with('#') {
+        return None;
+    }
+    if let Some(rest) = line.strip_prefix("set ") {
+        return fish_set(rest);
+    }
+    let line = match line.strip_prefix("export") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+        _ => line,
+    };
+    let (key, value) = line.split_once('=')?;
+    Some((schema::split_type(key.trim()).0, value.trim()))
+}
+
+/// The key and value of a fish `set`, its flags already consumed. None unless it exports.
+fn fish_set(mut rest: &str) -> Option<(&str, &str)> {
+    let mut exported = false;
+    loop {
+        rest = rest.trim_start();
+        let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match word.strip_prefix('-') {
+            Some("-export") => exported = true,
+            Some(flags) if !flags.starts_with('-') => exported |= flags.contains('x'),
+            Some(_) => {}
+            None if word.is_empty() => return None,
+            None => return exported.then(|| (word, tail.trim())),
+        }
+        rest = tail;
+    }
+}
+
 fn key_is_valid(key: &str) -> bool {
     !key.is_empty()
         && key
@@ -167,7 +2522,16 @@ fn key_is_valid(key: &str) -> bool {
         && !key.chars().any(|c| c.is_whitespace())
 }
 
-fn parse_value(v: &str) -> Result<String, BoxError> {
+/// Dequote a value. With `resolve`, variable references outside single quotes are expanded and
+/// `\$` stands for a literal `$`.
+fn parse_value(
+    v: &str,
+    resolve: Option<Resolve>,
+    substitute: Option<Substitute>,
+) -> Result<String, BoxError> {
+    if let Some(block) = v.strip_prefix("\"\"\"") {
+        return parse_block(block);
+    }
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
     enum S {
         DoubleQuote,
@@ -177,12 +2541,23 @@ fn parse_value(v: &str) -> Result<String, BoxError> {
     }
     let mut out = String::with_capacity(v.len());
     let mut state = vec![S::Start];
-    'outer: for c in v.chars() {
+    let mut chars = v.chars().peekable();
+    'outer: while let Some(c) = chars.next() {
         let s = *state.last().unwrap();
+        if c == '$' && matches!(s, S::Start | S::DoubleQuote) {
+            if let Some(resolve) = resolve {
+                match expand_reference(&mut chars, resolve, substitute)? {
+                    Some(value) => out.push_str(&value),
+                    None => out.push(c),
+                }
+                continue;
+            }
+        }
         match s {
             S::Escape => {
                 state.pop();
                 match (state.last().unwrap(), c) {
+                    (S::DoubleQuote, '$') if resolve.is_some() => out.push(c),
                     (S::DoubleQuote, '"')
                     | (S::SingleQuote, '\'')
                     | (S::DoubleQuote, '\\')
@@ -247,6 +2622,87 @@ fn parse_value(v: &str) -> Result<String, BoxError> {
     Ok(out)
 }
 
+/// The text of a `"""` block, the opening quotes already consumed. It is taken literally,
+/// without the line breaks after the opening and before the closing quotes.
+fn parse_block(v: &str) -> Result<String, BoxError> {
+    let (text, rest) = v
+        .split_once("\"\"\"")
+        .ok_or("error parsing value: unterminated \"\"\" block.")?;
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("error parsing value, unexpected text after \"\"\": {rest}").into());
+    }
+    let text = text.strip_prefix('\n').unwrap_or(text);
+    let text = match text.rsplit_once('\n') {
+        Some((head, last)) if last.trim().is_empty() => head,
+        _ => text,
+    };
+    Ok(text.to_owned())
+}
+
+/// Expand `${NAME}` or `$NAME`, or `$(command)` when substitution is enabled, the `$` already
+/// consumed. None when no reference follows, so that the `$` is kept. Undefined variables
+/// expand to nothing.
+fn expand_reference(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    resolve: &dyn Fn(&str) -> Option<String>,
+    substitute: Option<Substitute>,
+) -> Result<Option<String>, BoxError> {
+    match chars.peek() {
+        Some('{') => php::expand(chars, resolve).map(Some),
+        Some('(') if substitute.is_some() => {
+            chars.next();
+            let mut command = String::new();
+            let mut depth = 0;
+            loop {
+                match chars.next() {
+                    Some(')') if depth == 0 => break,
+                    Some(c) => {
+                        depth += match c {
+                            '(' => 1,
+                            ')' => -1,
+                            _ => 0,
+                        };
+                        command.push(c);
+                    }
+                    None => return Err("error parsing value: unterminated $(".into()),
+                }
+            }
+            substitute
+                .map(|substitute| substitute(&command))
+                .transpose()
+        }
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_') {
+                name.push(c);
+            }
+            Ok(Some(resolve(&name).unwrap_or_default()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Run the command of a `$(command)` with the shell, taking its output without the trailing
+/// newlines, as the shell does
+fn run_substitution(command: &str) -> Result<String, BoxError> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("could not run $({command}): {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "$({command}) failed with exit code {}",
+            exit_code(output.status)
+        )
+        .into());
+    }
+    let mut output = String::from_utf8(output.stdout)?;
+    output.truncate(output.trim_end_matches('\n').len());
+    Ok(output)
+}
+
 /// Trim ending whitespace without reallocating
 fn trim_end_whitespace(s: &mut String) {
     let trailing_whitespace = s
@@ -258,44 +2714,421 @@ fn trim_end_whitespace(s: &mut String) {
 }
 
 impl OptionsBuilder {
+    /// Whether `key` matches one of the `--only` patterns, if any, and none of the `--except`
+    /// ones
+    fn passes_filters(&self, key: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pattern| glob::matches(pattern, key)))
+            && !self
+                .except
+                .iter()
+                .any(|pattern| glob::matches(pattern, key))
+    }
+
     fn with_arg_matches(matches: ArgMatches<'static>) -> Result<Self, BoxError> {
-        const DEFAULT_VEC: Vec<String> = Vec::new();
+        let config = config::load(&env::current_dir()?)?;
+        let project_config = env::current_dir()?.join(profile::CONFIG_FILE_NAME);
         let mut opt_builder = OptionsBuilder {
+            project_config: Some(project_config).filter(|path| path.is_file()),
             ignore_env: matches.is_present("ignore_env"),
-            load_implicit_env_file: !matches.is_present("no_implicit_env_file"),
+            load_implicit_env_file: !(matches.is_present("no_implicit_env_file")
+                || matches.is_present("from_snapshot")
+                || config.no_implicit_env_file == Some(true)),
+            snapshot: matches
+                .value_of("from_snapshot")
+                .map(snapshot::load)
+                .transpose()?,
             print_warnings: !matches.is_present("quiet"),
+            allow_placeholders: matches.is_present("allow_placeholders"),
+            dangerous_vars: matches
+                .value_of("dangerous_vars")
+                .unwrap_or_default()
+                .parse()?,
+            strict_expiry: matches.is_present("strict_expiry"),
+            warn_duplicates: matches.is_present("warn_duplicates")
+                || matches.is_present("strict_duplicates"),
+            strict_duplicates: matches.is_present("strict_duplicates"),
+            strict_secrets: matches.is_present("strict_secrets"),
+            no_expand: matches.is_present("no_expand"),
+            raw_values: matches.is_present("raw_values"),
+            show_secrets: matches.is_present("show_secrets"),
+            strict: matches.is_present("strict"),
+            verbose: matches.is_present("verbose"),
+            require_schema: matches.is_present("require_schema"),
+            explain: matches.value_of("explain").map(str::to_owned),
+            kill_timeout: matches
+                .value_of("kill_timeout")
+                .map(str::parse)
+                .transpose()?,
+            timeout: matches
+                .value_of("timeout")
+                .map(parse_duration)
+                .transpose()?,
+            kill_after: matches
+                .value_of("kill_after")
+                .map(parse_duration)
+                .transpose()?,
+            fork: matches.is_present("fork"),
+            parallel: matches.is_present("parallel"),
+            pty: matches.is_present("pty"),
+            allow_file_refs: matches.is_present("allow_file_refs"),
+            allow_aws_refs: matches.is_present("allow_aws_refs"),
+            allow_cmd_refs: matches.is_present("allow_cmd_refs"),
+            allow_exec: matches.is_present("allow_exec"),
+            null: matches.is_present("null"),
+            // With --chdir-first, enw itself has already changed directory
+            chdir: matches
+                .value_of_os("chdir")
+                .filter(|_| !matches.is_present("chdir_first"))
+                .map(PathBuf::from),
+            empty_is_unset: matches.is_present("empty_is_unset"),
+            unset: values_of_os(&matches, "unset"),
+            no_override: if matches.is_present("no_override") {
+                true
+            } else if matches.is_present("override") {
+                false
+            } else {
+                match env::var(OVERRIDE_VAR).as_deref() {
+                    Ok("0" | "false") => true,
+                    Ok("1" | "true" | "") | Err(_) => false,
+                    Ok(value) => {
+                        return Err(format!("{OVERRIDE_VAR} must be 1 or 0, got {value}").into())
+                    }
+                }
+            },
+            report: matches.value_of_os("report").map(PathBuf::from),
+            search_path: matches.value_of_os("search_path").map(OsString::from),
+            color: matches.value_of("color").unwrap_or_default().parse()?,
+            isolate: matches.values_of_lossy("isolate").unwrap_or_default(),
+            sandbox: matches.values_of_lossy("sandbox").unwrap_or_default(),
+            systemd_run: match matches.is_present("systemd_run") {
+                true => Some(matches.value_of("systemd_run").unwrap_or("scope").parse()?),
+                false => None,
+            },
+            frozen: matches.is_present("frozen").then(|| {
+                matches
+                    .value_of_os("frozen")
+                    .map_or_else(|| PathBuf::from(lock::FILE_NAME), PathBuf::from)
+            }),
+            systemd_properties: matches
+                .values_of_lossy("systemd_property")
+                .unwrap_or_default(),
+            inherit_prefixes: matches
+                .values_of_lossy("inherit_prefix")
+                .unwrap_or_default(),
+            only: matches.values_of_lossy("only").unwrap_or_default(),
+            prefix: matches.value_of("prefix").map(str::to_owned),
+            strip_prefix: matches.is_present("strip_prefix"),
+            except: matches.values_of_lossy("except").unwrap_or_default(),
+            verify: matches.is_present("verify").then(|| verify::Keys {
+                allowed_signers: matches.value_of_os("allowed_signers").map(PathBuf::from),
+                minisign_key: matches.value_of_os("minisign_key").map(PathBuf::from),
+            }),
+            ini: ini::Options {
+                section: matches.value_of("section").map(str::to_owned),
+                section_prefix: matches.is_present("section_prefix"),
+            },
+            properties: properties::Options {
+                normalize_keys: matches.is_present("normalize_keys"),
+            },
+            structured: structured::Options {
+                key_separator: matches.value_of("key_separator").map(str::to_owned),
+            },
+            format: match matches.value_of("file_format") {
+                Some("dotenv") => Some(FileFormat::Dotenv),
+                Some(ext) => FileFormat::from_extension(ext),
+                None => None,
+            },
+            compat: match matches.value_of("compat").or(matches.value_of("dialect")) {
+                Some("php") => Some(Compat::Php),
+                Some("compose") => Some(Compat::Compose),
+                Some("dotenv") => Some(Compat::Node),
+                Some("shell") => Some(Compat::Shell),
+                _ => None,
+            },
             ..Default::default()
         };
+        if let Some(preset) = matches.value_of("preset") {
+            // The preset's files replace the implicit .env file
+            let preset: preset::Preset = preset.parse()?;
+            let current_dir = env::current_dir()?;
+            opt_builder.profile = preset.environment(|key| env::var(key).ok());
+            opt_builder.load_implicit_env_file = false;
+            opt_builder.env_files.extend(
+                preset
+                    .file_names(|key| env::var(key).ok())
+                    .into_iter()
+                    .map(|fname| EnvFile {
+                        path: current_dir.join(fname),
+                        is_default: true,
+                    }),
+            );
+        }
+        let profile_name = matches
+            .value_of("profile")
+            .or(config.profile.as_deref())
+            .filter(|name| !name.is_empty());
+        if let Some(name) = profile_name {
+            profile::check_name(name)?;
+        }
         if opt_builder.load_implicit_env_file {
             // .env file from current dir automatically loaded, overridden by explicitly passed in .env
-            // files
-            opt_builder.env_files.push(EnvFile {
-                path: env::current_dir()?.join(DEFAULT_ENV_FILE_NAME),
-                is_default: true,
+            // files. In a workspace, the root's and the package's go beneath it, recursively the
+            // parent directories' do.
+            let current_dir = env::current_dir()?;
+            let dirs = if matches.is_present("recursive") {
+                let stops = matches
+                    .values_of("stop_at")
+                    .unwrap_or_default()
+                    .map(str::parse)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let home = env::var_os("HOME").map(PathBuf::from);
+                discovery::env_dirs(&current_dir, &stops, home.as_deref())
+            } else {
+                workspace::env_dirs(&current_dir)
+            };
+            opt_builder
+                .env_files
+                .extend(
+                    profile::env_files(&dirs, profile_name)
+                        .into_iter()
+                        .map(|path| EnvFile {
+                            path,
+                            is_default: true,
+                        }),
+                );
+        }
+        if let Some(name) = profile_name {
+            let current_dir = env::current_dir()?;
+            let overlays = profile::overlays(name);
+            let overlaid = opt_builder.env_files.iter().any(|file| {
+                file.path.is_file()
+                    && file.path.file_name().is_some_and(|fname| {
+                        overlays.iter().any(|overlay| fname == overlay.as_str())
+                    })
             });
+            match profile::load(&current_dir, name)? {
+                Some(profile) => {
+                    opt_builder
+                        .env_files
+                        .extend(profile.files.iter().map(|fname| EnvFile {
+                            path: current_dir.join(fname),
+                            is_default: false,
+                        }));
+                    opt_builder.profile_vars = profile.vars;
+                }
+                None if !overlaid => {
+                    return Err(format!(
+                        "profile {name}: no .env.{name} files, and not declared in {}",
+                        profile::CONFIG_FILE_NAME
+                    )
+                    .into());
+                }
+                None => {}
+            }
+            opt_builder.profile = Some(name.to_owned());
         }
-        opt_builder.env_files.extend(
-            matches
-                .values_of_lossy("env_file")
-                .unwrap_or(DEFAULT_VEC)
-                .iter()
-                .map(|fname| EnvFile {
-                    path: fname.into(),
+        opt_builder
+            .env_files
+            .extend(config.files.into_iter().map(|path| EnvFile {
+                path,
+                is_default: false,
+            }));
+        for pattern in values_of_os(&matches, "env_file") {
+            let paths = match pattern.to_str() {
+                Some(pattern) if !http::is_url(Path::new(pattern)) => glob::expand(pattern)?,
+                _ => vec![PathBuf::from(pattern)],
+            };
+            opt_builder
+                .env_files
+                .extend(paths.into_iter().map(|path| EnvFile {
+                    path,
                     is_default: false,
-                }),
-        );
-        let rest = matches.values_of_lossy("rest").unwrap_or_default();
-        opt_builder.vars = rest
-            .iter()
-            .take_while(|x| x.contains('='))
-            .map(|line| parse_env_line(line))
-            .collect::<Result<Vec<_>, _>>()?;
-        opt_builder.command = rest.get(opt_builder.vars.len()).cloned();
-        opt_builder.args = rest
+                }));
+        }
+        for (name, list) in [
+            ("default_signal", &mut opt_builder.signals.default),
+            ("ignore_signal", &mut opt_builder.signals.ignore),
+            ("block_signal", &mut opt_builder.signals.block),
+        ] {
+            if matches.is_present(name) {
+                *list = signal::parse_list(&matches.values_of_lossy(name).unwrap_or_default())?;
+            }
+        }
+        opt_builder.providers = config.from;
+        opt_builder.project_providers = config.project_from;
+        opt_builder
+            .providers
+            .extend(matches.values_of_lossy("from").unwrap_or_default());
+        opt_builder.secret_patterns = config.secrets;
+        for (arg, provider) in [("vault", "vault"), ("aws_ssm", "aws-ssm")] {
+            opt_builder.providers.extend(
+                matches
+                    .values_of_lossy(arg)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|path| format!("{provider}:{path}")),
+            );
+        }
+        opt_builder.file_auth = matches
+            .value_of("file_auth")
+            .map(str::to_owned)
+            .or_else(|| env::var(FILE_AUTH_VAR).ok())
+            .filter(|token| !token.is_empty());
+        opt_builder.remote = http::Options {
+            cache: matches.is_present("cache"),
+            timeout: matches
+                .value_of("file_timeout")
+                .map(str::parse)
+                .transpose()?,
+            print_warnings: opt_builder.print_warnings,
+            color: opt_builder.color,
+            ..Default::default()
+        };
+        opt_builder.secret_via_file = matches
+            .values_of_lossy("secret_via_file")
+            .unwrap_or_default();
+        opt_builder.secret_via_fd = matches.values_of_lossy("secret_via_fd").unwrap_or_default();
+        if let Some(export) = matches.subcommand_matches("export") {
+            opt_builder.export = Some(export::Export {
+                format: export_format(export).parse()?,
+                key_prefix: export.value_of("key_prefix").map(str::to_owned),
+            });
+        }
+        let mut rest = values_of_os(&matches, "rest");
+        // `env - COMMAND` is the historical spelling of `env -i COMMAND`
+        if rest.first().is_some_and(|x| x == "-") {
+            rest.remove(0);
+            opt_builder.ignore_env = true;
+        }
+        // A `--` ends the NAME=VALUE pairs explicitly. Otherwise they run up to the first
+        // argument without a `=`.
+        let (var_count, command_index) = if let Some(separator) = rest
             .iter()
-            .skip(opt_builder.vars.len() + 1)
-            .cloned()
+            .position(|x| x == "--")
+            .filter(|&i| rest[..i].iter().all(|arg| is_assignment(arg)))
+        {
+            (separator, separator + 1)
+        } else if matches.is_present("no_positional_vars") {
+            (0, 0)
+        } else {
+            let count = rest.iter().take_while(|arg| is_assignment(arg)).count();
+            (count, count)
+        };
+        // With -0, --stdin takes NAME=VALUE pairs literally, like --env, so that they can hold
+        // newlines. Otherwise stdin is read as an env file, like `-f -`.
+        if matches.is_present("stdin") && !opt_builder.null {
+            opt_builder.env_files.push(EnvFile {
+                path: PathBuf::from("-"),
+                is_default: false,
+            });
+        } else if matches.is_present("stdin") {
+            for pair in pipeline::read_stdin()?
+                .split('\0')
+                .filter(|pair| !pair.is_empty())
+            {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--stdin expects NAME=VALUE: {pair}"))?;
+                if !key_is_valid(key) {
+                    return Err(format!("KEY contains invalid characters: {key}").into());
+                }
+                opt_builder.vars.push((key.to_owned(), value.to_owned()));
+            }
+        }
+        // Values given with --env are taken literally, without dequoting or escapes
+        for pair in values_of_os(&matches, "env") {
+            let Some(pair) = pair.to_str() else {
+                opt_builder.os_vars.push(split_os_pair(&pair)?);
+                continue;
+            };
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("--env expects KEY=VALUE: {pair}"))?;
+            if !key_is_valid(key) {
+                return Err(format!("KEY contains invalid characters: {key}").into());
+            }
+            opt_builder.vars.push((key.to_owned(), value.to_owned()));
+        }
+        for line in &rest[..var_count] {
+            match line.to_str() {
+                Some(line) => opt_builder.vars.push(parse_env_line(line)?),
+                None => opt_builder.os_vars.push(split_os_pair(line)?),
+            }
+        }
+        let commands: Vec<Vec<OsString>> = rest
+            .get(command_index..)
+            .unwrap_or_default()
+            .split(|arg| arg == "--and")
+            .map(<[OsString]>::to_vec)
             .collect();
+        if commands.len() > 1 {
+            if opt_builder.report.is_some() || opt_builder.timeout.is_some() {
+                return Err("--and cannot be used with --report or --timeout".into());
+            }
+            if opt_builder.pty {
+                return Err("--and cannot be used with --pty".into());
+            }
+            // The pipe holds one copy of each secret, which the first command would drain
+            if !opt_builder.secret_via_fd.is_empty() {
+                return Err(
+                    "--and cannot be used with --secret-via-fd, use --secret-via-file instead"
+                        .into(),
+                );
+            }
+            for command in &commands {
+                if command.is_empty() {
+                    return Err("--and needs a command on both sides".into());
+                }
+            }
+        }
+        let mut commands = commands.into_iter().map(|mut command| {
+            let args = command.split_off(1.min(command.len()));
+            (command.pop(), args)
+        });
+        (opt_builder.command, opt_builder.args) = commands.next().unwrap_or_default();
+        opt_builder.then = commands
+            .filter_map(|(command, args)| Some((command?, args)))
+            .collect();
+        if matches.is_present("via_shell") {
+            // The words make up the script, joined like `sh -c "$*"` would
+            let shell = env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into());
+            let wrap = |command: &mut OsString, args: &mut Vec<OsString>| {
+                let mut script = mem::replace(command, shell.clone());
+                for arg in args.drain(..) {
+                    script.push(" ");
+                    script.push(arg);
+                }
+                args.extend(["-c".into(), script]);
+            };
+            if let Some(command) = &mut opt_builder.command {
+                wrap(command, &mut opt_builder.args);
+            }
+            for (command, args) in &mut opt_builder.then {
+                wrap(command, args);
+            }
+        }
+        if opt_builder.null {
+            if let Some(command) = &opt_builder.command {
+                let command = command.to_string_lossy();
+                return Err(format!("-0 cannot be used with a command, got {command}").into());
+            }
+        }
+        if matches.is_present("print") {
+            if let Some(command) = &opt_builder.command {
+                let command = command.to_string_lossy();
+                return Err(format!("--print does not run a command, got {command}").into());
+            }
+            let default = if opt_builder.null { "null" } else { "env" };
+            let format = matches.value_of("print").unwrap_or(default).parse()?;
+            if opt_builder.null && format != print::Format::Null {
+                return Err("-0 only goes with --print=null".into());
+            }
+            opt_builder.print = Some(format);
+        }
+        if matches.subcommand_matches("shell").is_some() {
+            opt_builder.shell = true;
+            opt_builder.command = Some(env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into()));
+        }
         Ok(opt_builder)
     }
 }
@@ -449,6 +3282,213 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_multiline_values() {
+        let actual = parse_env_doc(
+            "KEY1=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\" # pem\n\
+             # KEY2=not a key\n\
+             KEY3=\"\"\"\n  {\"a\": \"$b\"}\n  \"\"\"\n\
+             KEY4='single'\n\
+             KEY5=\"\"\"one line\"\"\"\n",
+        );
+
+        let expected = vec![
+            ("KEY1", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+            ("KEY3", "  {\"a\": \"$b\"}"),
+            ("KEY4", "single"),
+            ("KEY5", "one line"),
+        ]
+        .into_iter()
+        .map(|(k, v)| owned(k, v))
+        .collect::<Vec<_>>();
+
+        let actual: Vec<_> = actual.into_iter().map(Result::unwrap).collect();
+        assert_eq!(actual, expected);
+        assert!(parse_env_doc("KEY=\"\"\"\nopen\n")[0].is_err());
+    }
+
+    #[test]
+    fn test_parse_shell_syntax() {
+        let actual = parse_env_doc(
+            r#"
+            export KEY1=foo
+            export  KEY2="bar baz"
+            set -gx KEY3 'qux'
+            set --export KEY4 quux
+            set LOCAL not exported
+            export=1
+            "#,
+        );
+
+        let expected = vec![
+            ("KEY1", "foo"),
+            ("KEY2", "bar baz"),
+            ("KEY3", "qux"),
+            ("KEY4", "quux"),
+            ("export", "1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| owned(k, v))
+        .collect::<Vec<_>>();
+
+        let actual: Vec<_> = actual.into_iter().map(Result::unwrap).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_expansion() {
+        let lookup = |key: &str| (key == "HOME").then(|| "/home/me".to_owned());
+        let actual = parse_env_doc_expanding(
+            r#"
+            DIR=$HOME/app
+            LOG="${DIR}/log"
+            RAW='$DIR'
+            ESCAPED=\$DIR "\${DIR}"
+            MISSING=[$UNSET]
+            PRICE=5$
+            "#,
+            &lookup,
+            None,
+        );
+
+        let expected = vec![
+            ("DIR", "/home/me/app"),
+            ("LOG", "/home/me/app/log"),
+            ("RAW", "$DIR"),
+            ("ESCAPED", "$DIR ${DIR}"),
+            ("MISSING", "[]"),
+            ("PRICE", "5$"),
+        ]
+        .into_iter()
+        .map(|(k, v)| owned(k, v));
+
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            assert_eq!(actual.unwrap(), expected);
+        }
+        let actual = parse_env_doc_expanding("KEY=${OPEN", &lookup, None);
+        assert!(actual[0].is_err());
+    }
+
+    #[test]
+    fn test_is_assignment() {
+        for arg in ["A=1", "a_b=", "_X==", "app.port=80", "A=b c"] {
+            assert!(is_assignment(OsStr::new(arg)), "{arg}");
+        }
+        for arg in [
+            "rsync", "./run=1", "/bin/x=y", "=x", "1A=x", "a b=c", "host:/x",
+        ] {
+            assert!(!is_assignment(OsStr::new(arg)), "{arg}");
+        }
+        assert!(is_assignment(OsStr::from_bytes(b"A=caf\xe9")));
+    }
+
+    #[test]
+    fn test_split_string() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        let string = OsStr::new("-f .env 'a b'");
+        for (before, rest_len, expected) in [
+            (
+                &["enw", "-S", "-f .env 'a b'", "x"][..],
+                1,
+                &["enw", "-f", ".env", "a b", "x"][..],
+            ),
+            (
+                &["enw", "-S-f .env 'a b'"],
+                0,
+                &["enw", "-f", ".env", "a b"],
+            ),
+            (
+                &["enw", "-iS-f .env 'a b'"],
+                0,
+                &["enw", "-i", "-f", ".env", "a b"],
+            ),
+            (
+                &["enw", "--split-string=-f .env 'a b'"],
+                0,
+                &["enw", "-f", ".env", "a b"],
+            ),
+        ] {
+            let mut actual = args(before);
+            split_string(&mut actual, string, rest_len).unwrap();
+            assert_eq!(actual, args(expected));
+        }
+        // Arguments of the command are left alone
+        let mut actual = args(&["enw", "sort", "-S", "-f .env 'a b'"]);
+        assert!(split_string(&mut actual, string, 3).is_err());
+    }
+
+    #[test]
+    fn test_parse_substitution() {
+        let lookup = |_: &str| None;
+        let text = "SHA=$(git rev-parse HEAD)\nMSG=\"at $(echo $(date))\"\nRAW='$(x)'";
+        let substitute = |command: &str| Ok(format!("<{command}>"));
+        let actual: Vec<_> = parse_env_doc_expanding(text, &lookup, Some(&substitute))
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                owned("SHA", "<git rev-parse HEAD>"),
+                owned("MSG", "at <echo $(date)>"),
+                owned("RAW", "$(x)"),
+            ]
+        );
+        let actual = parse_env_doc_expanding("SHA=$(git", &lookup, Some(&substitute));
+        assert!(actual[0].is_err());
+        let actual = parse_env_doc_expanding(text, &lookup, None);
+        assert_eq!(actual[0].as_ref().unwrap().1, "$(git rev-parse HEAD)");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("0.25s").unwrap(), Duration::from_millis(250));
+        for s in ["", "m", "-1", "5x", "1e400"] {
+            assert!(parse_duration(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let text = "# kept out\n\nA= 'quoted' # not a comment\nexport B=\\n$HOME\\\nC==\"\r\n";
+        let actual: Vec<_> = parse_env_doc_raw(text)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                owned("A", " 'quoted' # not a comment"),
+                owned("B", "\\n$HOME\\"),
+                owned("C", "=\""),
+            ]
+        );
+        assert!(parse_env_doc_raw("no value")[0].is_err());
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        assert_eq!(
+            check_syntax("# ok\n\nA=1\nexport B=\"two\nlines\"\n"),
+            Ok(())
+        );
+        assert_eq!(
+            check_syntax("A=1\n  oops\n"),
+            Err("2:3: expected KEY=VALUE".to_owned())
+        );
+        assert_eq!(
+            check_syntax("A=1\nbad key=2\n"),
+            Err("2:1: KEY contains invalid characters: bad key".to_owned())
+        );
+        assert_eq!(
+            check_syntax("A= 'open\n"),
+            Err("1:4: error parsing value: unmatched quotes.".to_owned())
+        );
+    }
+
     #[test]
     fn test_parse_value_escapes_invalid() {
         let actuals = parse_env_doc(
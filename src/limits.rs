@@ -0,0 +1,127 @@
+//! The kernel refuses to exec a command whose environment and arguments are too large (E2BIG).
+//! Checking beforehand allows naming the variables to blame.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    mem,
+};
+
+use crate::{BoxError, Var};
+
+/// Linux's limit on the length of a single argument or `KEY=VALUE` string
+const MAX_ARG_STRLEN: usize = 32 * 4096;
+/// Warn once this share of the limit is taken
+const WARN_PERCENT: usize = 75;
+const HINT: &str = "consider --secret-via-file for large values";
+
+/// Fails when exec would, warns when it gets close
+pub fn check(
+    env: &BTreeMap<OsString, OsString>,
+    argv: &[&OsStr],
+    vars: &[Var],
+) -> Result<Option<String>, BoxError> {
+    let too_long: Vec<_> = vars
+        .iter()
+        .filter(|var| cfg!(target_os = "linux") && size(var) > MAX_ARG_STRLEN)
+        .map(|var| var.key.as_str())
+        .collect();
+    if !too_long.is_empty() {
+        return Err(format!(
+            "{} too long for the environment, the limit is {MAX_ARG_STRLEN} bytes per \
+             variable; {HINT}",
+            too_long.join(", ")
+        )
+        .into());
+    }
+    let strings = env
+        .iter()
+        .map(|(key, value)| key.len() + value.len() + 2)
+        .chain(argv.iter().map(|arg| arg.len() + 1));
+    // Each string also takes a pointer in the array passed to exec
+    let total: usize = strings.map(|len| len + mem::size_of::<usize>()).sum();
+    let limit = arg_max();
+    if total > limit {
+        Err(format!(
+            "the environment and arguments take {total} bytes, more than the limit of {limit}; \
+             largest variables: {}; {HINT}",
+            largest(vars)
+        )
+        .into())
+    } else if total * 100 > limit * WARN_PERCENT {
+        Ok(Some(format!(
+            "the environment and arguments take {total} of {limit} bytes allowed; largest \
+             variables: {}",
+            largest(vars)
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+fn size(var: &Var) -> usize {
+    var.key.len() + var.value.len() + 2
+}
+
+fn largest(vars: &[Var]) -> String {
+    let mut vars: Vec<_> = vars.iter().collect();
+    vars.sort_by_key(|var| std::cmp::Reverse(size(var)));
+    let listing: Vec<_> = vars
+        .iter()
+        .take(3)
+        .map(|var| format!("{} ({} bytes)", var.key, size(var)))
+        .collect();
+    listing.join(", ")
+}
+
+fn arg_max() -> usize {
+    // SAFETY: no preconditions
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    if arg_max > 0 {
+        arg_max as usize
+    } else {
+        // The POSIX minimum
+        4096
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    fn env(vars: &[Var]) -> BTreeMap<OsString, OsString> {
+        vars.iter()
+            .map(|var| (var.key.clone().into(), var.value.clone().into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let small = vec![Var::new("A".into(), "1".into(), Origin::CommandLine)];
+        assert_eq!(
+            check(&env(&small), &[OsStr::new("true")], &small).unwrap(),
+            None
+        );
+
+        let mut huge = small.clone();
+        huge.push(Var::new(
+            "CERT".into(),
+            "x".repeat(MAX_ARG_STRLEN),
+            Origin::CommandLine,
+        ));
+        let message = check(&env(&huge), &[], &huge).unwrap_err().to_string();
+        assert!(message.starts_with("CERT too long"), "{message}");
+
+        let many: Vec<_> = (0..arg_max() / 1000)
+            .map(|i| Var::new(format!("V{i:05}"), "x".repeat(1000), Origin::CommandLine))
+            .collect();
+        let message = check(&env(&many), &[], &many).unwrap_err().to_string();
+        assert!(
+            message.contains("largest variables: V00000 (1008 bytes)"),
+            "{message}"
+        );
+    }
+}
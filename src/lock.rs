@@ -0,0 +1,128 @@
+//! `enw freeze` records the resolved variables in `.env.lock`, and `--frozen` refuses to run
+//! when the resolution no longer matches it. Secrets are recorded as digests, so the lock file
+//! can be committed.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{format_var, sha256, BoxError, Var};
+
+pub const FILE_NAME: &str = ".env.lock";
+const HEADER: &str = "# Generated by `enw freeze`, checked by `enw --frozen`. Do not edit.";
+const DIGEST_PREFIX: &str = "# sha256: ";
+
+/// The lines recorded for `vars`, without header
+fn body(vars: &[Var]) -> String {
+    vars.iter()
+        .map(|var| {
+            if var.is_secret() {
+                let digest = sha256::hex_digest(var.value.as_bytes());
+                format!("{}=sha256:{digest}\n", var.key)
+            } else {
+                format_var(&var.key, &var.value) + "\n"
+            }
+        })
+        .collect()
+}
+
+pub fn render(vars: &[Var]) -> String {
+    let body = body(vars);
+    let digest = sha256::hex_digest(body.as_bytes());
+    format!("{HEADER}\n{DIGEST_PREFIX}{digest}\n{body}")
+}
+
+pub fn write(path: &Path, vars: &[Var]) -> Result<(), BoxError> {
+    fs::write(path, render(vars))
+        .map_err(|e| format!("writing {}: {e}", path.to_string_lossy()).into())
+}
+
+/// Fail, listing the variables that changed, unless `vars` match the lock file at `path`
+pub fn check(path: &Path, vars: &[Var]) -> Result<(), BoxError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("--frozen: reading {}: {e}", path.to_string_lossy()))?;
+    let locked = text
+        .strip_prefix(HEADER)
+        .and_then(|rest| rest.strip_prefix('\n'))
+        .and_then(|rest| rest.strip_prefix(DIGEST_PREFIX))
+        .and_then(|rest| rest.split_once('\n'))
+        .filter(|(digest, body)| sha256::hex_digest(body.as_bytes()) == *digest)
+        .map(|(_, body)| body)
+        .ok_or_else(|| {
+            format!(
+                "--frozen: {} is damaged or was edited by hand, run `enw freeze` again",
+                path.to_string_lossy()
+            )
+        })?;
+    let current = body(vars);
+    if current == locked {
+        return Ok(());
+    }
+    let entries = |body: &str| -> BTreeMap<String, String> {
+        body.lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    };
+    let (old, new) = (entries(locked), entries(&current));
+    let mut changes = Vec::new();
+    for (key, value) in &old {
+        match new.get(key) {
+            None => changes.push(format!("{key} removed")),
+            Some(new_value) if new_value != value => changes.push(format!("{key} changed")),
+            _ => {}
+        }
+    }
+    changes.extend(
+        new.keys()
+            .filter(|key| !old.contains_key(*key))
+            .map(|key| format!("{key} added")),
+    );
+    Err(format!(
+        "--frozen: the environment differs from {}:\n  {}\nrun `enw freeze` to accept the changes",
+        path.to_string_lossy(),
+        changes.join("\n  ")
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    fn var(key: &str, value: &str) -> Var {
+        Var::new(key.into(), value.into(), Origin::CommandLine)
+    }
+
+    #[test]
+    fn test_render() {
+        let rendered = render(&[var("A", "1 2"), var("API_TOKEN", "abc")]);
+        let lines: Vec<_> = rendered.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "A=\"1 2\"",
+                "API_TOKEN=sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let path = env::temp_dir().join(format!("enw-lock-test-{}", std::process::id()));
+        write(&path, &[var("A", "1"), var("B", "2")]).unwrap();
+        assert!(check(&path, &[var("A", "1"), var("B", "2")]).is_ok());
+        let message = check(&path, &[var("A", "1"), var("B", "3"), var("C", "")])
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("\n  B changed\n  C added\n"), "{message}");
+
+        fs::write(&path, render(&[var("A", "1")]).replace("A=1", "A=2")).unwrap();
+        let message = check(&path, &[var("A", "2")]).unwrap_err().to_string();
+        assert!(message.contains("edited by hand"), "{message}");
+        fs::remove_file(path).unwrap();
+    }
+}
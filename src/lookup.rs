@@ -0,0 +1,61 @@
+//! Locating COMMAND, so a failed lookup can say where enw looked.
+
+use std::{
+    env,
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{BoxError, CommandError};
+
+/// Find `command` in the directories of `search_path`, a `PATH` style list. Commands containing
+/// a slash are not looked up.
+pub fn find(command: &OsStr, search_path: &OsStr) -> Result<PathBuf, BoxError> {
+    let bytes = command.as_encoded_bytes();
+    if bytes.contains(&b'/') {
+        return Ok(PathBuf::from(command));
+    }
+    env::split_paths(search_path)
+        .map(|dir| dir.join(command))
+        .find(|path| is_executable(path))
+        .ok_or_else(|| {
+            CommandError::not_found(format!(
+                "{}: command not found, searched: {}",
+                command.to_string_lossy(),
+                search_path.to_string_lossy()
+            ))
+            .into()
+        })
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let search_path = OsStr::new("/nonexistent:/bin:/usr/bin");
+        assert!(find(OsStr::new("sh"), search_path).unwrap().ends_with("sh"));
+        assert_eq!(
+            find(OsStr::new("./x"), search_path).unwrap(),
+            PathBuf::from("./x")
+        );
+        assert_eq!(
+            find(
+                OsStr::new("no-such-command"),
+                OsStr::new("/nonexistent:/bin")
+            )
+            .unwrap_err()
+            .to_string(),
+            "no-such-command: command not found, searched: /nonexistent:/bin"
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! `enw lsp`, a language server for env files speaking JSON-RPC over stdio. It offers
+//! diagnostics, hover, go-to-definition across the env files next to the document, and
+//! completion of keys set in those files.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{assignments, json::Value, lock, parse_env_line, split_assignment, BoxError};
+
+const ERROR: f64 = 1.0;
+const WARNING: f64 = 2.0;
+
+/// A `KEY=VALUE` line, or the error parsing it
+#[derive(Debug, PartialEq)]
+struct Entry {
+    line: usize,
+    column: usize,
+    key: String,
+    value: Result<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    line: usize,
+    start: usize,
+    end: usize,
+    severity: f64,
+    message: String,
+}
+
+/// Serve requests on stdin until the client sends `exit`
+pub fn serve() -> Result<(), BoxError> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    let mut documents: HashMap<String, String> = HashMap::new();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let uri = params
+            .get("textDocument")
+            .and_then(|doc| doc.get("uri"))
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_owned();
+        let result = match method {
+            "initialize" => capabilities(),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => {
+                        params.get("textDocument").and_then(|d| d.get("text"))
+                    }
+                    // Full document sync, the last change holds the whole text
+                    _ => params
+                        .get("contentChanges")
+                        .and_then(Value::as_array)
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text")),
+                };
+                let text = text.and_then(Value::as_str).unwrap_or("").to_owned();
+                publish(&mut output, &uri, &diagnostics(&text))?;
+                documents.insert(uri, text);
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+                publish(&mut output, &uri, &[])?;
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" | "textDocument/completion" => {
+                let text = documents.get(&uri).map_or("", String::as_str);
+                let (line, character) = position(&params);
+                let key = key_at(text, line, character);
+                let siblings = siblings(&uri_to_path(&uri));
+                match method {
+                    "textDocument/hover" => {
+                        key.map_or(Value::Null, |key| hover(text, &key, &siblings))
+                    }
+                    "textDocument/definition" => Value::Array(
+                        key.map(|key| definitions(&key, &siblings))
+                            .unwrap_or_default(),
+                    ),
+                    _ => Value::Array(completions(text, &siblings)),
+                }
+            }
+            _ => Value::Null,
+        };
+        // Notifications have no id and get no response
+        if let Some(id) = message.get("id") {
+            let mut response = BTreeMap::new();
+            response.insert("jsonrpc".to_owned(), Value::String("2.0".to_owned()));
+            response.insert("id".to_owned(), id.clone());
+            response.insert("result".to_owned(), result);
+            write_message(&mut output, &Value::Object(response))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, BoxError> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let mut body = vec![0; length.ok_or("lsp: message without Content-Length")?];
+    input.read_exact(&mut body)?;
+    Ok(Some(crate::json::parse(&String::from_utf8(body)?)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<(), BoxError> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn capabilities() -> Value {
+    object(vec![(
+        "capabilities",
+        object(vec![
+            ("textDocumentSync", Value::Number(1.0)),
+            ("hoverProvider", Value::Bool(true)),
+            ("definitionProvider", Value::Bool(true)),
+            ("completionProvider", object(vec![])),
+        ]),
+    )])
+}
+
+fn publish(output: &mut impl Write, uri: &str, diagnostics: &[Diagnostic]) -> Result<(), BoxError> {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            object(vec![
+                (
+                    "range",
+                    range(diagnostic.line, diagnostic.start, diagnostic.end),
+                ),
+                ("severity", Value::Number(diagnostic.severity)),
+                ("source", Value::String("enw".to_owned())),
+                ("message", Value::String(diagnostic.message.clone())),
+            ])
+        })
+        .collect();
+    let notification = object(vec![
+        ("jsonrpc", Value::String("2.0".to_owned())),
+        (
+            "method",
+            Value::String("textDocument/publishDiagnostics".to_owned()),
+        ),
+        (
+            "params",
+            object(vec![
+                ("uri", Value::String(uri.to_owned())),
+                ("diagnostics", Value::Array(diagnostics)),
+            ]),
+        ),
+    ]);
+    write_message(output, &notification)
+}
+
+fn range(line: usize, start: usize, end: usize) -> Value {
+    let position = |character: usize| {
+        object(vec![
+            ("line", Value::Number(line as f64)),
+            ("character", Value::Number(character as f64)),
+        ])
+    };
+    object(vec![("start", position(start)), ("end", position(end))])
+}
+
+fn position(params: &Value) -> (usize, usize) {
+    let field = |name| match params.get("position").and_then(|pos| pos.get(name)) {
+        Some(Value::Number(n)) => *n as usize,
+        _ => 0,
+    };
+    (field("line"), field("character"))
+}
+
+fn entries(text: &str) -> Vec<Entry> {
+    assignments(text)
+        .into_iter()
+        .map(|(line, raw)| {
+            let trimmed = raw.trim_start();
+            let (key, _) = split_assignment(raw).unwrap_or((trimmed, ""));
+            // The key is a slice of the line, behind any `export`
+            let offset = key.as_ptr() as usize - raw.as_ptr() as usize;
+            Entry {
+                line,
+                column: utf16_len(&raw[..offset]),
+                key: key.to_owned(),
+                value: parse_env_line(trimmed)
+                    .map(|(_, value)| value)
+                    .map_err(|e| e.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Editors count columns in UTF-16 code units
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    // Lines of values spanning several lines
+    let continued: Vec<usize> = assignments(text)
+        .into_iter()
+        .flat_map(|(line, raw)| line + 1..line + raw.lines().count())
+        .collect();
+    for (line, raw) in text.lines().enumerate() {
+        let trimmed = raw.trim();
+        if !continued.contains(&line)
+            && !trimmed.is_empty()
+            && !trimmed.starts_with('#')
+            && !trimmed.contains('=')
+        {
+            out.push(Diagnostic {
+                line,
+                start: 0,
+                end: utf16_len(raw),
+                severity: WARNING,
+                message: "ignored, not a KEY=VALUE line".to_owned(),
+            });
+        }
+    }
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let entries = entries(text);
+    for entry in &entries {
+        let end = entry.column + utf16_len(&entry.key);
+        if let Err(message) = &entry.value {
+            out.push(Diagnostic {
+                line: entry.line,
+                start: entry.column,
+                end,
+                severity: ERROR,
+                message: message.clone(),
+            });
+        } else if let Some(first) = seen.insert(&entry.key, entry.line) {
+            out.push(Diagnostic {
+                line: entry.line,
+                start: entry.column,
+                end,
+                severity: WARNING,
+                message: format!("{} is already set on line {}", entry.key, first + 1),
+            });
+        }
+    }
+    out.sort_by_key(|diagnostic| diagnostic.line);
+    out
+}
+
+/// The key defined on `line`, when the cursor is on it
+fn key_at(text: &str, line: usize, character: usize) -> Option<String> {
+    let entry = entries(text).into_iter().find(|entry| entry.line == line)?;
+    (character <= entry.column + utf16_len(&entry.key)).then_some(entry.key)
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let decoded = (b == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The env files in the document's directory, with their contents, in name order
+fn siblings(path: &Path) -> Vec<(PathBuf, String)> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(".env") && name != lock::FILE_NAME && path.is_file()
+        })
+        .filter_map(|path| Some((path.clone(), fs::read_to_string(&path).ok()?)))
+        .collect();
+    files.sort();
+    files
+}
+
+fn hover(text: &str, key: &str, siblings: &[(PathBuf, String)]) -> Value {
+    let mut lines = Vec::new();
+    if let Some(Entry {
+        value: Ok(value), ..
+    }) = entries(text)
+        .into_iter()
+        .rev()
+        .find(|entry| entry.key == key)
+    {
+        lines.push(format!("`{key}` = `{value}`"));
+    }
+    for (path, text) in siblings {
+        for entry in entries(text).into_iter().filter(|entry| entry.key == key) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            lines.push(format!("set in {name}:{}", entry.line + 1));
+        }
+    }
+    object(vec![("contents", Value::String(lines.join("\n\n")))])
+}
+
+fn definitions(key: &str, siblings: &[(PathBuf, String)]) -> Vec<Value> {
+    siblings
+        .iter()
+        .flat_map(|(path, text)| {
+            entries(text)
+                .into_iter()
+                .filter(|entry| entry.key == key)
+                .map(move |entry| {
+                    let end = entry.column + utf16_len(&entry.key);
+                    object(vec![
+                        (
+                            "uri",
+                            Value::String(format!("file://{}", path.to_string_lossy())),
+                        ),
+                        ("range", range(entry.line, entry.column, end)),
+                    ])
+                })
+        })
+        .collect()
+}
+
+/// Keys set in the other env files, but not in this one
+fn completions(text: &str, siblings: &[(PathBuf, String)]) -> Vec<Value> {
+    let present: Vec<_> = entries(text).into_iter().map(|entry| entry.key).collect();
+    let mut keys: BTreeMap<String, String> = BTreeMap::new();
+    for (path, text) in siblings {
+        for entry in entries(text) {
+            if !present.contains(&entry.key) {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                keys.entry(entry.key).or_insert_with(|| name.into_owned());
+            }
+        }
+    }
+    keys.into_iter()
+        .map(|(key, file)| {
+            object(vec![
+                ("label", Value::String(key.clone())),
+                ("detail", Value::String(file)),
+                ("insertText", Value::String(format!("{key}="))),
+            ])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let text = "A=1\n# comment\nnonsense\n  A=2\nB='open\nC D=3\n";
+        let found: Vec<_> = diagnostics(text)
+            .into_iter()
+            .map(|d| (d.line, d.start, d.end, d.severity, d.message))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, 0, 8, WARNING, "ignored, not a KEY=VALUE line".to_owned()),
+                (3, 2, 3, WARNING, "A is already set on line 1".to_owned()),
+                (
+                    4,
+                    0,
+                    1,
+                    ERROR,
+                    "error parsing value: unmatched quotes.".to_owned()
+                ),
+                (
+                    5,
+                    0,
+                    3,
+                    ERROR,
+                    "KEY contains invalid characters: C D".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_at() {
+        let text = "A=1\n  LONG_KEY = value\n";
+        assert_eq!(key_at(text, 1, 4), Some("LONG_KEY".to_owned()));
+        assert_eq!(key_at(text, 1, 15), None);
+        assert_eq!(key_at(text, 2, 0), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let siblings = vec![
+            (PathBuf::from(".env"), "A=1\nB=2\n".to_owned()),
+            (PathBuf::from(".env.example"), "C=\nA=\n".to_owned()),
+        ];
+        let labels: Vec<_> = completions("A=0\n", &siblings)
+            .iter()
+            .map(|item| {
+                item.get("label")
+                    .and_then(Value::as_str)
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(labels, vec!["B", "C"]);
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///my%20app/.env"),
+            PathBuf::from("/my app/.env")
+        );
+    }
+}
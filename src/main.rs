@@ -1,5 +1,9 @@
-use enw::BoxError;
-
-fn main() -> Result<(), BoxError> {
-    enw::run(std::env::args())
+fn main() {
+    match enw::run(std::env::args_os()) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            std::process::exit(enw::error_exit_code(&e))
+        }
+    }
 }
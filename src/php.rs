@@ -0,0 +1,179 @@
+//! Parsing rules of vlucas/phpdotenv (v5), selected with `--compat php`:
+//!
+//! * `${VAR}` is expanded in unquoted and double quoted values, from keys defined earlier in the
+//!   file, then from `lookup`. `$VAR` without braces is kept literally.
+//! * Single quoted values are taken literally, apart from `\'` and `\\`.
+//! * In unquoted values `#` only starts a comment when preceded by whitespace, so `A=b#c` is
+//!   `b#c`.
+
+use std::collections::HashMap;
+
+use crate::{key_is_valid, BoxError};
+
+pub fn parse(
+    text: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Vec<Result<(String, String), BoxError>> {
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut out = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            out.push(Err(format!("expected KEY=VALUE: {line}").into()));
+            continue;
+        };
+        let key = key.trim();
+        if !key_is_valid(key) {
+            out.push(Err(format!("KEY contains invalid characters: {key}").into()));
+            continue;
+        }
+        let resolve = |name: &str| defined.get(name).cloned().or_else(|| lookup(name));
+        let entry = parse_value(value.trim_start(), &resolve).map(|value| (key.to_owned(), value));
+        if let Ok((key, value)) = &entry {
+            defined.insert(key.clone(), value.clone());
+        }
+        out.push(entry);
+    }
+    out
+}
+
+fn parse_value(v: &str, resolve: &dyn Fn(&str) -> Option<String>) -> Result<String, BoxError> {
+    let mut out = String::new();
+    let mut chars = v.chars().peekable();
+    let rest: String = match chars.peek() {
+        Some('\'') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('\\') if matches!(chars.peek(), Some('\'') | Some('\\')) => {
+                        out.push(chars.next().unwrap())
+                    }
+                    Some('\'') => break,
+                    Some(c) => out.push(c),
+                    None => return Err("error parsing value: unmatched quotes.".into()),
+                }
+            }
+            chars.collect()
+        }
+        Some('"') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('n') => out.push('\n'),
+                        Some('r') => out.push('\r'),
+                        Some('t') => out.push('\t'),
+                        Some('f') => out.push('\u{c}'),
+                        Some('v') => out.push('\u{b}'),
+                        Some(c @ ('"' | '\\' | '$')) => out.push(c),
+                        Some(c) => {
+                            out.push('\\');
+                            out.push(c);
+                        }
+                        None => return Err("error parsing value: unmatched quotes.".into()),
+                    },
+                    Some('$') if chars.peek() == Some(&'{') => {
+                        out.push_str(&expand(&mut chars, resolve)?)
+                    }
+                    Some('"') => break,
+                    Some(c) => out.push(c),
+                    None => return Err("error parsing value: unmatched quotes.".into()),
+                }
+            }
+            chars.collect()
+        }
+        _ => {
+            let mut prev_whitespace = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '#' if prev_whitespace => break,
+                    '$' if chars.peek() == Some(&'{') => {
+                        out.push_str(&expand(&mut chars, resolve)?)
+                    }
+                    _ => out.push(c),
+                }
+                prev_whitespace = c.is_whitespace();
+            }
+            return Ok(out.trim_end().to_owned());
+        }
+    };
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(out)
+    } else {
+        Err(format!("error parsing value: unexpected characters after quotes: {rest}").into())
+    }
+}
+
+/// Expand `{NAME}`, the `$` already consumed. Undefined variables expand to nothing.
+pub(crate) fn expand(
+    chars: &mut impl Iterator<Item = char>,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, BoxError> {
+    chars.next();
+    let mut name = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(resolve(&name).unwrap_or_default());
+        }
+        name.push(c);
+    }
+    Err(format!("error parsing value: unterminated variable: ${{{name}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse_ok(text: &str) -> Vec<(String, String)> {
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/php".to_owned());
+        parse(text, &lookup)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_nested_variables() {
+        let text = r#"
+            BASE_DIR=${HOME}/app
+            export CACHE_DIR="${BASE_DIR}/cache"
+            LITERAL='${BASE_DIR}'
+            ESCAPED="\${BASE_DIR}"
+            UNBRACED=$BASE_DIR
+            MISSING=${NOPE}x
+        "#;
+        let expected: Vec<(String, String)> = [
+            ("BASE_DIR", "/home/php/app"),
+            ("CACHE_DIR", "/home/php/app/cache"),
+            ("LITERAL", "${BASE_DIR}"),
+            ("ESCAPED", "${BASE_DIR}"),
+            ("UNBRACED", "$BASE_DIR"),
+            ("MISSING", "x"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(parse_ok(text), expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let text = "A=b#c\nB=b #c\nC=\"quoted\" # comment\nD='x#y'";
+        let values: Vec<_> = parse_ok(text).into_iter().map(|(_, v)| v).collect();
+        assert_eq!(values, vec!["b#c", "b", "quoted", "x#y"]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let lookup = |_: &str| None;
+        for text in ["A=\"open", "A='open", "A=\"x\" y", "A=${OPEN"] {
+            assert!(parse(text, &lookup)[0].is_err(), "expected err: {text}");
+        }
+    }
+}
@@ -0,0 +1,696 @@
+//! Resolution happens in ordered phases: discover → parse → interpolate → validate → redact →
+//! export. Each phase has a built-in stage, and library users can add their own stages after
+//! it, e.g. to decrypt values with a company specific scheme:
+//!
+//! ```no_run
+//! use enw::pipeline::{Context, Phase, Pipeline};
+//!
+//! let pipeline = Pipeline::new().add(Phase::Interpolate, |ctx: &mut Context| {
+//!     for var in ctx.vars_mut() {
+//!         if let Some(encrypted) = var.value.strip_prefix("enc:") {
+//!             var.value = encrypted.chars().rev().collect();
+//!         }
+//!     }
+//!     Ok(())
+//! });
+//! std::process::exit(enw::run_with(std::env::args_os(), &pipeline).unwrap());
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    ffi::OsString,
+    fs, io, mem,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    assignments, crypt, danger, expiry, git, glob, http, lock, parse_env_doc, parse_env_file,
+    placeholder, profile, prompt, provider, reference,
+    schema::{self, Schema},
+    secret_file, split_assignment, trust, verify, BoxError, EnvFile, OptionsBuilder, Origin, Var,
+    DEFAULT_ENV_FILE_NAME,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Find the env files to load
+    Discover,
+    /// Read files, providers and command line into variables. `${VAR}` and `$(command)` are
+    /// expanded here, as what expands depends on the quoting and order of the assignments.
+    Parse,
+    /// Replace references like `@file:PATH` in values of env files with what they refer to
+    Interpolate,
+    /// Check the variables, failing the run if they are unfit
+    Validate,
+    /// Hide values that must not be shown
+    Redact,
+    /// Prepare the variables for handing over to the command
+    Export,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Discover,
+        Phase::Parse,
+        Phase::Interpolate,
+        Phase::Validate,
+        Phase::Redact,
+        Phase::Export,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Discover => "discover",
+            Phase::Parse => "parse",
+            Phase::Interpolate => "interpolate",
+            Phase::Validate => "validate",
+            Phase::Redact => "redact",
+            Phase::Export => "export",
+        }
+    }
+}
+
+pub trait Stage {
+    fn run(&self, ctx: &mut Context) -> Result<(), BoxError>;
+}
+
+impl<F> Stage for F
+where
+    F: Fn(&mut Context) -> Result<(), BoxError>,
+{
+    fn run(&self, ctx: &mut Context) -> Result<(), BoxError> {
+        self(ctx)
+    }
+}
+
+/// State passed through the stages
+#[derive(Debug)]
+pub struct Context {
+    pub(crate) opts: OptionsBuilder,
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) vars: Vec<Var>,
+    pub(crate) unset: Vec<OsString>,
+    pub(crate) warnings: Vec<String>,
+    /// How long each provider took to fetch, by spec
+    pub(crate) provider_timings: Vec<(String, Duration)>,
+    /// How long each phase took, custom stages included
+    pub(crate) phase_timings: Vec<(Phase, Duration)>,
+}
+
+impl Context {
+    pub(crate) fn new(opts: OptionsBuilder) -> Self {
+        Context {
+            opts,
+            files: Vec::new(),
+            vars: Vec::new(),
+            unset: Vec::new(),
+            warnings: Vec::new(),
+            provider_timings: Vec::new(),
+            phase_timings: Vec::new(),
+        }
+    }
+
+    /// The value the command gets for `key`, set by enw or inherited
+    pub(crate) fn value(&self, key: &str) -> Option<String> {
+        if self.opts.unset.iter().any(|unset| unset == key) {
+            return None;
+        }
+        if let Some(var) = self.vars.iter().rev().find(|var| var.key == key) {
+            return Some(var.value.clone());
+        }
+        let inherited = !self.opts.ignore_env
+            || self
+                .opts
+                .inherit_prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix.as_str()));
+        env::var(key).ok().filter(|_| inherited)
+    }
+
+    /// Env files to load, lowest precedence first
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut Vec<PathBuf> {
+        &mut self.files
+    }
+
+    /// Variables resolved so far, sorted by key once parsed
+    pub fn vars(&self) -> &[Var] {
+        &self.vars
+    }
+
+    pub fn vars_mut(&mut self) -> &mut Vec<Var> {
+        &mut self.vars
+    }
+
+    pub fn warn(&mut self, warning: impl Into<String>) {
+        self.warnings.push(warning.into());
+    }
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<(Phase, Box<dyn Stage>)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Run `stage` after the built-in stage of `phase`, and after stages added earlier
+    pub fn add(mut self, phase: Phase, stage: impl Stage + 'static) -> Self {
+        self.stages.push((phase, Box::new(stage)));
+        self
+    }
+
+    pub(crate) fn run(&self, ctx: &mut Context) -> Result<(), BoxError> {
+        for phase in Phase::ALL {
+            let started = Instant::now();
+            builtin(phase, ctx)?;
+            for (_, stage) in self.stages.iter().filter(|(p, _)| *p == phase) {
+                stage.run(ctx)?;
+            }
+            ctx.phase_timings.push((phase, started.elapsed()));
+        }
+        Ok(())
+    }
+}
+
+fn builtin(phase: Phase, ctx: &mut Context) -> Result<(), BoxError> {
+    match phase {
+        Phase::Discover => discover(ctx),
+        Phase::Parse => parse(ctx),
+        Phase::Interpolate => interpolate(ctx),
+        Phase::Redact => redact(ctx),
+        Phase::Validate => validate(ctx),
+        Phase::Export => export(ctx),
+    }
+}
+
+/// Resolve the references among the values env files set, see [`reference`]
+fn interpolate(ctx: &mut Context) -> Result<(), BoxError> {
+    for var in &mut ctx.vars {
+        if matches!(var.origin, Origin::File(_)) {
+            reference::resolve(var, &ctx.opts)?;
+        }
+    }
+    Ok(())
+}
+
+/// Mark the variables named by the configured secret patterns as secrets
+fn redact(ctx: &mut Context) -> Result<(), BoxError> {
+    let patterns = secret_patterns(&ctx.opts);
+    for var in &mut ctx.vars {
+        var.secret |= is_configured_secret(&patterns, &var.key);
+    }
+    Ok(())
+}
+
+/// The configured secret patterns, matched case-insensitively
+fn secret_patterns(opts: &OptionsBuilder) -> Vec<String> {
+    opts.secret_patterns
+        .iter()
+        .map(|pattern| pattern.to_ascii_uppercase())
+        .collect()
+}
+
+fn is_configured_secret(patterns: &[String], key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    patterns.iter().any(|pattern| glob::matches(pattern, &key))
+}
+
+fn discover(ctx: &mut Context) -> Result<(), BoxError> {
+    for EnvFile { path, is_default } in mem::take(&mut ctx.opts.env_files) {
+        if path.is_dir() {
+            let file_path = path.join(DEFAULT_ENV_FILE_NAME);
+            if file_path.is_file() {
+                ctx.files.push(file_path);
+            } else if !is_default {
+                ctx.warn(format!(
+                    "no {DEFAULT_ENV_FILE_NAME} file found in {}",
+                    path.to_string_lossy()
+                ));
+            }
+        } else if path.is_file() || http::is_url(&path) || is_stdin(&path) {
+            ctx.files.push(path);
+        } else if !is_default {
+            ctx.warn(format!("{} does not exist", path.to_string_lossy()));
+        }
+    }
+    Ok(())
+}
+
+fn parse(ctx: &mut Context) -> Result<(), BoxError> {
+    let opts = &ctx.opts;
+    let env_files: Vec<(PathBuf, Vec<u8>, String)> = ctx
+        .files
+        .iter()
+        .map(|path| read_env_file(path, opts).map(|(raw, text)| (path.clone(), raw, text)))
+        .collect::<Result<_, _>>()?;
+    if let Some(keys) = &opts.verify {
+        for (path, raw, _) in &env_files {
+            verify::verify(path, raw, keys)?;
+        }
+    }
+    let mut env_vars: HashMap<String, Var> = HashMap::new();
+    // Where the env files define each key, in the order they are read
+    let mut sites: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut dangerous = Vec::new();
+    let mut exposed = Vec::new();
+    // Redact applies them to every variable later, but the exposure check needs them now
+    let patterns = secret_patterns(opts);
+    for (path, raw, text) in &env_files {
+        let trusted = opts.dangerous_vars == danger::Policy::Allow || trust::is_trusted(path, raw);
+        let mut expiry_dates =
+            expiry::annotations(text).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        let mut secret_keys = Vec::new();
+        let mut lines: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (line, raw) in assignments(text) {
+            if let Some((key, _)) = split_assignment(raw) {
+                lines.entry(key).or_default().push(line + 1);
+            }
+        }
+        let mut defined = HashSet::new();
+        let rules =
+            schema::annotations(text).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        for entry in parse_env_file(path, text, opts, &env_vars) {
+            let (key, mut value) = entry?;
+            if let Some((line, rule)) = rules.get(&key) {
+                if let Some(problem) = rule.check(&key, Some(&value)) {
+                    return Err(format!("{}:{line}: {problem}", path.to_string_lossy()).into());
+                }
+                value = rule.ty.normalize(&value);
+            }
+            if !trusted && danger::is_dangerous(&key) {
+                dangerous.push(format!("{} sets {key}", path.to_string_lossy()));
+            }
+            let mut var = Var::new(key.clone(), value, Origin::File(path.clone()));
+            var.expires = expiry_dates.remove(&key);
+            // The last assignment of a key is the one that counts
+            var.line = lines
+                .get(key.as_str())
+                .and_then(|lines| lines.last().copied());
+            if defined.insert(key.clone()) {
+                let path = path.to_string_lossy();
+                let key_sites = sites.entry(key.clone()).or_default();
+                match lines.get(key.as_str()) {
+                    Some(lines) => {
+                        key_sites.extend(lines.iter().map(|line| format!("{path}:{line}")))
+                    }
+                    None => key_sites.push(path.into_owned()),
+                }
+            }
+            if var.is_secret() || is_configured_secret(&patterns, &key) {
+                secret_keys.push(key.clone());
+            }
+            env_vars.insert(key, var);
+        }
+        if secret_keys.is_empty() {
+            continue;
+        }
+        let secrets = secret_keys.join(", ");
+        // Committing encrypted files is what they are for, and fetched ones are not in git
+        let exposure = if crypt::is_encrypted(path) || http::is_url(path) || is_stdin(path) {
+            None
+        } else {
+            git::exposure(path)
+        };
+        let path = path.to_string_lossy();
+        match exposure {
+            Some(git::Exposure::Tracked) => exposed.push(format!(
+                "{path} holds secrets ({secrets}) and is tracked by git; rotate them, run \
+                 `git rm --cached {path}` and add it to .gitignore"
+            )),
+            Some(git::Exposure::NotIgnored) => exposed.push(format!(
+                "{path} holds secrets ({secrets}) and is not ignored by git; add it to .gitignore"
+            )),
+            None => {}
+        }
+    }
+    if opts.warn_duplicates {
+        let duplicates: Vec<String> = sites
+            .iter()
+            .filter(|(_, sites)| sites.len() > 1)
+            .map(|(key, sites)| format!("{key} is defined more than once: {}", sites.join(", ")))
+            .collect();
+        if opts.strict_duplicates && !duplicates.is_empty() {
+            return Err(duplicates.join("\n").into());
+        }
+        ctx.warnings.extend(duplicates);
+    }
+    if opts.strict_secrets && !exposed.is_empty() {
+        return Err(exposed.join("\n").into());
+    }
+    ctx.warnings.extend(exposed);
+    // Profile variables and providers declared by a cloned repository are as much of a risk as
+    // its env files, unless its .enw.toml is trusted
+    let config_trusted = opts.dangerous_vars == danger::Policy::Allow
+        || opts
+            .project_config
+            .as_ref()
+            .is_some_and(|path| fs::read(path).is_ok_and(|raw| trust::is_trusted(path, &raw)));
+    let config_name = || {
+        opts.project_config
+            .as_deref()
+            .unwrap_or(Path::new(profile::CONFIG_FILE_NAME))
+            .to_string_lossy()
+            .into_owned()
+    };
+    for spec in &opts.providers {
+        let started = Instant::now();
+        let declared = !config_trusted && opts.project_providers.contains(spec);
+        for var in provider::from_spec(spec, opts.print_warnings, opts.color)?.fetch()? {
+            if declared && danger::is_dangerous(&var.key) {
+                dangerous.push(format!(
+                    "{spec}, named in {}, sets {}",
+                    config_name(),
+                    var.key
+                ));
+            }
+            env_vars.insert(var.key.clone(), var);
+        }
+        ctx.provider_timings.push((spec.clone(), started.elapsed()));
+    }
+    if let Some(profile) = &opts.profile {
+        for (key, value) in &opts.profile_vars {
+            if !config_trusted && danger::is_dangerous(key) {
+                dangerous.push(format!("{} sets {key} in profile {profile}", config_name()));
+            }
+            let origin = Origin::Profile(profile.clone());
+            env_vars.insert(key.clone(), Var::new(key.clone(), value.clone(), origin));
+        }
+    }
+    if !dangerous.is_empty() {
+        let hint = "inspect the files and run `enw trust FILE` if they are safe";
+        if opts.dangerous_vars == danger::Policy::Deny {
+            return Err(format!(
+                "refusing to load dangerous variables:\n  {}\n{hint}",
+                dangerous.join("\n  ")
+            )
+            .into());
+        }
+        for entry in dangerous {
+            ctx.warnings
+                .push(format!("{entry}, which can hijack the command; {hint}"));
+        }
+    }
+    for (key, value) in &opts.vars {
+        env_vars.insert(
+            key.clone(),
+            Var::new(key.clone(), value.clone(), Origin::CommandLine),
+        );
+    }
+    ctx.vars = env_vars.into_values().collect();
+    // Sorted, so printing, exports and the command's environment come out the same on every
+    // run and machine
+    ctx.vars.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(())
+}
+
+/// Whether a `--file` is `-`, standing for stdin
+pub(crate) fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// All of stdin, read on first use and kept, since watch restarts and bench iterations resolve
+/// the environment again and stdin would be empty by then
+pub(crate) fn read_stdin() -> Result<&'static str, BoxError> {
+    static STDIN: OnceLock<Result<String, String>> = OnceLock::new();
+    STDIN
+        .get_or_init(|| io::read_to_string(io::stdin()).map_err(|e| format!("stdin: {e}")))
+        .as_deref()
+        .map_err(|e| e.clone().into())
+}
+
+/// The contents of an env file as read, and its text: read from stdin for `-`, fetched if it is
+/// a URL, and decrypted if it is encrypted
+fn read_env_file(path: &Path, opts: &OptionsBuilder) -> Result<(Vec<u8>, String), BoxError> {
+    if is_stdin(path) {
+        let text = read_stdin()?;
+        return Ok((text.as_bytes().to_vec(), text.to_owned()));
+    }
+    if http::is_url(path) {
+        let url = path.to_string_lossy();
+        let auth = opts
+            .file_auth
+            .as_ref()
+            .map(|token| format!("Bearer {token}"));
+        let headers: Vec<(&str, &str)> = auth
+            .iter()
+            .map(|auth| ("Authorization", auth.as_str()))
+            .collect();
+        let data = http::get_bytes_with(&url, &headers, &opts.remote)?;
+        let text = match crypt::is_encrypted(path) {
+            true => {
+                String::from_utf8(crypt::decrypt(path, &data).map_err(|e| format!("{url}: {e}"))?)?
+            }
+            false => String::from_utf8(data.clone()).map_err(|e| format!("{url}: {e}"))?,
+        };
+        return Ok((data, text));
+    }
+    let data = fs::read(path)?;
+    if !crypt::is_encrypted(path) {
+        let text = String::from_utf8(data.clone())
+            .map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        return Ok((data, text));
+    }
+    let plain =
+        crypt::decrypt(path, &data).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+    Ok((data, String::from_utf8(plain)?))
+}
+
+fn validate(ctx: &mut Context) -> Result<(), BoxError> {
+    let opts = &ctx.opts;
+    for key in opts.secret_via_file.iter().chain(&opts.secret_via_fd) {
+        if !ctx.vars.iter().any(|var| var.key == *key) {
+            return Err(format!("secret {key} is not defined").into());
+        }
+    }
+    let today = expiry::Date::today();
+    let expired: Vec<_> = ctx
+        .vars
+        .iter()
+        .filter_map(|var| Some((&var.key, var.expires.filter(|date| *date < today)?)))
+        .map(|(key, date)| format!("{key} expired on {date}, rotate it"))
+        .collect();
+    if opts.strict_expiry && !expired.is_empty() {
+        return Err(expired.join("\n").into());
+    }
+    if let Some(path) = &opts.frozen {
+        lock::check(path, &ctx.vars)?;
+    }
+    if (opts.export.is_some() || opts.command.is_some()) && !opts.allow_placeholders {
+        check_placeholders(&ctx.vars)?;
+    }
+    if opts.require_schema {
+        check_schema(ctx)?;
+    }
+    ctx.warnings.extend(expired);
+    Ok(())
+}
+
+/// Fail listing every variable that does not satisfy the schema
+pub(crate) fn check_schema(ctx: &Context) -> Result<(), BoxError> {
+    let schema = Schema::load(&env::current_dir()?)?.ok_or_else(|| {
+        format!(
+            "no {} or {} to check the variables against",
+            schema::FILE_NAME,
+            placeholder::EXAMPLE_FILE_NAME
+        )
+    })?;
+    let problems = schema.check(|key| ctx.value(key));
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let listing: String = problems.iter().map(|line| format!("\n  {line}")).collect();
+    Err(format!(
+        "variables do not match {}:{listing}",
+        schema.path.to_string_lossy()
+    )
+    .into())
+}
+
+fn check_placeholders(env_vars: &[Var]) -> Result<(), BoxError> {
+    let example = fs::read_to_string(placeholder::EXAMPLE_FILE_NAME)
+        .map(|text| parse_env_doc(&text).into_iter().flatten().collect())
+        .unwrap_or_default();
+    let found = placeholder::find(env_vars, &example);
+    if found.is_empty() {
+        return Ok(());
+    }
+    let listing: String = found
+        .iter()
+        .map(|(key, reason)| format!("\n  {key}: {reason}"))
+        .collect();
+    Err(format!(
+        "refusing to run, placeholder values found:{listing}\n\
+         fill them in, or pass --allow-placeholders to run anyway"
+    )
+    .into())
+}
+
+fn export(ctx: &mut Context) -> Result<(), BoxError> {
+    if let Some(prefix) = &ctx.opts.prefix {
+        let strip = ctx.opts.strip_prefix;
+        let given: Vec<String> = ctx
+            .vars
+            .iter()
+            .filter(|var| matches!(var.origin, Origin::CommandLine))
+            .map(|var| var.key.clone())
+            .collect();
+        ctx.vars.retain_mut(|var| {
+            // Variables given on the command line are meant for the command as they are, and
+            // win over those renamed
+            if matches!(var.origin, Origin::CommandLine) {
+                return true;
+            }
+            let Some(rest) = var.key.strip_prefix(prefix.as_str()) else {
+                return false;
+            };
+            if strip {
+                if rest.is_empty() || given.iter().any(|key| key == rest) {
+                    return false;
+                }
+                var.key = rest.to_owned();
+            }
+            true
+        });
+        ctx.vars.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+    if ctx.opts.no_override {
+        // Only what the command would inherit is kept
+        let inherited = |key: &str| {
+            env::var_os(key).is_some()
+                && (!ctx.opts.ignore_env
+                    || ctx
+                        .opts
+                        .inherit_prefixes
+                        .iter()
+                        .any(|prefix| key.starts_with(prefix.as_str())))
+        };
+        ctx.vars
+            .retain(|var| !matches!(var.origin, Origin::File(_)) || !inherited(&var.key));
+    }
+    for key in &ctx.opts.unset {
+        ctx.vars.retain(|var| var.key.as_str() != key);
+        ctx.unset.push(key.clone());
+    }
+    if ctx.opts.empty_is_unset {
+        let unset = &mut ctx.unset;
+        ctx.vars.retain(|var| {
+            if var.value.is_empty() {
+                unset.push(var.key.clone().into());
+            }
+            !var.value.is_empty()
+        });
+    }
+    if ctx.opts.shell {
+        let current_dir = env::current_dir()?;
+        let files: Vec<_> = ctx
+            .files
+            .iter()
+            .map(|path| current_dir.join(path))
+            .collect();
+        ctx.vars
+            .extend(prompt::vars(ctx.opts.profile.as_deref(), &files));
+    }
+    if ctx.opts.command.is_some() && ctx.opts.export.is_none() {
+        for var in ctx.vars.iter_mut() {
+            if ctx.opts.secret_via_file.contains(&var.key) {
+                let path = secret_file::write_file(&var.key, &var.value)?;
+                var.key.push_str("_FILE");
+                var.value = path.to_string_lossy().into_owned();
+            } else if ctx.opts.secret_via_fd.contains(&var.key) {
+                let fd = secret_file::write_pipe(&var.value)?;
+                var.key.push_str("_FD");
+                var.value = fd.to_string();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_stages_run_after_builtin_in_phase_order() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = |phase: Phase| {
+            let seen = Rc::clone(&seen);
+            move |ctx: &mut Context| {
+                seen.borrow_mut().push((phase, ctx.vars().len()));
+                Ok(())
+            }
+        };
+        let pipeline = Pipeline::new()
+            .add(Phase::Export, log(Phase::Export))
+            .add(Phase::Parse, |ctx: &mut Context| {
+                ctx.vars_mut()
+                    .push(Var::new("A".into(), "1".into(), Origin::CommandLine));
+                Ok(())
+            })
+            .add(Phase::Discover, log(Phase::Discover))
+            .add(Phase::Parse, log(Phase::Parse));
+        let mut ctx = Context::new(OptionsBuilder::default());
+        pipeline.run(&mut ctx).unwrap();
+        assert_eq!(
+            *seen.borrow(),
+            vec![(Phase::Discover, 0), (Phase::Parse, 1), (Phase::Export, 1)]
+        );
+    }
+
+    #[test]
+    fn test_interpolate_resolves_references_after_parse() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = |seen: &Rc<RefCell<Vec<String>>>| {
+            let seen = Rc::clone(seen);
+            move |ctx: &mut Context| {
+                seen.borrow_mut().push(ctx.vars()[0].value.clone());
+                Ok(())
+            }
+        };
+        let pipeline = Pipeline::new()
+            .add(Phase::Parse, |ctx: &mut Context| {
+                let origin = Origin::File(PathBuf::from(".env"));
+                let value = "@file:tests/data/conf.d/10-base.env".into();
+                ctx.vars_mut().push(Var::new("A".into(), value, origin));
+                Ok(())
+            })
+            .add(Phase::Parse, log(&seen))
+            .add(Phase::Interpolate, log(&seen));
+        let mut ctx = Context::new(OptionsBuilder {
+            allow_file_refs: true,
+            ..Default::default()
+        });
+        pipeline.run(&mut ctx).unwrap();
+        let secret = fs::read_to_string("tests/data/conf.d/10-base.env").unwrap();
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                "@file:tests/data/conf.d/10-base.env".to_owned(),
+                secret.trim().to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failing_stage_stops_pipeline() {
+        let pipeline = Pipeline::new()
+            .add(Phase::Validate, |_: &mut Context| Err("nope".into()))
+            .add(Phase::Redact, |_: &mut Context| panic!("ran after failure"));
+        let mut ctx = Context::new(OptionsBuilder::default());
+        assert_eq!(pipeline.run(&mut ctx).unwrap_err().to_string(), "nope");
+    }
+}
@@ -0,0 +1,86 @@
+//! Detection of values that were never filled in: well known placeholder spellings, and
+//! secrets left at the value from `.env.example`.
+
+use std::collections::HashMap;
+
+use crate::{glob, Var};
+
+pub const EXAMPLE_FILE_NAME: &str = ".env.example";
+
+/// Matched case insensitively against the whole value
+const PLACEHOLDER_PATTERNS: &[&str] = &[
+    "CHANGEME",
+    "CHANGE_ME",
+    "CHANGE-ME",
+    "CHANGE ME",
+    "REPLACEME",
+    "REPLACE_ME",
+    "REPLACE-ME",
+    "TODO",
+    "FIXME",
+    "TBD",
+    "<*>",
+    "YOUR*HERE",
+    "*CHANGE*THIS*",
+    "XXX",
+    "XXXX*",
+];
+
+/// List variables with placeholder values, together with the reason they were flagged
+pub fn find(vars: &[Var], example: &HashMap<String, String>) -> Vec<(String, String)> {
+    vars.iter()
+        .filter_map(|var| {
+            let value = var.value.to_ascii_uppercase();
+            if let Some(pattern) = PLACEHOLDER_PATTERNS
+                .iter()
+                .find(|pattern| glob::matches(pattern, &value))
+            {
+                Some((
+                    var.key.clone(),
+                    format!("matches placeholder pattern {pattern}"),
+                ))
+            } else if var.is_secret()
+                && !var.value.is_empty()
+                && example.get(&var.key) == Some(&var.value)
+            {
+                Some((
+                    var.key.clone(),
+                    format!("secret copied from {EXAMPLE_FILE_NAME}"),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    #[test]
+    fn test_find() {
+        let vars: Vec<_> = [
+            ("A", "changeme"),
+            ("B", "<your-key-here>"),
+            ("C", "your_api_key_here"),
+            ("D", "real value"),
+            ("API_TOKEN", "abc123"),
+            ("HOST", "localhost"),
+            ("E", "xxxxxx"),
+            ("F", "xx"),
+        ]
+        .iter()
+        .map(|(k, v)| Var::new(k.to_string(), v.to_string(), Origin::CommandLine))
+        .collect();
+        let example = [("API_TOKEN", "abc123"), ("HOST", "localhost")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let flagged: Vec<_> = find(&vars, &example).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(flagged, vec!["A", "B", "C", "API_TOKEN", "E"]);
+    }
+}
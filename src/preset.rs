@@ -0,0 +1,110 @@
+//! Framework presets, mirroring which files each ecosystem's own dotenv loader reads and in
+//! which order. Later files take precedence over earlier ones.
+
+use std::str::FromStr;
+
+use crate::BoxError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Flask via python-dotenv: `.flaskenv`, overridden by `.env`
+    Flask,
+    /// dotenv-rails, environment taken from `RAILS_ENV` or `RACK_ENV`
+    Rails,
+    /// dotenv-flow and Vite, environment taken from `NODE_ENV`
+    Node,
+    /// Next.js, environment taken from `NODE_ENV`, ordered like dotenv-rails
+    Next,
+    /// django-environ reads a single `.env`
+    Django,
+}
+
+impl Preset {
+    pub const NAMES: &'static [&'static str] = &["flask", "rails", "node", "next", "django"];
+
+    /// File names to load, lowest precedence first. `var` looks up the inherited environment.
+    pub fn file_names(self, var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+        match (self, self.environment(var)) {
+            (Preset::Flask, _) => vec![".flaskenv".to_owned(), ".env".to_owned()],
+            (Preset::Rails | Preset::Next, Some(env)) => layered(&env, true),
+            (_, Some(env)) => layered(&env, false),
+            (_, None) => vec![".env".to_owned()],
+        }
+    }
+
+    /// The environment the files are picked for, for the presets that layer them by one
+    pub fn environment(self, var: impl Fn(&str) -> Option<String>) -> Option<String> {
+        let env = match self {
+            Preset::Flask | Preset::Django => return None,
+            Preset::Rails => var("RAILS_ENV").or_else(|| var("RACK_ENV")),
+            Preset::Node | Preset::Next => var("NODE_ENV"),
+        };
+        Some(env.unwrap_or_else(|| "development".to_owned()))
+    }
+}
+
+/// `.env.local` overrides `.env.<env>` when `local_first`, as in dotenv-rails and Next.js, and
+/// is overridden by it otherwise, as in dotenv-flow. It is skipped in the test environment so
+/// that tests give the same results for everyone.
+fn layered(env: &str, local_first: bool) -> Vec<String> {
+    let mut files = vec![".env".to_owned(), format!(".env.{env}")];
+    if env != "test" {
+        files.insert(if local_first { 2 } else { 1 }, ".env.local".to_owned());
+    }
+    files.push(format!(".env.{env}.local"));
+    files
+}
+
+impl FromStr for Preset {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flask" => Ok(Preset::Flask),
+            "rails" => Ok(Preset::Rails),
+            "node" => Ok(Preset::Node),
+            "next" => Ok(Preset::Next),
+            "django" => Ok(Preset::Django),
+            _ => Err(format!("unknown preset: {s}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_file_names() {
+        let none = |_: &str| None;
+        assert_eq!(Preset::Flask.file_names(none), vec![".flaskenv", ".env"]);
+        assert_eq!(
+            Preset::Node.file_names(none),
+            vec![
+                ".env",
+                ".env.local",
+                ".env.development",
+                ".env.development.local"
+            ]
+        );
+        assert_eq!(
+            Preset::Rails.file_names(none),
+            vec![
+                ".env",
+                ".env.development",
+                ".env.local",
+                ".env.development.local"
+            ]
+        );
+        assert_eq!(
+            Preset::Next.file_names(none),
+            Preset::Rails.file_names(none)
+        );
+        assert_eq!(
+            Preset::Rails.file_names(|k| (k == "RACK_ENV").then(|| "test".to_owned())),
+            vec![".env", ".env.test", ".env.test.local"]
+        );
+    }
+}
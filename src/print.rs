@@ -0,0 +1,113 @@
+//! `enw --print` writes the environment the command would get, instead of running one.
+
+use std::{collections::BTreeMap, ffi::OsString, os::unix::ffi::OsStrExt, str::FromStr};
+
+use crate::{json, shell, BoxError};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// `KEY=VALUE` lines, like `env` prints them
+    #[default]
+    Env,
+    /// `export KEY=VALUE` statements for POSIX shells
+    Export,
+    /// A JSON object
+    Json,
+    /// `KEY=VALUE` entries terminated by NUL, like `env -0` prints them
+    Null,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["env", "export", "json", "null"];
+
+    /// Whether the output is meant for people, and secrets are masked in it rather than refused
+    pub fn is_for_people(self) -> bool {
+        self == Format::Env
+    }
+}
+
+impl FromStr for Format {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "env" => Ok(Format::Env),
+            "export" => Ok(Format::Export),
+            "json" => Ok(Format::Json),
+            "null" => Ok(Format::Null),
+            _ => Err(format!("unknown print format: {s}").into()),
+        }
+    }
+}
+
+/// Render the environment. `env` and `null` keep the bytes as they are, the other formats
+/// replace invalid UTF-8.
+pub fn render(format: Format, env: &BTreeMap<OsString, OsString>) -> Vec<u8> {
+    let mut out = Vec::new();
+    match format {
+        Format::Env | Format::Null => {
+            let terminator = if format == Format::Null { b'\0' } else { b'\n' };
+            for (key, value) in env {
+                out.extend_from_slice(key.as_bytes());
+                out.push(b'=');
+                out.extend_from_slice(value.as_bytes());
+                out.push(terminator);
+            }
+        }
+        Format::Export => {
+            for (key, value) in env {
+                let line = format!(
+                    "export {}={}\n",
+                    key.to_string_lossy(),
+                    shell::quote(&value.to_string_lossy())
+                );
+                out.extend_from_slice(line.as_bytes());
+            }
+        }
+        Format::Json => {
+            let entries: Vec<String> = env
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "  {}: {}",
+                        json::quote(&key.to_string_lossy()),
+                        json::quote(&value.to_string_lossy())
+                    )
+                })
+                .collect();
+            let body = if entries.is_empty() {
+                "{}\n".to_owned()
+            } else {
+                format!("{{\n{}\n}}\n", entries.join(",\n"))
+            };
+            out.extend_from_slice(body.as_bytes());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let env: BTreeMap<OsString, OsString> = [("A", "1"), ("B", "it's \"x\"")]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        let render = |format| String::from_utf8(render(format, &env)).unwrap();
+        assert_eq!(render(Format::Env), "A=1\nB=it's \"x\"\n");
+        assert_eq!(render(Format::Null), "A=1\0B=it's \"x\"\0");
+        assert_eq!(
+            render(Format::Export),
+            "export A=1\nexport B='it'\\''s \"x\"'\n"
+        );
+        assert_eq!(
+            render(Format::Json),
+            "{\n  \"A\": \"1\",\n  \"B\": \"it's \\\"x\\\"\"\n}\n"
+        );
+    }
+}
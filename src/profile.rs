@@ -0,0 +1,202 @@
+//! Profiles, selected with `--profile NAME` or `ENW_PROFILE`. Like dotenv-flow and Vite do,
+//! a profile overlays `.env.NAME` and `.env.NAME.local` on each `.env` file, in that order of
+//! increasing precedence.
+//!
+//! A profile can also be declared in the project's `.enw.toml`, naming more env files to load
+//! and variables to set. It may extend another profile, whose files and variables come first
+//! and are overridden by its own:
+//!
+//! ```toml
+//! [profiles.base]
+//! files = [".env.shared"]
+//!
+//! [profiles.dev]
+//! extends = "base"
+//! files = [".env.dev"]
+//! vars.LOG_LEVEL = "debug"
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{toml, BoxError, DEFAULT_ENV_FILE_NAME};
+
+pub const CONFIG_FILE_NAME: &str = ".enw.toml";
+
+/// A profile with its ancestors' files and variables merged in
+#[derive(Debug, Default, PartialEq)]
+pub struct Profile {
+    /// The profile and the ones it extends, most derived first
+    pub chain: Vec<String>,
+    /// Paths relative to the config file, lowest precedence first
+    pub files: Vec<String>,
+    /// Later entries override earlier ones with the same key
+    pub vars: Vec<(String, String)>,
+}
+
+/// The implicitly loaded env files of `dirs`, lowest precedence first: per directory `.env`,
+/// then the profile's overlays
+pub fn env_files(dirs: &[PathBuf], profile: Option<&str>) -> Vec<PathBuf> {
+    let mut names = vec![DEFAULT_ENV_FILE_NAME.to_owned()];
+    names.extend(profile.into_iter().flat_map(overlays));
+    dirs.iter()
+        .flat_map(|dir| names.iter().map(|name| dir.join(name)))
+        .collect()
+}
+
+/// The names of the files the profile overlays on `.env`
+pub fn overlays(name: &str) -> [String; 2] {
+    [
+        format!("{DEFAULT_ENV_FILE_NAME}.{name}"),
+        format!("{DEFAULT_ENV_FILE_NAME}.{name}.local"),
+    ]
+}
+
+pub fn check_name(name: &str) -> Result<(), BoxError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid profile name: {name:?}").into());
+    }
+    Ok(())
+}
+
+/// Load profile `name` from the `.enw.toml` in `dir`. None when it is not declared there.
+pub fn load(dir: &Path, name: &str) -> Result<Option<Profile>, BoxError> {
+    let path = dir.join(CONFIG_FILE_NAME);
+    let text = match fs::read_to_string(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        text => text?,
+    };
+    let doc = toml::parse(&text).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+    if !doc.contains_key(&format!("profiles.{name}")) {
+        return Ok(None);
+    }
+    flatten(&doc, name).map(Some)
+}
+
+fn flatten(doc: &toml::Document, name: &str) -> Result<Profile, BoxError> {
+    let mut chain = vec![name.to_owned()];
+    let mut current = name.to_owned();
+    loop {
+        let table = doc
+            .get(&format!("profiles.{current}"))
+            .ok_or_else(|| format!("profile {current} is not defined in {CONFIG_FILE_NAME}"))?;
+        let Some(parent) = table.get("extends") else {
+            break;
+        };
+        let parent = parent
+            .as_str()
+            .ok_or_else(|| format!("profile {current}: extends must be a string"))?;
+        if chain.iter().any(|seen| seen == parent) {
+            chain.push(parent.to_owned());
+            return Err(format!("profile inheritance cycle: {}", chain.join(" -> ")).into());
+        }
+        chain.push(parent.to_owned());
+        current = parent.to_owned();
+    }
+    let mut profile = Profile::default();
+    // Base first, so that derived profiles override it
+    for name in chain.iter().rev() {
+        let table = &doc[&format!("profiles.{name}")];
+        if let Some(files) = table.get("files") {
+            profile.files.extend(
+                files
+                    .as_strings()
+                    .ok_or_else(|| format!("profile {name}: files must be a list of strings"))?,
+            );
+        }
+        for (key, value) in doc
+            .get(&format!("profiles.{name}.vars"))
+            .into_iter()
+            .flatten()
+        {
+            let value = value
+                .as_str()
+                .ok_or_else(|| format!("profile {name}: {key} must be a string"))?;
+            profile.vars.push((key.clone(), value.to_owned()));
+        }
+    }
+    profile.chain = chain;
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+[profiles.base]
+files = [".env.shared"]
+vars.LOG_LEVEL = "info"
+vars.REGION = "eu"
+
+[profiles.dev]
+extends = "base"
+files = [".env.dev"]
+vars.LOG_LEVEL = "debug"
+
+[profiles.a]
+extends = "b"
+
+[profiles.b]
+extends = "c"
+
+[profiles.c]
+extends = "a"
+"#;
+
+    #[test]
+    fn test_flatten() {
+        let doc = toml::parse(CONFIG).unwrap();
+        assert_eq!(
+            flatten(&doc, "dev").unwrap(),
+            Profile {
+                chain: vec!["dev".into(), "base".into()],
+                files: vec![".env.shared".into(), ".env.dev".into()],
+                vars: vec![
+                    ("LOG_LEVEL".into(), "info".into()),
+                    ("REGION".into(), "eu".into()),
+                    ("LOG_LEVEL".into(), "debug".into()),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_env_files() {
+        let dirs = [PathBuf::from("/repo"), PathBuf::from("/repo/app")];
+        assert_eq!(
+            env_files(&dirs, None),
+            vec![PathBuf::from("/repo/.env"), PathBuf::from("/repo/app/.env")]
+        );
+        assert_eq!(
+            env_files(&dirs, Some("test")),
+            vec![
+                PathBuf::from("/repo/.env"),
+                PathBuf::from("/repo/.env.test"),
+                PathBuf::from("/repo/.env.test.local"),
+                PathBuf::from("/repo/app/.env"),
+                PathBuf::from("/repo/app/.env.test"),
+                PathBuf::from("/repo/app/.env.test.local"),
+            ]
+        );
+        assert!(check_name("staging").is_ok());
+        assert!(check_name("../secrets").is_err());
+    }
+
+    #[test]
+    fn test_flatten_errors() {
+        let doc = toml::parse(CONFIG).unwrap();
+        assert_eq!(
+            flatten(&doc, "a").unwrap_err().to_string(),
+            "profile inheritance cycle: a -> b -> c -> a"
+        );
+        assert_eq!(
+            flatten(&doc, "prod").unwrap_err().to_string(),
+            "profile prod is not defined in .enw.toml"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Variables telling shell prompts which environment is loaded, and `enw prompt` to format them.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Origin, Var};
+
+/// Set to `1` in shells started by `enw shell`
+pub const ACTIVE: &str = "ENW_ACTIVE";
+/// The environment picked by `--preset`, when it has one
+pub const PROFILE: &str = "ENW_PROFILE";
+/// Colon separated absolute paths of the loaded env files
+pub const FILES: &str = "ENW_FILES";
+
+pub const DEFAULT_FORMAT: &str = "enw:{name}";
+
+/// The variables to hand to the shell
+pub fn vars(profile: Option<&str>, files: &[PathBuf]) -> Vec<Var> {
+    let files: Vec<_> = files.iter().map(|path| path.to_string_lossy()).collect();
+    let mut vars = vec![
+        Var::new(ACTIVE.to_owned(), "1".to_owned(), Origin::CommandLine),
+        Var::new(FILES.to_owned(), files.join(":"), Origin::CommandLine),
+    ];
+    if let Some(profile) = profile {
+        vars.push(Var::new(
+            PROFILE.to_owned(),
+            profile.to_owned(),
+            Origin::CommandLine,
+        ));
+    }
+    vars
+}
+
+/// Expand `{profile}`, `{files}` (file names, comma separated) and `{name}` (the profile, or
+/// else the file names) in `format`. None when no environment is active, so that prompts print
+/// nothing.
+pub fn format(format: &str, var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    if var(ACTIVE).as_deref() != Some("1") {
+        return None;
+    }
+    let profile = var(PROFILE).unwrap_or_default();
+    let files: Vec<_> = var(FILES)
+        .unwrap_or_default()
+        .split(':')
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            Path::new(path).file_name().map_or_else(
+                || path.to_owned(),
+                |name| name.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+    let files = files.join(",");
+    let name = if profile.is_empty() { &files } else { &profile };
+    Some(
+        format
+            .replace("{name}", name)
+            .replace("{profile}", &profile)
+            .replace("{files}", &files),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn render(fmt: &str, env: &[(&str, &str)]) -> Option<String> {
+        let env: HashMap<_, _> = env.iter().copied().collect();
+        format(fmt, |key| env.get(key).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(render(DEFAULT_FORMAT, &[]), None);
+        let files = (FILES, "/app/.env:/app/.env.local");
+        assert_eq!(
+            render(DEFAULT_FORMAT, &[(ACTIVE, "1"), files]),
+            Some("enw:.env,.env.local".to_owned())
+        );
+        assert_eq!(
+            render(DEFAULT_FORMAT, &[(ACTIVE, "1"), files, (PROFILE, "test")]),
+            Some("enw:test".to_owned())
+        );
+        assert_eq!(
+            render("[{profile}|{files}]", &[(ACTIVE, "1"), (FILES, "")]),
+            Some("[|]".to_owned())
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Java `.properties` files as an env source, as specified by `java.util.Properties.load`.
+
+use crate::{key_is_valid, BoxError};
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Upcase keys and turn dots and dashes into underscores: `db.url` becomes `DB_URL`
+    pub normalize_keys: bool,
+}
+
+pub fn parse(text: &str, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    logical_lines(text)
+        .into_iter()
+        .filter(|line| !line.is_empty() && !line.starts_with(['#', '!']))
+        .map(|line| {
+            let (key, value) = split_key_value(&line);
+            let mut key = unescape(&key)?;
+            if opts.normalize_keys {
+                key = key.to_uppercase().replace(['.', '-'], "_");
+            }
+            if !key_is_valid(&key) {
+                return Err(format!("KEY contains invalid characters: {key}").into());
+            }
+            Ok((key, unescape(value)?))
+        })
+        .collect()
+}
+
+/// Join continuation lines, which end in an odd number of backslashes, and strip leading
+/// whitespace from every physical line.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut continued = false;
+    for line in text.lines() {
+        let line = line.trim_start();
+        if !continued && line.starts_with(['#', '!']) {
+            lines.push(line.to_owned());
+            continue;
+        }
+        let trailing_backslashes = line.chars().rev().take_while(|&c| c == '\\').count();
+        if trailing_backslashes % 2 == 1 {
+            current.push_str(&line[..line.len() - 1]);
+            continued = true;
+        } else {
+            current.push_str(line);
+            lines.push(std::mem::take(&mut current));
+            continued = false;
+        }
+    }
+    if continued {
+        lines.push(current);
+    }
+    lines
+}
+
+/// The key ends at the first unescaped `=`, `:` or whitespace. Whitespace around the separator
+/// is skipped.
+fn split_key_value(line: &str) -> (String, &str) {
+    let mut key = String::new();
+    let mut chars = line.char_indices();
+    let mut rest = "";
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                key.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    key.push(escaped);
+                }
+            }
+            '=' | ':' => {
+                rest = line[i + 1..].trim_start();
+                break;
+            }
+            c if c.is_whitespace() => {
+                rest = line[i..].trim_start();
+                if let Some(after) = rest.strip_prefix(['=', ':']) {
+                    rest = after.trim_start();
+                }
+                break;
+            }
+            _ => key.push(c),
+        }
+    }
+    (key, rest)
+}
+
+fn unescape(s: &str) -> Result<String, BoxError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\u{c}'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid unicode escape: \\u{hex}"))?;
+                out.push(
+                    char::from_u32(code)
+                        .ok_or_else(|| format!("invalid unicode escape: \\u{hex}"))?,
+                );
+            }
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse_ok(text: &str, normalize_keys: bool) -> Vec<(String, String)> {
+        parse(text, &Options { normalize_keys })
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn owned(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_separators_and_escapes() {
+        let text = r"
+            # comment
+            ! also a comment
+            db.url = jdbc:postgresql://localhost/db
+            db.user:admin
+            greeting   Hello\tWorld
+            name=J\u00fcrgen
+            path=C:\\Temp
+        ";
+        assert_eq!(
+            parse_ok(text, false),
+            owned(&[
+                ("db.url", "jdbc:postgresql://localhost/db"),
+                ("db.user", "admin"),
+                ("greeting", "Hello\tWorld"),
+                ("name", "Jürgen"),
+                ("path", r"C:\Temp"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let text = "fruits = apple, \\\n    banana, \\\n    pear\nnext=1\n";
+        assert_eq!(
+            parse_ok(text, false),
+            owned(&[("fruits", "apple, banana, pear"), ("next", "1")])
+        );
+    }
+
+    #[test]
+    fn test_normalize_keys() {
+        assert_eq!(
+            parse_ok("db.max-connections=10", true),
+            owned(&[("DB_MAX_CONNECTIONS", "10")])
+        );
+    }
+}
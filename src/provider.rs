@@ -0,0 +1,34 @@
+//! Providers pull environment variables from remote services. They are selected with
+//! `--from NAME:SPEC`, e.g. `--from vercel:my-project/production` or
+//! `--from vault://secret/data/myapp`, optionally followed by HTTP options such as `;proxy=URL`,
+//! see [`http::Options`]. Their variables override those of env files.
+
+use crate::{color, http, BoxError, Var};
+
+pub mod aws;
+mod vault;
+mod vercel;
+
+pub trait Provider {
+    fn fetch(&self) -> Result<Vec<Var>, BoxError>;
+}
+
+/// `print_warnings` and `color` are those of the command line, for warnings about retries
+pub fn from_spec(
+    spec: &str,
+    print_warnings: bool,
+    color: color::Choice,
+) -> Result<Box<dyn Provider>, BoxError> {
+    let (name, rest) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("invalid provider spec, expected NAME:SPEC: {spec}"))?;
+    let (rest, mut http) = http::Options::split_spec(rest)?;
+    http.print_warnings = print_warnings;
+    http.color = color;
+    match name {
+        "aws-ssm" => Ok(Box::new(aws::Ssm::with_spec(rest)?)),
+        "vault" => Ok(Box::new(vault::Vault::with_spec(rest, http)?)),
+        "vercel" => Ok(Box::new(vercel::Vercel::with_spec(rest, http)?)),
+        _ => Err(format!("unknown provider: {name}").into()),
+    }
+}
@@ -0,0 +1,168 @@
+//! AWS Systems Manager Parameter Store and Secrets Manager, reached through the `aws` CLI so
+//! that its credential chain, profiles and region settings apply as usual.
+
+use std::process::Command;
+
+use super::Provider;
+use crate::{json, BoxError, Origin, Var};
+
+/// The parameters under a path, named by the rest of their name with `/` replaced by `_`
+#[derive(Debug)]
+pub struct Ssm {
+    path: String,
+}
+
+impl Ssm {
+    pub fn with_spec(spec: &str) -> Result<Self, BoxError> {
+        if spec.is_empty() {
+            return Err("aws-ssm: path missing".into());
+        }
+        let path = format!("/{}/", spec.trim_matches('/'));
+        Ok(Ssm {
+            path: path.replace("//", "/"),
+        })
+    }
+}
+
+impl Provider for Ssm {
+    fn fetch(&self) -> Result<Vec<Var>, BoxError> {
+        let doc = aws(&[
+            "ssm",
+            "get-parameters-by-path",
+            "--path",
+            &self.path,
+            "--recursive",
+            "--with-decryption",
+        ])?;
+        parse_parameters(&doc, &self.path)
+    }
+}
+
+fn parse_parameters(doc: &json::Value, path: &str) -> Result<Vec<Var>, BoxError> {
+    let parameters = doc
+        .get("Parameters")
+        .and_then(json::Value::as_array)
+        .ok_or("aws-ssm: unexpected response, Parameters missing")?;
+    Ok(parameters
+        .iter()
+        .filter_map(|parameter| {
+            let name = parameter.get("Name")?.as_str()?;
+            let key = name.strip_prefix(path).unwrap_or(name).replace('/', "_");
+            let value = parameter.get("Value")?.as_str()?;
+            // Only encrypted parameters are secrets, and their ARN tells ECS where to find them
+            let reference = match parameter.get("Type")?.as_str()? {
+                "SecureString" => parameter.get("ARN").and_then(json::Value::as_str),
+                _ => None,
+            };
+            let origin = Origin::Provider {
+                name: "aws-ssm".to_owned(),
+                reference: reference.map(str::to_owned),
+            };
+            Some(Var::new(key, value.to_owned(), origin))
+        })
+        .collect())
+}
+
+/// The secret a `secretsmanager://NAME[#FIELD]` reference names, or one field of it when the
+/// secret is a JSON object, and where it is stored
+pub fn secret(reference: &str) -> Result<(String, Origin), BoxError> {
+    let (name, field) = match reference.split_once('#') {
+        Some((name, field)) => (name, Some(field)),
+        None => (reference, None),
+    };
+    let doc = aws(&["secretsmanager", "get-secret-value", "--secret-id", name])?;
+    let (value, arn) = parse_secret(&doc, field)?;
+    let origin = Origin::Provider {
+        name: "secretsmanager".to_owned(),
+        reference: arn,
+    };
+    Ok((value, origin))
+}
+
+/// The secret's string, or one field of it, and its ARN, in the `ARN:FIELD::` form ECS takes
+/// for fields
+fn parse_secret(
+    doc: &json::Value,
+    field: Option<&str>,
+) -> Result<(String, Option<String>), BoxError> {
+    let secret = doc
+        .get("SecretString")
+        .and_then(json::Value::as_str)
+        .ok_or("secretsmanager: only string secrets are supported")?;
+    let arn = doc
+        .get("ARN")
+        .and_then(json::Value::as_str)
+        .map(str::to_owned);
+    let Some(field) = field else {
+        return Ok((secret.to_owned(), arn));
+    };
+    let value = match json::parse(secret)?.get(field) {
+        Some(json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+        None => return Err(format!("secretsmanager: the secret has no field {field}").into()),
+    };
+    Ok((value, arn.map(|arn| format!("{arn}:{field}::"))))
+}
+
+fn aws(args: &[&str]) -> Result<json::Value, BoxError> {
+    let output = Command::new("aws")
+        .args(args)
+        .args(["--output", "json"])
+        .output()
+        .map_err(|e| format!("could not run aws: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("aws: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    json::parse(&String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_parameters() {
+        let doc = json::parse(
+            r#"{"Parameters": [
+                {"Name": "/app/prod/DB_URL", "Type": "String", "Value": "postgres://db",
+                 "ARN": "arn:aws:ssm:eu-west-1:1:parameter/app/prod/DB_URL"},
+                {"Name": "/app/prod/api/TOKEN", "Type": "SecureString", "Value": "abc",
+                 "ARN": "arn:aws:ssm:eu-west-1:1:parameter/app/prod/api/TOKEN"}
+            ]}"#,
+        )
+        .unwrap();
+        let ssm = Ssm::with_spec("app/prod").unwrap();
+        assert_eq!(ssm.path, "/app/prod/");
+        let vars = parse_parameters(&doc, &ssm.path).unwrap();
+        let pairs: Vec<_> = vars
+            .iter()
+            .map(|var| (var.key.as_str(), var.value.as_str(), var.is_secret()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("DB_URL", "postgres://db", false),
+                ("api_TOKEN", "abc", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_secret() {
+        let doc = json::parse(
+            r#"{"ARN": "arn:aws:secretsmanager:eu-west-1:1:secret:db",
+                "SecretString": "{\"password\": \"hunter2\", \"port\": 5432}"}"#,
+        )
+        .unwrap();
+        let arn = "arn:aws:secretsmanager:eu-west-1:1:secret:db";
+        assert_eq!(
+            parse_secret(&doc, Some("password")).unwrap(),
+            ("hunter2".to_owned(), Some(format!("{arn}:password::")))
+        );
+        assert_eq!(parse_secret(&doc, Some("port")).unwrap().0, "5432");
+        assert_eq!(parse_secret(&doc, None).unwrap().1.as_deref(), Some(arn));
+        assert!(parse_secret(&doc, Some("user")).is_err());
+    }
+}
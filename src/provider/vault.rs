@@ -0,0 +1,121 @@
+//! Secrets from HashiCorp Vault's KV engine, version 1 or 2. Talks to `VAULT_ADDR` with
+//! `VAULT_TOKEN`, or the token stored by `enw login vault`, in `VAULT_NAMESPACE` when set.
+
+use std::env;
+
+use super::Provider;
+use crate::{credentials, http, json, BoxError, Origin, Var};
+
+#[derive(Debug)]
+pub struct Vault {
+    path: String,
+    key: Option<String>,
+    http: http::Options,
+}
+
+impl Vault {
+    /// Spec is `PATH[#KEY]` as given to the HTTP API, e.g. `secret/data/myapp` for KV version 2.
+    /// With a key, only that field of the secret is loaded.
+    pub fn with_spec(spec: &str, http: http::Options) -> Result<Self, BoxError> {
+        let (path, key) = match spec.split_once('#') {
+            Some((path, key)) => (path, Some(key.to_owned())),
+            None => (spec, None),
+        };
+        let path = path.trim_start_matches("//").trim_matches('/');
+        if path.is_empty() {
+            return Err("vault: path missing".into());
+        }
+        Ok(Vault {
+            path: path.to_owned(),
+            key,
+            http,
+        })
+    }
+}
+
+impl Provider for Vault {
+    fn fetch(&self) -> Result<Vec<Var>, BoxError> {
+        let addr = env::var("VAULT_ADDR").map_err(|_| "vault: VAULT_ADDR is not set")?;
+        let token = credentials::token("vault", "VAULT_TOKEN")?;
+        let url = format!("{}/v1/{}", addr.trim_end_matches('/'), self.path);
+        let namespace = env::var("VAULT_NAMESPACE").ok();
+        let mut headers = vec![("X-Vault-Token", token.as_str())];
+        if let Some(namespace) = &namespace {
+            headers.push(("X-Vault-Namespace", namespace));
+        }
+        let body = http::get_with(&url, &headers, &self.http)?;
+        let mut fields = parse_secret(&body)?;
+        if let Some(key) = &self.key {
+            fields.retain(|(name, _)| name == key);
+            if fields.is_empty() {
+                return Err(format!("vault: {} has no field {key}", self.path).into());
+            }
+        }
+        Ok(fields
+            .into_iter()
+            .map(|(key, value)| {
+                let origin = Origin::Provider {
+                    name: "vault".to_owned(),
+                    reference: Some(format!("{}#{key}", self.path)),
+                };
+                Var::new(key, value, origin)
+            })
+            .collect())
+    }
+}
+
+/// The fields of a secret. KV version 2 nests them in `data.data`, next to `data.metadata`.
+fn parse_secret(body: &str) -> Result<Vec<(String, String)>, BoxError> {
+    let doc = json::parse(body)?;
+    let data = doc
+        .get("data")
+        .ok_or("vault: unexpected response, data missing")?;
+    let fields = match (data.get("data"), data.get("metadata")) {
+        (Some(fields), Some(_)) => fields,
+        _ => data,
+    };
+    let json::Value::Object(fields) = fields else {
+        return Err("vault: unexpected response, data is not an object".into());
+    };
+    Ok(fields
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                json::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            (key.clone(), value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_secret() {
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        let v2 = r#"{"data": {"data": {"DB_URL": "postgres://db", "PORT": 5432},
+                     "metadata": {"version": 3}}}"#;
+        assert_eq!(
+            parse_secret(v2).unwrap(),
+            vec![owned("DB_URL", "postgres://db"), owned("PORT", "5432")]
+        );
+        let v1 = r#"{"data": {"TOKEN": "abc"}, "lease_duration": 0}"#;
+        assert_eq!(parse_secret(v1).unwrap(), vec![owned("TOKEN", "abc")]);
+        assert!(parse_secret(r#"{"errors": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_spec() {
+        let v = Vault::with_spec("//secret/data/myapp#KEY", http::Options::default()).unwrap();
+        assert_eq!(
+            (v.path.as_str(), v.key.as_deref()),
+            ("secret/data/myapp", Some("KEY"))
+        );
+        assert!(Vault::with_spec("#KEY", http::Options::default()).is_err());
+    }
+}
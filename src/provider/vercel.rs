@@ -0,0 +1,128 @@
+//! Project environment variables from the Vercel API. Authenticates with `VERCEL_TOKEN`, or the
+//! token stored by `enw login vercel`, and scopes requests to a team when `VERCEL_TEAM_ID` (or
+//! `VERCEL_ORG_ID`, as written by `vercel link`) is set.
+
+use std::env;
+
+use super::Provider;
+use crate::{credentials, http, json, BoxError, Origin, Var};
+
+const API_URL: &str = "https://api.vercel.com";
+const DEFAULT_TARGET: &str = "development";
+const TARGETS: &[&str] = &["production", "preview", "development"];
+
+#[derive(Debug)]
+pub struct Vercel {
+    project: String,
+    target: String,
+    http: http::Options,
+}
+
+impl Vercel {
+    /// Spec is `PROJECT[/TARGET]`, where target is one of production, preview or development
+    pub fn with_spec(spec: &str, http: http::Options) -> Result<Self, BoxError> {
+        let (project, target) = spec.split_once('/').unwrap_or((spec, DEFAULT_TARGET));
+        if project.is_empty() {
+            return Err("vercel: project missing".into());
+        }
+        // Variables are filtered by target, so a misspelt one would quietly match none
+        if !TARGETS.contains(&target) {
+            return Err(format!(
+                "vercel: unknown target {target}, expected one of {}",
+                TARGETS.join(", ")
+            )
+            .into());
+        }
+        Ok(Vercel {
+            project: project.to_owned(),
+            target: target.to_owned(),
+            http,
+        })
+    }
+}
+
+impl Provider for Vercel {
+    fn fetch(&self) -> Result<Vec<Var>, BoxError> {
+        let token = credentials::token("vercel", "VERCEL_TOKEN")?;
+        let mut url = format!(
+            "{API_URL}/v9/projects/{}/env?decrypt=true",
+            http::encode(&self.project)
+        );
+        if let Ok(team) = env::var("VERCEL_TEAM_ID").or_else(|_| env::var("VERCEL_ORG_ID")) {
+            url.push_str(&format!("&teamId={}", http::encode(&team)));
+        }
+        let auth = format!("Bearer {token}");
+        let body = http::get_with(&url, &[("Authorization", &auth)], &self.http)?;
+        let origin = Origin::Provider {
+            name: "vercel".to_owned(),
+            reference: None,
+        };
+        Ok(parse_envs(&body, &self.target)?
+            .into_iter()
+            .map(|(key, value)| Var::new(key, value, origin.clone()))
+            .collect())
+    }
+}
+
+fn parse_envs(body: &str, target: &str) -> Result<Vec<(String, String)>, BoxError> {
+    let doc = json::parse(body)?;
+    let envs = doc
+        .get("envs")
+        .and_then(json::Value::as_array)
+        .ok_or("vercel: unexpected response, envs missing")?;
+    Ok(envs
+        .iter()
+        .filter(|env| {
+            env.get("target")
+                .and_then(json::Value::as_array)
+                .is_some_and(|targets| targets.iter().any(|t| t.as_str() == Some(target)))
+        })
+        .filter_map(|env| {
+            // Sensitive variables are never returned in plain text, so they are skipped
+            let key = env.get("key")?.as_str()?;
+            let value = env.get("value")?.as_str()?;
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_envs_filters_target() {
+        let body = r#"{"envs": [
+            {"key": "API_URL", "value": "https://prod", "target": ["production"], "type": "plain"},
+            {"key": "API_URL", "value": "https://dev", "target": ["development", "preview"]},
+            {"key": "SECRET", "target": ["production"], "type": "sensitive"}
+        ]}"#;
+        assert_eq!(
+            parse_envs(body, "production").unwrap(),
+            vec![("API_URL".to_owned(), "https://prod".to_owned())]
+        );
+        assert_eq!(
+            parse_envs(body, "preview").unwrap(),
+            vec![("API_URL".to_owned(), "https://dev".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_spec() {
+        let v = Vercel::with_spec("web/production", http::Options::default()).unwrap();
+        assert_eq!(
+            (v.project.as_str(), v.target.as_str()),
+            ("web", "production")
+        );
+        let v = Vercel::with_spec("web", http::Options::default()).unwrap();
+        assert_eq!(v.target, DEFAULT_TARGET);
+        assert!(Vercel::with_spec("/production", http::Options::default()).is_err());
+        let err = Vercel::with_spec("web/prod", http::Options::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "vercel: unknown target prod, expected one of production, preview, development"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! `--pty` runs the command in a pseudo-terminal of its own, so that it sees a terminal even when
+//! enw's output is piped, and enw copies between the two. When enw's input is a terminal, it is
+//! put in raw mode and passed on as is, so that keys like Ctrl-C and the arrows reach the
+//! command's terminal, which then acts on them as enw's would have. Changes to the window size
+//! follow through SIGWINCH.
+
+use std::{
+    ffi::{CStr, OsStr},
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::OpenOptionsExt,
+        io::{AsRawFd, FromRawFd},
+        process::CommandExt,
+    },
+    process::{Command, Stdio},
+    ptr,
+    sync::atomic::{AtomicI32, Ordering},
+    thread::{self, JoinHandle},
+};
+
+use libc::c_int;
+
+/// The pseudo-terminal and enw's terminal, for the SIGWINCH handler
+static MASTER: AtomicI32 = AtomicI32::new(-1);
+static TERMINAL: AtomicI32 = AtomicI32::new(-1);
+
+/// End-of-file for a terminal in canonical mode, Ctrl-D
+const EOF: u8 = 4;
+
+pub struct Pty {
+    master: File,
+    slave: File,
+}
+
+impl Pty {
+    /// Open a pseudo-terminal with the settings and window size of enw's terminal, if it has one
+    pub fn open() -> io::Result<Self> {
+        // SAFETY: the master is owned by the File as soon as it is opened, and the slave's name
+        // is copied out of ptsname's static buffer before anything else can call it
+        let (master, path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0
+                || libc::grantpt(fd) != 0
+                || libc::unlockpt(fd) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            let name = libc::ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let path = OsStr::from_bytes(CStr::from_ptr(name).to_bytes()).to_owned();
+            (master, path)
+        };
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        // SAFETY: termios is plain data, only used when tcgetattr filled it in
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios);
+            }
+        }
+        if let Some(terminal) = terminal() {
+            copy_size(terminal, master.as_raw_fd());
+        }
+        Ok(Pty { master, slave })
+    }
+
+    /// Make the pseudo-terminal the command's standard streams and controlling terminal
+    pub fn attach(&self, cmd: &mut Command) -> io::Result<()> {
+        cmd.stdin(Stdio::from(self.slave.try_clone()?))
+            .stdout(Stdio::from(self.slave.try_clone()?))
+            .stderr(Stdio::from(self.slave.try_clone()?));
+        // SAFETY: setsid and ioctl are async-signal-safe
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            })
+        };
+        Ok(())
+    }
+
+    /// Start copying between the pseudo-terminal and enw's standard streams. Call once the
+    /// command is spawned and its copies of the slave are closed, so that the output ends when
+    /// the command exits.
+    pub fn relay(self) -> io::Result<Relay> {
+        let Pty { master, slave } = self;
+        drop(slave);
+        let mut relay = Relay {
+            output: None,
+            saved: None,
+            winch: None,
+        };
+        if let Some(terminal) = terminal() {
+            MASTER.store(master.as_raw_fd(), Ordering::SeqCst);
+            TERMINAL.store(terminal, Ordering::SeqCst);
+            relay.winch = Some(handle_winch()?);
+        }
+        // SAFETY: termios is plain data, only used when tcgetattr filled it in
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                relay.saved = Some(termios);
+                libc::cfmakeraw(&mut termios);
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &termios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        let raw = relay.saved.is_some();
+        let mut input = master.try_clone()?;
+        // Not joined: reading enw's input only ends with it, which may be never
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            loop {
+                match io::stdin().lock().read(&mut buf) {
+                    Ok(0) => {
+                        // Without a terminal to send Ctrl-D, tell the command the input ended
+                        if !raw {
+                            let _ = input.write_all(&[EOF]);
+                        }
+                        return;
+                    }
+                    Ok(n) if input.write_all(&buf[..n]).is_ok() => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    _ => return,
+                }
+            }
+        });
+        let mut output = master;
+        relay.output = Some(thread::spawn(move || {
+            let mut buf = [0; 4096];
+            let mut stdout = io::stdout();
+            loop {
+                match output.read(&mut buf) {
+                    Ok(0) => return Ok(()),
+                    Ok(n) => {
+                        stdout.write_all(&buf[..n])?;
+                        stdout.flush()?;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    // Linux reports a closed slave as EIO
+                    Err(e) if e.raw_os_error() == Some(libc::EIO) => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+        }));
+        Ok(relay)
+    }
+}
+
+/// Copying in progress, enw's terminal restored on drop
+pub struct Relay {
+    output: Option<JoinHandle<io::Result<()>>>,
+    saved: Option<libc::termios>,
+    winch: Option<libc::sigaction>,
+}
+
+impl Relay {
+    /// Wait until all of the command's output has been copied
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.output.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("output relay panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        // SAFETY: restores what tcgetattr and sigaction read
+        unsafe {
+            if let Some(saved) = &self.saved {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, saved);
+            }
+            if let Some(winch) = &self.winch {
+                libc::sigaction(libc::SIGWINCH, winch, ptr::null_mut());
+            }
+        }
+        MASTER.store(-1, Ordering::SeqCst);
+        TERMINAL.store(-1, Ordering::SeqCst);
+    }
+}
+
+/// The first of enw's standard streams that is a terminal
+fn terminal() -> Option<c_int> {
+    [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .into_iter()
+        // SAFETY: no preconditions
+        .find(|&fd| unsafe { libc::isatty(fd) } == 1)
+}
+
+/// Give `to` the window size of `from`. Only makes async-signal-safe calls.
+fn copy_size(from: c_int, to: c_int) {
+    // SAFETY: winsize is plain data, only used when TIOCGWINSZ filled it in
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(from, libc::TIOCGWINSZ as _, &mut size) == 0 {
+            libc::ioctl(to, libc::TIOCSWINSZ as _, &size);
+        }
+    }
+}
+
+extern "C" fn resize(_: c_int) {
+    let (terminal, master) = (
+        TERMINAL.load(Ordering::SeqCst),
+        MASTER.load(Ordering::SeqCst),
+    );
+    if terminal >= 0 && master >= 0 {
+        // The kernel tells the command with a SIGWINCH of its own
+        copy_size(terminal, master);
+    }
+}
+
+/// Install the SIGWINCH handler, returning the previous action
+fn handle_winch() -> io::Result<libc::sigaction> {
+    // SAFETY: sigaction is plain data, fully initialized before use
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = resize as extern "C" fn(c_int) as usize;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old: libc::sigaction = mem::zeroed();
+        if libc::sigaction(libc::SIGWINCH, &action, &mut old) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(old)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    #[test]
+    fn test_attach() {
+        let pty = Pty::open().unwrap();
+        let mut cmd = Command::new("sh");
+        // /dev/tty opens only with a controlling terminal
+        cmd.args(["-c", "test -t 0 && test -t 1 && test -t 2 && : </dev/tty"]);
+        pty.attach(&mut cmd).unwrap();
+        let mut child = cmd.spawn().unwrap();
+        drop(cmd);
+        let Pty { mut master, slave } = pty;
+        drop(slave);
+        assert!(child.wait().unwrap().success());
+        let mut output = Vec::new();
+        let _ = master.read_to_end(&mut output);
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Masking of secret values in output meant for people: the listing without a command,
+//! `--verbose`, `--explain`, `--print` and diffs show `****` instead, unless `--show-secrets` is
+//! given. Output meant for programs, `-0` and `--print=export|json|null`, is refused instead, as
+//! they would take the mask for the value. Which variables are secrets is up to
+//! [`Var::is_secret`], the command gets their values either way.
+
+use std::{collections::BTreeMap, ffi::OsString};
+
+use crate::{BoxError, Var};
+
+pub const MASK: &str = "****";
+
+/// The value of `var` as shown, masked if it is a secret
+pub fn value(var: &Var, show_secrets: bool) -> &str {
+    match var.is_secret() && !show_secrets {
+        true => MASK,
+        false => &var.value,
+    }
+}
+
+/// Mask the values `env` has for the secrets among `vars`
+pub fn env(env: &mut BTreeMap<OsString, OsString>, vars: &[Var], show_secrets: bool) {
+    for var in vars.iter().filter(|var| var.is_secret() && !show_secrets) {
+        if let Some(value) = env.get_mut(&OsString::from(&var.key)) {
+            *value = MASK.into();
+        }
+    }
+}
+
+/// Fail if any of `vars`, about to be written for a program to read, would have to be masked
+pub fn refuse_masked<'a>(
+    vars: impl IntoIterator<Item = &'a Var>,
+    show_secrets: bool,
+) -> Result<(), BoxError> {
+    let secrets: Vec<&str> = vars
+        .into_iter()
+        .filter(|var| var.is_secret() && !show_secrets)
+        .map(|var| var.key.as_str())
+        .collect();
+    if secrets.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "refusing to print secrets ({}) masked in output for programs; give --show-secrets to \
+         print their values",
+        secrets.join(", ")
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    #[test]
+    fn test_env() {
+        let vars = vec![
+            Var::new("HOST".into(), "db".into(), Origin::CommandLine),
+            Var::new("DB_PASSWORD".into(), "hunter2".into(), Origin::CommandLine),
+        ];
+        let pairs = |pairs: &[(&str, &str)]| -> BTreeMap<OsString, OsString> {
+            pairs
+                .iter()
+                .map(|&(key, value)| (key.into(), value.into()))
+                .collect()
+        };
+        let mut masked = pairs(&[("HOST", "db"), ("DB_PASSWORD", "hunter2")]);
+        env(&mut masked, &vars, false);
+        assert_eq!(masked, pairs(&[("HOST", "db"), ("DB_PASSWORD", MASK)]));
+        let mut shown = pairs(&[("HOST", "db"), ("DB_PASSWORD", "hunter2")]);
+        env(&mut shown, &vars, true);
+        assert_eq!(shown, pairs(&[("HOST", "db"), ("DB_PASSWORD", "hunter2")]));
+        assert_eq!(value(&vars[1], false), MASK);
+    }
+
+    #[test]
+    fn test_refuse_masked() {
+        let vars = vec![
+            Var::new("HOST".into(), "db".into(), Origin::CommandLine),
+            Var::new("DB_PASSWORD".into(), "hunter2".into(), Origin::CommandLine),
+        ];
+        assert!(refuse_masked(&vars[..1], false).is_ok());
+        assert!(refuse_masked(&vars, true).is_ok());
+        let err = refuse_masked(&vars, false).unwrap_err().to_string();
+        assert!(err.starts_with("refusing to print secrets (DB_PASSWORD) masked"));
+    }
+}
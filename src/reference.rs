@@ -0,0 +1,128 @@
+//! Values of env files naming where the actual value is kept, resolved in the interpolate phase
+//! once all sources are parsed:
+//!
+//! ```text
+//! DATABASE_URL=@file:/run/secrets/db_url
+//! API_KEY=@cmd:pass show api-key
+//! DB_PASSWORD='secretsmanager://prod/db#password'
+//! ```
+//!
+//! File contents and command output are trimmed. Each kind of reference is opt in, with
+//! `--allow-file-refs`, `--allow-cmd-refs` and `--allow-aws-refs`, so that the env file of a
+//! repository can not copy `~/.ssh` or the secrets of the AWS account into the environment. The
+//! values are treated as secrets.
+
+use std::{fs, process::Command};
+
+use crate::{provider::aws, BoxError, OptionsBuilder, Origin, Var};
+
+struct Resolver {
+    prefix: &'static str,
+    /// The flag enabling the resolver
+    opt_in: &'static str,
+    enabled: fn(&OptionsBuilder) -> bool,
+    resolve: fn(&str) -> Result<(String, Origin), BoxError>,
+}
+
+const RESOLVERS: &[Resolver] = &[
+    Resolver {
+        prefix: "@file:",
+        opt_in: "--allow-file-refs",
+        enabled: |opts| opts.allow_file_refs,
+        resolve: read_file,
+    },
+    Resolver {
+        prefix: "@cmd:",
+        opt_in: "--allow-cmd-refs",
+        enabled: |opts| opts.allow_cmd_refs,
+        resolve: run_command,
+    },
+    Resolver {
+        prefix: "secretsmanager://",
+        opt_in: "--allow-aws-refs",
+        enabled: |opts| opts.allow_aws_refs,
+        resolve: aws::secret,
+    },
+];
+
+/// Replace the value of `var` with the one it refers to, if it is a reference
+pub fn resolve(var: &mut Var, opts: &OptionsBuilder) -> Result<(), BoxError> {
+    let Some((resolver, rest)) = RESOLVERS.iter().find_map(|resolver| {
+        let rest = var.value.strip_prefix(resolver.prefix)?;
+        Some((resolver, rest))
+    }) else {
+        return Ok(());
+    };
+    if !(resolver.enabled)(opts) {
+        return Err(format!(
+            "{}: {} references are not allowed without {}",
+            var.key, resolver.prefix, resolver.opt_in
+        )
+        .into());
+    }
+    let (value, origin) = (resolver.resolve)(rest).map_err(|e| format!("{}: {e}", var.key))?;
+    var.value = value;
+    var.origin = origin;
+    Ok(())
+}
+
+fn read_file(path: &str) -> Result<(String, Origin), BoxError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    Ok((text.trim().to_owned(), origin("file", path)))
+}
+
+fn run_command(command: &str) -> Result<(String, Origin), BoxError> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .output()
+        .map_err(|e| format!("could not run {command}: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{command} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    let value = String::from_utf8(output.stdout)?.trim().to_owned();
+    Ok((value, origin("cmd", command)))
+}
+
+fn origin(name: &str, reference: &str) -> Origin {
+    Origin::Provider {
+        name: name.to_owned(),
+        reference: Some(reference.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let path = std::env::temp_dir().join(format!("enw-reference-test-{}", std::process::id()));
+        fs::write(&path, "s3cret\n").unwrap();
+        let file_var =
+            |value: String| Var::new("KEY".to_owned(), value, Origin::File(PathBuf::from(".env")));
+        let mut opts = OptionsBuilder::default();
+        let mut var = file_var(format!("@file:{}", path.to_string_lossy()));
+        assert!(resolve(&mut var, &opts).is_err());
+        opts.allow_file_refs = true;
+        resolve(&mut var, &opts).unwrap();
+        assert_eq!(var.value, "s3cret");
+        assert!(var.is_secret());
+        let mut var = file_var("@cmd:echo ' hi '".to_owned());
+        assert!(resolve(&mut var, &opts).is_err());
+        opts.allow_cmd_refs = true;
+        resolve(&mut var, &opts).unwrap();
+        assert_eq!(var.value, "hi");
+        let mut var = file_var("@mention".to_owned());
+        resolve(&mut var, &opts).unwrap();
+        assert_eq!(var.value, "@mention");
+        fs::remove_file(path).unwrap();
+    }
+}
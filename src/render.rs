@@ -0,0 +1,58 @@
+//! `enw render TEMPLATE`, an envsubst that knows about env files: `${VAR}` and `$VAR` in the
+//! template are replaced with the values the command would get. Undefined variables are
+//! replaced with nothing, like envsubst does, and `$` not followed by a name is kept.
+
+use crate::{BoxError, Resolve};
+
+pub fn render(template: &str, resolve: Resolve) -> Result<String, BoxError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 1..];
+        if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| {
+                let line = template[..template.len() - rest.len()].lines().count();
+                format!("line {line}: unterminated ${{")
+            })?;
+            out.push_str(&resolve(&braced[..end]).unwrap_or_default());
+            rest = &braced[end + 1..];
+            continue;
+        }
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push('$');
+            continue;
+        }
+        out.push_str(&resolve(&rest[..len]).unwrap_or_default());
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let resolve = |name: &str| (name == "HOST").then(|| "db.local".to_owned());
+        assert_eq!(
+            render(
+                "url=http://$HOST:${PORT}/x\ncost: $5, $ and $HOST_\n",
+                &resolve
+            )
+            .unwrap(),
+            "url=http://db.local:/x\ncost: $5, $ and \n"
+        );
+        assert_eq!(
+            render("a\n${HOST", &resolve).unwrap_err().to_string(),
+            "line 2: unterminated ${"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! `--report PATH` writes what a run did and how long it took as JSON, once the command exits.
+
+use std::{
+    collections::BTreeMap, fs, os::unix::process::ExitStatusExt, path::Path, process::ExitStatus,
+    time::Duration,
+};
+
+use crate::{json, BoxError};
+
+#[derive(Debug)]
+pub struct Report {
+    /// Env files and provider specs, in load order
+    pub sources: Vec<String>,
+    pub var_count: usize,
+    pub provider_timings: Vec<(String, Duration)>,
+    /// Time from start until the command was spawned
+    pub spawn_time: Duration,
+    pub wall_time: Duration,
+    pub status: ExitStatus,
+}
+
+impl Report {
+    pub fn to_json(&self) -> json::Value {
+        let string = |s: &str| json::Value::String(s.to_owned());
+        let seconds = |d: Duration| json::Value::Number(d.as_secs_f64());
+        let providers = self
+            .provider_timings
+            .iter()
+            .map(|(spec, time)| {
+                let mut entry = BTreeMap::new();
+                entry.insert("spec".to_owned(), string(spec));
+                entry.insert("seconds".to_owned(), seconds(*time));
+                json::Value::Object(entry)
+            })
+            .collect();
+        let mut exit = BTreeMap::new();
+        if let Some(code) = self.status.code() {
+            exit.insert("code".to_owned(), json::Value::Number(code.into()));
+        }
+        if let Some(signal) = self.status.signal() {
+            exit.insert("signal".to_owned(), json::Value::Number(signal.into()));
+        }
+        let mut doc = BTreeMap::new();
+        doc.insert(
+            "sources".to_owned(),
+            json::Value::Array(self.sources.iter().map(|s| string(s)).collect()),
+        );
+        doc.insert(
+            "variables".to_owned(),
+            json::Value::Number(self.var_count as f64),
+        );
+        doc.insert("providers".to_owned(), json::Value::Array(providers));
+        doc.insert("spawn_seconds".to_owned(), seconds(self.spawn_time));
+        doc.insert("wall_seconds".to_owned(), seconds(self.wall_time));
+        doc.insert("exit".to_owned(), json::Value::Object(exit));
+        json::Value::Object(doc)
+    }
+
+    /// Write to `path`, or to stderr when it is `-`
+    pub fn write(&self, path: &Path) -> Result<(), BoxError> {
+        let text = format!("{}\n", self.to_json().pretty());
+        if path == Path::new("-") {
+            eprint!("{text}");
+        } else {
+            fs::write(path, text)
+                .map_err(|e| format!("writing report to {}: {e}", path.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let report = Report {
+            sources: vec![".env".into(), "vercel:app".into()],
+            var_count: 3,
+            provider_timings: vec![("vercel:app".into(), Duration::from_millis(250))],
+            spawn_time: Duration::from_millis(500),
+            wall_time: Duration::from_secs(2),
+            status: ExitStatus::from_raw(9),
+        };
+        assert_eq!(
+            report.to_json().to_string(),
+            r#"{"exit":{"signal":9},"providers":[{"seconds":0.25,"spec":"vercel:app"}],"sources":[".env","vercel:app"],"spawn_seconds":0.5,"variables":3,"wall_seconds":2}"#
+        );
+    }
+}
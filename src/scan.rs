@@ -0,0 +1,204 @@
+//! `enw scan` looks through source code for environment variable accesses and compares them
+//! with the variables declared in `.env.example`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{glob, placeholder::EXAMPLE_FILE_NAME, BoxError};
+
+/// Source files worth reading
+const EXTENSIONS: &[&str] = &[
+    "rs", "js", "mjs", "cjs", "jsx", "ts", "tsx", "py", "go", "rb", "c", "h", "sh",
+];
+/// Directories that hold dependencies or build output rather than project code
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "vendor", "dist", "build", "venv"];
+
+/// Function calls taking the name as a string literal
+const CALLS: &[&str] = &[
+    "env::var(",
+    "env::var_os(",
+    "env!(",
+    "option_env!(",
+    "getenv(",
+    "os.environ.get(",
+    "os.LookupEnv(",
+    "ENV.fetch(",
+];
+/// Objects indexed by the name, `process.env` also by property access
+const INDEXED: &[&str] = &["process.env", "import.meta.env", "os.environ", "ENV"];
+
+/// Set by the system or the build tool rather than the project's configuration
+const AMBIENT: &[&str] = &[
+    "CARGO*", "HOME", "PATH", "USER", "SHELL", "TERM", "PWD", "LANG", "TMPDIR", "OUT_DIR",
+];
+
+/// Where each variable is used, as file and line number
+pub type Uses = BTreeMap<String, Vec<(PathBuf, usize)>>;
+
+/// Find the variables accessed by source files below `root`
+pub fn scan(root: &Path) -> Result<Uses, BoxError> {
+    let mut uses = Uses::new();
+    scan_dir(root, &mut uses)?;
+    Ok(uses)
+}
+
+fn scan_dir(dir: &Path, uses: &mut Uses) -> Result<(), BoxError> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                scan_dir(&path, uses)?;
+            }
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext == *e))
+        {
+            // Skip files that are not UTF-8, they are unlikely to be source code
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            for (key, line) in find_uses(&text) {
+                uses.entry(key).or_default().push((path.clone(), line));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lines reporting variables used but not declared in `declared`, and declared but unused
+pub fn report(uses: &Uses, declared: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let undeclared: Vec<_> = uses
+        .iter()
+        .filter(|(key, _)| {
+            !declared.contains(key) && !AMBIENT.iter().any(|pattern| glob::matches(pattern, key))
+        })
+        .collect();
+    if !undeclared.is_empty() {
+        out.push(format!("used but not declared in {EXAMPLE_FILE_NAME}:"));
+        for (key, places) in undeclared {
+            let (path, line) = &places[0];
+            out.push(format!("  {key} ({}:{line})", path.to_string_lossy()));
+        }
+    }
+    let unused: Vec<_> = declared
+        .iter()
+        .filter(|key| !uses.contains_key(*key))
+        .collect();
+    if !unused.is_empty() {
+        out.push(format!("declared in {EXAMPLE_FILE_NAME} but never used:"));
+        out.extend(unused.iter().map(|key| format!("  {key}")));
+    }
+    out
+}
+
+/// Variable names accessed in `text`, with 1-based line numbers
+pub fn find_uses(text: &str) -> Vec<(String, usize)> {
+    let mut found = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        for call in CALLS {
+            for (start, _) in line.match_indices(call) {
+                found.extend(quoted_name(&line[start + call.len()..]).map(|key| (key, number + 1)));
+            }
+        }
+        for object in INDEXED {
+            for (start, _) in line.match_indices(object) {
+                let before = line[..start].chars().next_back();
+                if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                    continue;
+                }
+                let rest = &line[start + object.len()..];
+                let key = if let Some(rest) = rest.strip_prefix('[') {
+                    quoted_name(rest)
+                } else if let Some(rest) = rest.strip_prefix('.') {
+                    // Methods of os.environ and the like are not variables
+                    name(rest).filter(|key| !rest[key.len()..].starts_with('('))
+                } else {
+                    None
+                };
+                found.extend(key.map(|key| (key, number + 1)));
+            }
+        }
+    }
+    found
+}
+
+/// A variable name in a string literal at the start of `text`
+fn quoted_name(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    let quote = text
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let rest = &text[1..];
+    let key = name(rest)?;
+    rest[key.len()..].starts_with(quote).then_some(key)
+}
+
+fn name(text: &str) -> Option<String> {
+    let key: String = text
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        .then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_find_uses() {
+        let text = r#"
+            let url = std::env::var("DATABASE_URL")?; let k = env!("CARGO_PKG_NAME");
+            const port = process.env.PORT || process.env['HOST'];
+            debug = os.environ.get("DEBUG") or os.environ["LEVEL"] or os.getenv('HOME')
+            os.environ.copy(); myprocess.env.NOPE; env::var(name);
+        "#;
+        let keys: Vec<_> = find_uses(text)
+            .into_iter()
+            .map(|(key, line)| format!("{key}:{line}"))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "DATABASE_URL:2",
+                "CARGO_PKG_NAME:2",
+                "PORT:3",
+                "HOST:3",
+                "HOME:4",
+                "DEBUG:4",
+                "LEVEL:4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let mut uses = Uses::new();
+        for key in ["API_URL", "HOME", "PORT"] {
+            uses.insert(key.into(), vec![(PathBuf::from("src/main.rs"), 3)]);
+        }
+        assert_eq!(
+            report(&uses, &["PORT".into(), "OLD_FLAG".into()]),
+            vec![
+                "used but not declared in .env.example:",
+                "  API_URL (src/main.rs:3)",
+                "declared in .env.example but never used:",
+                "  OLD_FLAG",
+            ]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! The variables a project needs, declared in `.env.schema`, or else `.env.example`. Every key
+//! of `.env.example` is required. In `.env.schema` the value is a comma separated spec: a type
+//! (`string`, `int`, `bool` or `url`), `optional` and `nonempty`, e.g. `PORT=int` or
+//! `DEBUG=bool,optional`. An empty spec declares a required string.
+//!
+//! Env files can declare types too, for the variables they set: `PORT:int=8080`, or a comment
+//! on the line before, `# enw: type=int, required`, where `required` means not empty. Values
+//! are checked when the file is loaded, and normalized: booleans become `true` or `false`.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{assignments, placeholder, split_assignment, BoxError};
+
+pub const FILE_NAME: &str = ".env.schema";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Type {
+    #[default]
+    String,
+    Int,
+    Bool,
+    Url,
+}
+
+impl Type {
+    /// Whether `value` is of the type
+    pub fn check(self, value: &str) -> Result<(), String> {
+        let ok = match self {
+            Type::String => true,
+            Type::Int => value.parse::<i64>().is_ok(),
+            Type::Bool => parse_bool(value).is_some(),
+            Type::Url => value.split_once("://").is_some_and(|(scheme, rest)| {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                    && !rest.is_empty()
+            }),
+        };
+        match ok {
+            true => Ok(()),
+            false => Err(format!("expected {self}, got {value:?}")),
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Type::String => "string",
+            Type::Int => "int",
+            Type::Bool => "bool",
+            Type::Url => "url",
+        })
+    }
+}
+
+impl FromStr for Type {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(Type::String),
+            "int" => Ok(Type::Int),
+            "bool" => Ok(Type::Bool),
+            "url" => Ok(Type::Url),
+            _ => Err(format!("unknown type: {s}").into()),
+        }
+    }
+}
+
+impl Type {
+    /// The canonical spelling of a value already checked
+    pub fn normalize(self, value: &str) -> String {
+        match self {
+            Type::Bool => parse_bool(value).map_or_else(|| value.to_owned(), |b| b.to_string()),
+            Type::Int => value
+                .parse::<i64>()
+                .map_or_else(|_| value.to_owned(), |n| n.to_string()),
+            Type::String | Type::Url => value.to_owned(),
+        }
+    }
+}
+
+/// Split the type off a key declared as `KEY:TYPE`
+pub fn split_type(key: &str) -> (&str, Option<Type>) {
+    match key.rsplit_once(':') {
+        Some((name, ty)) if !name.is_empty() => match ty.parse() {
+            Ok(ty) => (name, Some(ty)),
+            Err(_) => (key, None),
+        },
+        _ => (key, None),
+    }
+}
+
+/// Map keys of a dotenv document to the line, from 1, and rule of their annotations
+pub fn annotations(text: &str) -> Result<HashMap<String, (usize, Rule)>, BoxError> {
+    let starts: HashMap<usize, &str> = assignments(text).into_iter().collect();
+    let mut out = HashMap::new();
+    let mut pending = None;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(spec) = trimmed
+            .strip_prefix('#')
+            .and_then(|comment| comment.trim().strip_prefix("enw:"))
+        {
+            let rule = annotation(spec).map_err(|e| format!("line {}: {e}", i + 1))?;
+            pending = Some(rule);
+            continue;
+        }
+        let Some(assignment) = starts.get(&i) else {
+            continue;
+        };
+        let raw_key = assignment
+            .trim_start()
+            .trim_start_matches("export ")
+            .split('=')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        let inline = split_type(raw_key).1;
+        let Some((key, _)) = split_assignment(assignment) else {
+            continue;
+        };
+        let rule = match (pending.take(), inline) {
+            (Some(rule), Some(ty)) => Some(Rule { ty, ..rule }),
+            (rule, None) => rule,
+            (None, Some(ty)) => Some(Rule {
+                ty,
+                required: false,
+                non_empty: false,
+            }),
+        };
+        if let Some(rule) = rule {
+            out.insert(key.to_owned(), (i + 1, rule));
+        }
+    }
+    Ok(out)
+}
+
+/// The rule of a `# enw: type=int, required` comment
+fn annotation(spec: &str) -> Result<Rule, BoxError> {
+    let mut rule = Rule {
+        required: false,
+        ..Default::default()
+    };
+    for word in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+    {
+        match word {
+            "required" | "nonempty" => rule.non_empty = true,
+            "optional" => {}
+            _ => match word.strip_prefix("type=") {
+                Some(ty) => rule.ty = ty.trim().parse()?,
+                None => return Err(format!("unknown annotation: {word}").into()),
+            },
+        }
+    }
+    Ok(rule)
+}
+
+/// The spellings of booleans accepted, case insensitively
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub ty: Type,
+    pub required: bool,
+    pub non_empty: bool,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            ty: Type::String,
+            required: true,
+            non_empty: false,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = BoxError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut rule = Rule::default();
+        for word in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|word| !word.is_empty())
+        {
+            match word {
+                "optional" => rule.required = false,
+                "required" => rule.required = true,
+                "nonempty" => rule.non_empty = true,
+                _ => rule.ty = word.parse()?,
+            }
+        }
+        Ok(rule)
+    }
+}
+
+impl Rule {
+    /// What is wrong with the value of `key`, if anything
+    pub fn check(&self, key: &str, value: Option<&str>) -> Option<String> {
+        match value {
+            None if self.required => Some(format!("{key} is missing")),
+            None => None,
+            Some("") if self.non_empty => Some(format!("{key} is empty")),
+            // Unset and empty are the same to most programs
+            Some("") if self.ty != Type::String && !self.required => None,
+            Some(value) => self.ty.check(value).err().map(|e| format!("{key}: {e}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Schema {
+    pub path: PathBuf,
+    pub rules: Vec<(String, Rule)>,
+}
+
+impl Schema {
+    /// `.env.schema` in `dir`, or else `.env.example`
+    pub fn load(dir: &Path) -> Result<Option<Self>, BoxError> {
+        for name in [FILE_NAME, placeholder::EXAMPLE_FILE_NAME] {
+            let path = dir.join(name);
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("{}: {e}", path.to_string_lossy()).into()),
+            };
+            let typed = name == FILE_NAME;
+            let mut rules = Vec::new();
+            for (line, assignment) in assignments(&text) {
+                let Some((key, spec)) = split_assignment(assignment) else {
+                    continue;
+                };
+                let rule = match typed {
+                    true => spec
+                        .parse()
+                        .map_err(|e| format!("{}:{}: {e}", path.to_string_lossy(), line + 1))?,
+                    false => Rule::default(),
+                };
+                rules.push((key.to_owned(), rule));
+            }
+            return Ok(Some(Schema { path, rules }));
+        }
+        Ok(None)
+    }
+
+    /// Everything wrong with the variables, in the order of the schema
+    pub fn check(&self, value: impl Fn(&str) -> Option<String>) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter_map(|(key, rule)| rule.check(key, value(key).as_deref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let dir = std::env::temp_dir().join(format!("enw-schema-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(Schema::load(&dir).unwrap().is_none());
+        fs::write(dir.join(placeholder::EXAMPLE_FILE_NAME), "A=example\n").unwrap();
+        let schema = Schema::load(&dir).unwrap().unwrap();
+        assert_eq!(schema.rules, vec![("A".to_owned(), Rule::default())]);
+        fs::write(
+            dir.join(FILE_NAME),
+            "# the service\nURL=url\nPORT=int\nDEBUG=bool,optional\nTOKEN=nonempty\nNAME=\n",
+        )
+        .unwrap();
+        let schema = Schema::load(&dir).unwrap().unwrap();
+        let values = |key: &str| match key {
+            "URL" => Some("localhost:5432".to_owned()),
+            "PORT" => Some("8080".to_owned()),
+            "TOKEN" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(
+            schema.check(values),
+            vec![
+                "URL: expected url, got \"localhost:5432\"",
+                "TOKEN is empty",
+                "NAME is missing"
+            ]
+        );
+        assert!(Type::Bool.check("Yes").is_ok());
+        assert!("int,float".parse::<Rule>().is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_annotations() {
+        let text = "
+            # enw: type=bool, required
+            DEBUG=Yes
+            PORT:int=8080
+            # enw: type=int
+            export RETRIES:url=3
+            PLAIN=1
+        ";
+        let rules = annotations(text).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules["DEBUG"].0, 3);
+        assert_eq!(rules["DEBUG"].1.ty, Type::Bool);
+        assert!(rules["DEBUG"].1.non_empty);
+        assert_eq!(rules["PORT"].1.ty, Type::Int);
+        assert_eq!(rules["RETRIES"].1.ty, Type::Url);
+        assert_eq!(Type::Bool.normalize("Yes"), "true");
+        assert_eq!(Type::Int.normalize("+08"), "8");
+        assert_eq!(split_type("a:b:int"), ("a:b", Some(Type::Int)));
+        assert_eq!(split_type("url:x"), ("url:x", None));
+        assert!(annotations("# enw: type=float\nA=1").is_err());
+    }
+}
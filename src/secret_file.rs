@@ -0,0 +1,104 @@
+//! Handing secrets to the child through a file or an inherited file descriptor instead of the
+//! environment, where they would be readable from `/proc/<pid>/environ` and end up in crash
+//! dumps. Programs are pointed to the secret with `KEY_FILE` or `KEY_FD`.
+
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::{
+        fd::{AsRawFd, IntoRawFd},
+        unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    },
+    path::PathBuf,
+    process,
+};
+
+use crate::BoxError;
+
+/// The directory holding this process's secret files
+fn dir() -> PathBuf {
+    let base = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    base.join(format!("enw-{}", process::id()))
+}
+
+/// Write the value to a file only readable by the current user and return the path. A file
+/// written for an earlier run of the command, e.g. before a `--watch` restart, is overwritten.
+/// Only when enw replaces itself with the command is the file left for the command to remove,
+/// otherwise enw removes it once the command exits, see [`Cleanup`].
+pub fn write_file(key: &str, value: &str) -> Result<PathBuf, BoxError> {
+    let dir = dir();
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e.into()),
+        Err(_) => {
+            // The name is predictable, so only use a directory that enw created
+            let metadata = fs::symlink_metadata(&dir)?;
+            // SAFETY: no preconditions
+            let uid = unsafe { libc::geteuid() };
+            if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+                return Err(format!("{} is not a private directory", dir.display()).into());
+            }
+        }
+        Ok(()) => {}
+    }
+    let path = dir.join(key);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&path)?;
+    file.write_all(value.as_bytes())?;
+    Ok(fs::canonicalize(path)?)
+}
+
+/// Remove the files written by [`write_file`], once the command that reads them has exited
+pub fn remove_files() -> io::Result<()> {
+    match fs::remove_dir_all(dir()) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Removes the secret files when dropped, i.e. when enw returns instead of replacing itself
+/// with the command
+pub struct Cleanup;
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        if let Err(e) = remove_files() {
+            eprintln!(
+                "enw: could not remove secret files in {}: {e}",
+                dir().display()
+            );
+        }
+    }
+}
+
+/// Write the value to a pipe and return the read end, left open across exec. The value has to
+/// fit in the pipe buffer since nothing is reading yet.
+pub fn write_pipe(value: &str) -> Result<i32, BoxError> {
+    let (reader, mut writer) = io::pipe()?;
+    // Writing must never block, a value larger than the pipe buffer is an error instead
+    let writer_fd = writer.as_raw_fd();
+    // SAFETY: writer_fd is a valid descriptor for the duration of the calls
+    let flags = unsafe { libc::fcntl(writer_fd, libc::F_GETFL) };
+    if flags == -1
+        || unsafe { libc::fcntl(writer_fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1
+    {
+        return Err(io::Error::last_os_error().into());
+    }
+    writer
+        .write_all(value.as_bytes())
+        .map_err(|e| format!("secret too large to pass through a pipe: {e}"))?;
+    drop(writer);
+    let fd = reader.into_raw_fd();
+    // SAFETY: fd is a valid descriptor, owned from here on by the command
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(fd)
+}
@@ -0,0 +1,94 @@
+//! Quoting for POSIX shells.
+
+use crate::BoxError;
+
+/// Quote a word so that a POSIX shell reads it back verbatim. Words made up of safe characters
+/// are left as is, everything else is single quoted.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty()
+        && word.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '_' | '-' | '.' | '/' | ':' | '=' | ',' | '@' | '%' | '+')
+        })
+    {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, minus expansions: whitespace
+/// separates words, quotes and backslashes protect it.
+pub fn split(line: &str) -> Result<Vec<String>, BoxError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated quote: {line}").into()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("unterminated quote: {line}").into()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated quote: {line}").into()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash: {line}").into()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("KEY=value"), "KEY=value");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(quote("line\nbreak"), "'line\nbreak'");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#" -f .env.prod  a='b c' "d\"e"f\ g '' "#).unwrap(),
+            vec!["-f", ".env.prod", "a=b c", "d\"ef g", ""]
+        );
+        assert!(split("'open").is_err());
+        assert!(split("trailing\\").is_err());
+    }
+}
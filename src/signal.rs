@@ -0,0 +1,166 @@
+//! GNU env's `--default-signal`, `--ignore-signal` and `--block-signal`, which set up the
+//! signal dispositions the command starts with.
+
+use std::{io, mem, ops::RangeInclusive};
+
+use libc::c_int;
+
+use crate::BoxError;
+
+const NAMES: &[(&str, c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+/// Real-time signals, which have no names and are given by number
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn realtime() -> RangeInclusive<c_int> {
+    libc::SIGRTMIN()..=libc::SIGRTMAX()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn realtime() -> RangeInclusive<c_int> {
+    #[allow(clippy::reversed_empty_ranges)]
+    {
+        1..=0
+    }
+}
+
+/// Signals to reset, ignore and block in the command, applied in that order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dispositions {
+    pub default: Vec<c_int>,
+    pub ignore: Vec<c_int>,
+    pub block: Vec<c_int>,
+}
+
+impl Dispositions {
+    pub fn is_empty(&self) -> bool {
+        self.default.is_empty() && self.ignore.is_empty() && self.block.is_empty()
+    }
+
+    /// Apply to the current process. Only calls async-signal-safe functions, so it can run
+    /// between fork and exec.
+    pub fn apply(&self) -> io::Result<()> {
+        for (signals, handler) in [
+            (&self.default, libc::SIG_DFL),
+            (&self.ignore, libc::SIG_IGN),
+        ] {
+            for &signal in signals {
+                // SAFETY: installs one of the predefined dispositions, no handler code runs
+                if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        if !self.block.is_empty() {
+            // SAFETY: the set is initialized by sigemptyset before use
+            unsafe {
+                let mut set: libc::sigset_t = mem::zeroed();
+                libc::sigemptyset(&mut set);
+                for &signal in &self.block {
+                    libc::sigaddset(&mut set, signal);
+                }
+                if libc::sigprocmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the values of one of the options: no values means every signal that can be caught,
+/// otherwise each is a name, with or without `SIG`, or a number.
+pub fn parse_list(values: &[String]) -> Result<Vec<c_int>, BoxError> {
+    if values.is_empty() {
+        return Ok(NAMES
+            .iter()
+            .map(|&(_, signal)| signal)
+            .filter(|&signal| signal != libc::SIGKILL && signal != libc::SIGSTOP)
+            .chain(realtime())
+            .collect());
+    }
+    values.iter().map(|value| parse(value)).collect()
+}
+
+/// A signal whose disposition can be changed, see [`name`]
+fn parse(value: &str) -> Result<c_int, BoxError> {
+    let signal = name(value)?;
+    if signal == libc::SIGKILL || signal == libc::SIGSTOP {
+        return Err(format!("the disposition of {value} can not be changed").into());
+    }
+    Ok(signal)
+}
+
+/// A signal name, with or without `SIG`, or number
+pub fn name(value: &str) -> Result<c_int, BoxError> {
+    let upper = value.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    let signal = match value.parse::<c_int>() {
+        Ok(number)
+            if NAMES.iter().any(|&(_, signal)| signal == number)
+                || realtime().contains(&number) =>
+        {
+            number
+        }
+        Ok(_) => return Err(format!("invalid signal: {value}").into()),
+        Err(_) => NAMES
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, signal)| signal)
+            .ok_or_else(|| format!("invalid signal: {value}"))?,
+    };
+    Ok(signal)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let values = vec!["PIPE".to_owned(), "sigint".to_owned(), "15".to_owned()];
+        assert_eq!(
+            parse_list(&values).unwrap(),
+            vec![libc::SIGPIPE, libc::SIGINT, libc::SIGTERM]
+        );
+        let all = parse_list(&[]).unwrap();
+        assert!(all.contains(&libc::SIGHUP) && !all.contains(&libc::SIGKILL));
+        for invalid in ["KILL", "SIGSTOP", "NOPE", "0"] {
+            assert!(parse(invalid).is_err(), "expected err: {invalid}");
+        }
+        assert_eq!(name("KILL").unwrap(), libc::SIGKILL);
+        assert_eq!(name("sigstop").unwrap(), libc::SIGSTOP);
+        assert!(name("NOPE").is_err());
+    }
+}
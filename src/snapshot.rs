@@ -0,0 +1,132 @@
+//! `enw snapshot save NAME` records the environment a command would get, inherited variables
+//! included, and `--from-snapshot NAME` gives a command that environment again in place of the
+//! inherited one, without loading the implicit env files. Snapshots are kept in
+//! `$XDG_STATE_HOME/enw/snapshots`, or `~/.local/state/enw/snapshots`, readable by the user
+//! only, as they hold secrets in the clear.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::{OsStr, OsString},
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{DirBuilderExt, OpenOptionsExt},
+    },
+    path::PathBuf,
+};
+
+use crate::BoxError;
+
+pub type Environment = BTreeMap<OsString, OsString>;
+
+fn dir() -> Result<PathBuf, BoxError> {
+    let dir = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .ok_or("could not determine the state directory, set XDG_STATE_HOME")?;
+    Ok(dir.join("enw").join("snapshots"))
+}
+
+fn path(name: &str) -> Result<PathBuf, BoxError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid snapshot name: {name:?}").into());
+    }
+    Ok(dir()?.join(name))
+}
+
+/// Save `env` as snapshot `name`, replacing an earlier one
+pub fn save(name: &str, env: &Environment) -> Result<(), BoxError> {
+    let path = path(name)?;
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(&encode(env))
+        .map_err(|e| format!("writing {}: {e}", path.to_string_lossy()).into())
+}
+
+pub fn load(name: &str) -> Result<Environment, BoxError> {
+    let path = path(name)?;
+    match fs::read(&path) {
+        Ok(data) => decode(&data).map_err(|e| format!("snapshot {name}: {e}").into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(format!("no snapshot {name}, save it with `enw snapshot save {name}`").into())
+        }
+        Err(e) => Err(format!("reading {}: {e}", path.to_string_lossy()).into()),
+    }
+}
+
+/// The names of the saved snapshots, sorted
+pub fn list() -> Result<Vec<String>, BoxError> {
+    let entries = match fs::read_dir(dir()?) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        entries => entries?,
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// `KEY=VALUE` entries terminated by NUL, like `env -0` prints them, so that any value fits
+fn encode(env: &Environment) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (key, value) in env {
+        out.extend_from_slice(key.as_bytes());
+        out.push(b'=');
+        out.extend_from_slice(value.as_bytes());
+        out.push(0);
+    }
+    out
+}
+
+fn decode(data: &[u8]) -> Result<Environment, BoxError> {
+    let mut env = Environment::new();
+    for entry in data.split(|&b| b == 0).filter(|entry| !entry.is_empty()) {
+        let at = entry
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or("damaged, an entry has no =")?;
+        env.insert(
+            OsStr::from_bytes(&entry[..at]).to_owned(),
+            OsStr::from_bytes(&entry[at + 1..]).to_owned(),
+        );
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let env: Environment = [
+            (OsStr::new("MULTI"), OsStr::new("a\nb=c")),
+            (OsStr::new("BYTES"), OsStr::from_bytes(b"caf\xe9")),
+            (OsStr::new("EMPTY"), OsStr::new("")),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
+        assert_eq!(
+            encode(&env),
+            b"BYTES=caf\xe9\0EMPTY=\0MULTI=a\nb=c\0".to_vec()
+        );
+        assert_eq!(decode(&encode(&env)).unwrap(), env);
+        assert!(decode(b"A=1\0B\0").is_err());
+        assert!(path("../x").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! JSON and YAML files as an env source. Nested keys are joined with `_` and upcased, like the
+//! sections of INI files: `{"database": {"url": …}}` becomes `DATABASE_URL`, and list items
+//! are numbered, `HOSTS_0`. Keys at the top level are taken as is.
+
+use crate::{json::Value, key_is_valid, BoxError};
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Join nested keys with this separator, keeping their case, e.g. `database.url`
+    pub key_separator: Option<String>,
+}
+
+pub fn parse_json(text: &str, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    match crate::json::parse(text) {
+        Ok(value) => flatten(&value, opts),
+        Err(e) => vec![Err(e)],
+    }
+}
+
+pub fn parse_yaml(text: &str, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    match crate::yaml::parse(text) {
+        Ok(value) => flatten(&value, opts),
+        Err(e) => vec![Err(e)],
+    }
+}
+
+fn flatten(value: &Value, opts: &Options) -> Vec<Result<(String, String), BoxError>> {
+    let Value::Object(map) = value else {
+        return vec![Err("expected an object of KEY: VALUE pairs".into())];
+    };
+    let mut out = Vec::new();
+    for (key, value) in map {
+        flatten_into(key.clone(), value, opts, &mut out);
+    }
+    out
+}
+
+fn flatten_into(
+    key: String,
+    value: &Value,
+    opts: &Options,
+    out: &mut Vec<Result<(String, String), BoxError>>,
+) {
+    let join = |child: &str| match &opts.key_separator {
+        Some(separator) => format!("{key}{separator}{child}"),
+        None => format!("{key}_{child}").to_uppercase(),
+    };
+    let value = match value {
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten_into(join(child), value, opts, out);
+            }
+            return;
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten_into(join(&i.to_string()), value, opts, out);
+            }
+            return;
+        }
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+    };
+    if key_is_valid(&key) {
+        out.push(Ok((key, value)));
+    } else {
+        out.push(Err(format!("KEY contains invalid characters: {key}").into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let json = r#"{
+            "PORT": 8080,
+            "debug": true,
+            "database": {"url": "postgres://db", "pool": {"size": 5}},
+            "hosts": ["a", "b"],
+            "empty": null
+        }"#;
+        let pairs = |opts| -> Vec<(String, String)> {
+            parse_json(json, &opts)
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let owned = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        assert_eq!(
+            pairs(Options::default()),
+            vec![
+                owned("PORT", "8080"),
+                owned("DATABASE_POOL_SIZE", "5"),
+                owned("DATABASE_URL", "postgres://db"),
+                owned("debug", "true"),
+                owned("empty", ""),
+                owned("HOSTS_0", "a"),
+                owned("HOSTS_1", "b"),
+            ]
+        );
+        let dotted = Options {
+            key_separator: Some(".".to_owned()),
+        };
+        assert_eq!(pairs(dotted)[1], owned("database.pool.size", "5"));
+        assert_eq!(
+            parse_yaml("app:\n  name: x\n", &Options::default())[0]
+                .as_ref()
+                .unwrap(),
+            &owned("APP_NAME", "x")
+        );
+        assert!(parse_json("[1]", &Options::default())[0].is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! `enw sync push|pull --remote URL` shares env files through an object storage bucket. Files
+//! are bundled, encrypted with `age` to the recipients listed in `.enw-recipients`, and stored
+//! as numbered versions next to a `HEAD` object holding the latest version number:
+//!
+//! ```text
+//! s3://bucket/project/HEAD
+//! s3://bucket/project/bundle-1.age
+//! s3://bucket/project/bundle-2.age
+//! ```
+//!
+//! The version and digest of the last push or pull are kept in `.enw-sync`, which is how
+//! changes made on both sides since then are detected. Buckets offer no compare-and-swap, so
+//! two pushes racing each other can still both succeed; the later one wins.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Component, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    crypt::{age_decrypt as decrypt, age_encrypt as encrypt, pipe},
+    sha256, BoxError,
+};
+
+pub const STATE_FILE_NAME: &str = ".enw-sync";
+const BUNDLE_HEADER: &str = "enw-bundle 1\n";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Remote {
+    S3(String),
+    Gcs(String),
+    /// A directory, e.g. on a shared drive
+    Dir(PathBuf),
+}
+
+impl Remote {
+    pub fn parse(url: &str) -> Result<Self, BoxError> {
+        let url = url.trim_end_matches('/');
+        if url.starts_with("s3://") {
+            Ok(Remote::S3(url.to_owned()))
+        } else if url.starts_with("gs://") {
+            Ok(Remote::Gcs(url.to_owned()))
+        } else if let Some(path) = url.strip_prefix("file://") {
+            Ok(Remote::Dir(PathBuf::from(path)))
+        } else {
+            Err(format!("unsupported remote, expected s3://, gs:// or file://: {url}").into())
+        }
+    }
+
+    /// The object's content, None if it does not exist
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, BoxError> {
+        let (program, args) = match self {
+            Remote::Dir(dir) => {
+                return match fs::read(dir.join(name)) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                    data => Ok(Some(data?)),
+                };
+            }
+            Remote::S3(url) => ("aws", ["s3", "cp", &format!("{url}/{name}"), "-"]),
+            Remote::Gcs(url) => ("gcloud", ["storage", "cp", &format!("{url}/{name}"), "-"]),
+        };
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("could not run {program}: {e}"))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            Ok(Some(output.stdout))
+        } else if ["404", "Not Found", "NoSuchKey", "No URLs matched"]
+            .iter()
+            .any(|marker| stderr.contains(marker))
+        {
+            Ok(None)
+        } else {
+            Err(format!("{program}: {}", stderr.trim()).into())
+        }
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), BoxError> {
+        let (program, args) = match self {
+            Remote::Dir(dir) => {
+                fs::create_dir_all(dir)?;
+                return Ok(fs::write(dir.join(name), data)?);
+            }
+            Remote::S3(url) => ("aws", ["s3", "cp", "-", &format!("{url}/{name}")]),
+            Remote::Gcs(url) => ("gcloud", ["storage", "cp", "-", &format!("{url}/{name}")]),
+        };
+        pipe(Command::new(program).args(args), data)?;
+        Ok(())
+    }
+
+    fn head(&self) -> Result<u64, BoxError> {
+        match self.get("HEAD")? {
+            None => Ok(0),
+            Some(data) => Ok(String::from_utf8(data)?.trim().parse()?),
+        }
+    }
+}
+
+/// Version and bundle digest as of the last push or pull
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct State {
+    version: u64,
+    digest: String,
+}
+
+impl State {
+    fn read() -> Result<Self, BoxError> {
+        let text = match fs::read_to_string(STATE_FILE_NAME) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(State::default()),
+            text => text?,
+        };
+        let mut state = State::default();
+        for line in text.lines() {
+            match line.split_once(' ') {
+                Some(("version", version)) => state.version = version.parse()?,
+                Some(("digest", digest)) => state.digest = digest.to_owned(),
+                _ => return Err(format!("{STATE_FILE_NAME}: unexpected line: {line}").into()),
+            }
+        }
+        Ok(state)
+    }
+
+    fn write(&self) -> Result<(), BoxError> {
+        let text = format!("version {}\ndigest {}\n", self.version, self.digest);
+        Ok(fs::write(STATE_FILE_NAME, text)?)
+    }
+}
+
+pub fn push(remote: &Remote, files: &[PathBuf], force: bool) -> Result<(), BoxError> {
+    let state = State::read()?;
+    let head = remote.head()?;
+    if head != state.version && !force {
+        return Err(format!(
+            "remote is at version {head}, but this copy was last synced at version {}; \
+             run `enw sync pull` first, or pass --force to overwrite",
+            state.version
+        )
+        .into());
+    }
+    let contents = files
+        .iter()
+        .map(|path| {
+            let data = fs::read(path).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+            Ok((path.clone(), data))
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+    let bundle = encode(&contents)?;
+    let digest = sha256::hex_digest(&bundle);
+    if head == state.version && digest == state.digest {
+        eprintln!("already up to date at version {head}");
+        return Ok(());
+    }
+    let version = head + 1;
+    remote.put(&format!("bundle-{version}.age"), &encrypt(&bundle)?)?;
+    remote.put("HEAD", format!("{version}\n").as_bytes())?;
+    State { version, digest }.write()?;
+    eprintln!("pushed version {version}");
+    Ok(())
+}
+
+/// Fetch the latest version, or the one given
+pub fn pull(remote: &Remote, version: Option<u64>, force: bool) -> Result<(), BoxError> {
+    let state = State::read()?;
+    let version = match version {
+        Some(version) => version,
+        None => remote.head()?,
+    };
+    if version == 0 {
+        return Err("nothing has been pushed to the remote yet".into());
+    }
+    let name = format!("bundle-{version}.age");
+    let encrypted = remote
+        .get(&name)?
+        .ok_or_else(|| format!("version {version} not found on the remote"))?;
+    let bundle = decrypt(&encrypted)?;
+    let files = decode(&bundle)?;
+    // Local edits since the last sync would be lost
+    let local: Vec<_> = files
+        .iter()
+        .filter_map(|(path, _)| Some((path.clone(), fs::read(path).ok()?)))
+        .collect();
+    let local_digest = sha256::hex_digest(&encode(&local)?);
+    let unchanged = local.is_empty() || local_digest == state.digest;
+    if !unchanged && !force && sha256::hex_digest(&bundle) != local_digest {
+        return Err(
+            "local env files changed since the last sync; push them first, or pass --force to \
+             overwrite them"
+                .into(),
+        );
+    }
+    for (path, data) in &files {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        // Only readable by the user when created, an existing file keeps its mode
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(data)?;
+    }
+    State {
+        version,
+        digest: sha256::hex_digest(&bundle),
+    }
+    .write()?;
+    eprintln!("pulled version {version}");
+    Ok(())
+}
+
+/// `file LENGTH PATH` headers, each followed by the content and a newline
+fn encode(files: &[(PathBuf, Vec<u8>)]) -> Result<Vec<u8>, BoxError> {
+    let mut out = BUNDLE_HEADER.as_bytes().to_vec();
+    for (path, data) in files {
+        let name = path.to_str().ok_or("file names must be valid UTF-8")?;
+        if name.contains('\n') {
+            return Err(format!("file name contains a newline: {name:?}").into());
+        }
+        out.extend_from_slice(format!("file {} {name}\n", data.len()).as_bytes());
+        out.extend_from_slice(data);
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn decode(bundle: &[u8]) -> Result<Vec<(PathBuf, Vec<u8>)>, BoxError> {
+    let mut rest = bundle
+        .strip_prefix(BUNDLE_HEADER.as_bytes())
+        .ok_or("not an enw bundle")?;
+    let mut files = Vec::new();
+    while !rest.is_empty() {
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("truncated bundle")?;
+        let header = std::str::from_utf8(&rest[..end])?;
+        let (len, name) = header
+            .strip_prefix("file ")
+            .and_then(|header| header.split_once(' '))
+            .ok_or_else(|| format!("invalid bundle entry: {header}"))?;
+        let len: usize = len.parse()?;
+        let path = PathBuf::from(name);
+        // Bundles come from other people, only accept paths below the current directory
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("refusing to write outside the current directory: {name}").into());
+        }
+        let data = rest.get(end + 1..end + 1 + len).ok_or("truncated bundle")?;
+        files.push((path, data.to_vec()));
+        rest = rest.get(end + 2 + len..).ok_or("truncated bundle")?;
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let files = vec![
+            (PathBuf::from(".env"), b"A=1\n".to_vec()),
+            (
+                PathBuf::from("config/.env.dev"),
+                b"B=2\nno newline".to_vec(),
+            ),
+            (PathBuf::from("empty"), Vec::new()),
+        ];
+        let bundle = encode(&files).unwrap();
+        assert_eq!(decode(&bundle).unwrap(), files);
+        assert!(decode(&bundle[..bundle.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_escaping_paths() {
+        for name in ["../.bashrc", "/etc/passwd"] {
+            let bundle = encode(&[(PathBuf::from(name), b"x".to_vec())]).unwrap();
+            assert!(decode(&bundle).is_err(), "expected err: {name}");
+        }
+    }
+
+    #[test]
+    fn test_remote_parse() {
+        assert_eq!(
+            Remote::parse("s3://bucket/project/").unwrap(),
+            Remote::S3("s3://bucket/project".into())
+        );
+        assert_eq!(
+            Remote::parse("gs://bucket/p").unwrap(),
+            Remote::Gcs("gs://bucket/p".into())
+        );
+        assert_eq!(
+            Remote::parse("file:///mnt/share").unwrap(),
+            Remote::Dir("/mnt/share".into())
+        );
+        assert!(Remote::parse("https://example.com").is_err());
+    }
+}
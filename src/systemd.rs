@@ -0,0 +1,107 @@
+//! `--systemd-run` launches COMMAND through `systemd-run` as a transient unit, for resource
+//! accounting, journald logging and cgroup limits. The variables are passed as `--setenv=KEY`,
+//! which makes systemd-run read the values from its own environment, keeping them off the
+//! command line.
+
+use std::{
+    ffi::{OsStr, OsString},
+    str::FromStr,
+};
+
+use crate::{BoxError, Var};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// Run in the foreground as a child of systemd-run, in a scope unit
+    Scope,
+    /// Run as a service, waiting for it and connecting its stdio to ours
+    Service,
+}
+
+impl Unit {
+    pub const NAMES: &'static [&'static str] = &["scope", "service"];
+}
+
+impl FromStr for Unit {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scope" => Ok(Unit::Scope),
+            "service" => Ok(Unit::Service),
+            _ => Err(format!("unknown unit type: {s}").into()),
+        }
+    }
+}
+
+/// The arguments to systemd-run for running `command`
+pub fn args(
+    unit: Unit,
+    user: bool,
+    properties: &[String],
+    vars: &[Var],
+    command: &OsStr,
+    args: &[OsString],
+) -> Vec<OsString> {
+    let mut out = vec!["--quiet".into(), "--collect".into()];
+    if user {
+        out.push("--user".into());
+    }
+    match unit {
+        Unit::Scope => out.push("--scope".into()),
+        Unit::Service => {
+            out.push("--wait".into());
+            out.push("--pipe".into());
+        }
+    }
+    for property in properties {
+        out.push(format!("--property={property}").into());
+    }
+    for var in vars {
+        out.push(format!("--setenv={}", var.key).into());
+    }
+    out.push("--".into());
+    out.push(command.to_owned());
+    out.extend(args.iter().cloned());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Origin;
+
+    #[test]
+    fn test_args() {
+        let vars = vec![Var::new(
+            "DB_PASSWORD".into(),
+            "hunter2".into(),
+            Origin::CommandLine,
+        )];
+        assert_eq!(
+            args(
+                Unit::Service,
+                true,
+                &["MemoryMax=1G".into()],
+                &vars,
+                OsStr::new("server"),
+                &["--port".into(), "80".into()],
+            ),
+            vec![
+                "--quiet",
+                "--collect",
+                "--user",
+                "--wait",
+                "--pipe",
+                "--property=MemoryMax=1G",
+                "--setenv=DB_PASSWORD",
+                "--",
+                "server",
+                "--port",
+                "80",
+            ]
+        );
+    }
+}
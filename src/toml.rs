@@ -0,0 +1,265 @@
+//! Minimal TOML support for enw's own configuration: tables, strings, integers, booleans and
+//! arrays. Dates, floats, inline tables and arrays of tables are not supported.
+
+use std::{collections::BTreeMap, iter::Peekable, str::Chars};
+
+use crate::BoxError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The strings of an array, None if it is not an array of strings
+    pub fn as_strings(&self) -> Option<Vec<String>> {
+        match self {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_owned))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Keys and values of each table, by dotted table name. Keys before the first header belong to
+/// the table named `""`.
+pub type Document = BTreeMap<String, BTreeMap<String, Value>>;
+
+pub fn parse(text: &str) -> Result<Document, BoxError> {
+    let mut doc = Document::new();
+    let mut table = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    loop {
+        skip_blank(&mut chars, &mut line);
+        let Some(&c) = chars.peek() else {
+            return Ok(doc);
+        };
+        let at = move |e: BoxError| -> BoxError { format!("line {line}: {e}").into() };
+        if c == '[' {
+            chars.next();
+            table = parse_key(&mut chars).map_err(at)?;
+            skip_spaces(&mut chars);
+            if chars.next() != Some(']') {
+                return Err(at("expected ] after table name".into()));
+            }
+            if doc.contains_key(&table) {
+                return Err(at(format!("table {table} defined twice").into()));
+            }
+            doc.insert(table.clone(), BTreeMap::new());
+        } else {
+            let key = parse_key(&mut chars).map_err(at)?;
+            skip_spaces(&mut chars);
+            if chars.next() != Some('=') {
+                return Err(at(format!("expected = after {key}").into()));
+            }
+            skip_spaces(&mut chars);
+            let value = parse_value(&mut chars, &mut line).map_err(at)?;
+            let (table, key) = match key.rsplit_once('.') {
+                Some((sub, key)) if table.is_empty() => (sub.to_owned(), key.to_owned()),
+                Some((sub, key)) => (format!("{table}.{sub}"), key.to_owned()),
+                None => (table.clone(), key),
+            };
+            let entries = doc.entry(table).or_default();
+            if entries.insert(key.clone(), value).is_some() {
+                return Err(at(format!("{key} defined twice").into()));
+            }
+        }
+        // Only a comment may follow on the same line
+        skip_spaces(&mut chars);
+        match chars.peek() {
+            None | Some('\n') | Some('\r') | Some('#') => {}
+            Some(c) => return Err(format!("line {line}: unexpected {c:?}").into()),
+        }
+    }
+}
+
+/// A dotted key, each part bare or quoted
+fn parse_key(chars: &mut Peekable<Chars>) -> Result<String, BoxError> {
+    let mut parts = Vec::new();
+    loop {
+        skip_spaces(chars);
+        let part = match chars.peek() {
+            Some('"') | Some('\'') => parse_string(chars)?,
+            _ => {
+                let mut part = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        break;
+                    }
+                    part.push(c);
+                    chars.next();
+                }
+                if part.is_empty() {
+                    return Err("expected a key".into());
+                }
+                part
+            }
+        };
+        parts.push(part);
+        skip_spaces(chars);
+        if chars.peek() != Some(&'.') {
+            return Ok(parts.join("."));
+        }
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, line: &mut usize) -> Result<Value, BoxError> {
+    match chars.peek() {
+        Some('"') | Some('\'') => parse_string(chars).map(Value::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_blank(chars, line);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Value::Array(items));
+                }
+                items.push(parse_value(chars, line)?);
+                skip_blank(chars, line);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("expected , or ] in array".into()),
+                }
+            }
+        }
+        _ => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => word
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| format!("unsupported value: {word:?}").into()),
+            }
+        }
+    }
+}
+
+/// A basic string, with escapes, or a literal string
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, BoxError> {
+    let quote = chars.next();
+    let mut out = String::new();
+    loop {
+        match (quote, chars.next()) {
+            (_, None) | (_, Some('\n')) => return Err("unterminated string".into()),
+            (q, Some(c)) if q == Some(c) => return Ok(out),
+            (Some('"'), Some('\\')) => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid escape: \\u{hex}"))?;
+                    out.push(c);
+                }
+                c => return Err(format!("invalid escape: {c:?}").into()),
+            },
+            (_, Some(c)) => out.push(c),
+        }
+    }
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+}
+
+/// Whitespace, newlines and comments
+fn skip_blank(chars: &mut Peekable<Chars>, line: &mut usize) {
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => *line += 1,
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            c if c.is_whitespace() => {}
+            _ => return,
+        }
+        chars.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let doc = parse(
+            r#"
+# comment
+name = "app"
+
+[profiles.dev]
+extends = 'base'  # trailing comment
+files = [
+  ".env.dev",
+  "secrets/\"dev\".env",
+]
+vars.PORT = "8080"
+debug = true
+workers = 1_000
+"#,
+        )
+        .unwrap();
+        assert_eq!(doc[""]["name"], Value::String("app".into()));
+        let dev = &doc["profiles.dev"];
+        assert_eq!(dev["extends"].as_str(), Some("base"));
+        assert_eq!(
+            dev["files"].as_strings(),
+            Some(vec![".env.dev".into(), "secrets/\"dev\".env".into()])
+        );
+        assert_eq!(dev["debug"], Value::Bool(true));
+        assert_eq!(dev["workers"], Value::Integer(1000));
+        assert_eq!(
+            doc["profiles.dev.vars"]["PORT"],
+            Value::String("8080".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "a = ",
+            "a = \"x",
+            "[a",
+            "a = 1\na = 2",
+            "a = [1 2]",
+            "a = 1 b",
+        ] {
+            assert!(parse(input).is_err(), "expected err: {input}");
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! `enw trace-usage -- COMMAND` runs COMMAND with the enw-trace library preloaded, and reports
+//! which of the resolved variables it looked up.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process::{self, Command},
+};
+
+use crate::{forward::Forwarding, BoxError, Var};
+
+const LIBRARY_NAME: &str = "libenw_trace.so";
+
+/// The interposer library: `ENW_TRACE_LIBRARY`, or next to the enw executable
+fn library() -> Result<PathBuf, BoxError> {
+    let path = match env::var_os("ENW_TRACE_LIBRARY") {
+        Some(path) => PathBuf::from(path),
+        None => env::current_exe()?.with_file_name(LIBRARY_NAME),
+    };
+    if !path.is_file() {
+        return Err(format!(
+            "{} not found, build it in a checkout of enw with `cargo build --release -p \
+             enw-trace` and copy it there, or set ENW_TRACE_LIBRARY",
+            path.to_string_lossy()
+        )
+        .into());
+    }
+    Ok(path)
+}
+
+/// Run the command in `child_env`, print which of the resolved variables it read to stderr and
+/// return its exit code
+pub fn run(
+    vars: &[Var],
+    child_env: BTreeMap<OsString, OsString>,
+    argv: &[OsString],
+) -> Result<i32, BoxError> {
+    let (command, args) = argv.split_first().ok_or("trace-usage expects a COMMAND")?;
+    let library = library()?;
+    let trace_file = env::temp_dir().join(format!("enw-trace-{}", process::id()));
+    // Created afresh and private, as the name in the shared temporary directory is predictable
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&trace_file)
+        .map_err(|e| format!("{}: {e}", trace_file.to_string_lossy()))?;
+    let mut preload = library.into_os_string();
+    if let Some(existing) = env::var_os("LD_PRELOAD").filter(|v| !v.is_empty()) {
+        preload.push(":");
+        preload.push(existing);
+    }
+    let forwarding = Forwarding::install(None)?;
+    let status = Command::new(command)
+        .args(args)
+        .env_clear()
+        .envs(child_env)
+        .env("LD_PRELOAD", preload)
+        .env("ENW_TRACE_FILE", &trace_file)
+        .spawn()
+        .and_then(|mut child| forwarding.wait(&mut child))
+        .map_err(|e| format!("{}: {e}", command.to_string_lossy()));
+    let trace = fs::read_to_string(&trace_file);
+    fs::remove_file(&trace_file)?;
+    let status = status?;
+    let looked_up: BTreeSet<_> = trace?.lines().map(str::to_owned).collect();
+    let (read, unread): (Vec<_>, Vec<_>) = vars
+        .iter()
+        .map(|var| var.key.as_str())
+        .partition(|key| looked_up.contains(*key));
+    eprintln!("read: {}", read.join(", "));
+    eprintln!("never read: {}", unread.join(", "));
+    Ok(crate::exit_code(status))
+}
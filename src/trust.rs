@@ -0,0 +1,61 @@
+//! The trust allowlist: env files, and `.enw.toml` files, the user has vouched for with
+//! `enw trust FILE`. An entry records the content hash, so any change to a file revokes its
+//! trust.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{config_dir, sha256, BoxError};
+
+fn allowlist_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("trusted"))
+}
+
+/// Entries are `SHA256 PATH` lines
+fn read_allowlist() -> Vec<(String, PathBuf)> {
+    allowlist_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (hash, path) = line.split_once(' ')?;
+            Some((hash.to_owned(), PathBuf::from(path)))
+        })
+        .collect()
+}
+
+/// Whether the file at `path` is trusted with `content`, its bytes as read from disk, i.e.
+/// before any decryption, as hashed by [`trust`]
+pub fn is_trusted(path: &Path, content: &[u8]) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    let hash = sha256::hex_digest(content);
+    read_allowlist()
+        .iter()
+        .any(|(trusted_hash, trusted_path)| *trusted_path == path && *trusted_hash == hash)
+}
+
+/// Add files to the allowlist, replacing earlier entries for the same paths
+pub fn trust(paths: &[PathBuf]) -> Result<(), BoxError> {
+    let allowlist = allowlist_path().ok_or("could not determine config directory")?;
+    let mut entries = read_allowlist();
+    for path in paths {
+        let path =
+            fs::canonicalize(path).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        let hash = sha256::hex_digest(&fs::read(&path)?);
+        entries.retain(|(_, trusted_path)| *trusted_path != path);
+        entries.push((hash, path));
+    }
+    if let Some(dir) = allowlist.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::File::create(&allowlist)?;
+    for (hash, path) in entries {
+        writeln!(file, "{hash} {}", path.to_string_lossy())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+//! `enw self-update` replaces the running binary with the latest GitHub release. Releases carry
+//! a `SHA256SUMS` file signed with minisign; the binary is only installed when the signature
+//! and its checksum check out.
+
+use std::{
+    cmp::Ordering,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    process::Command,
+};
+
+use crate::{http, json, sha256, BoxError};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/ramn/enw/releases";
+const SUMS_FILE_NAME: &str = "SHA256SUMS";
+/// The release signing key, embedded by release builds
+const RELEASE_KEY: Option<&str> = option_env!("ENW_RELEASE_KEY");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    /// Pre-releases as well
+    Beta,
+}
+
+impl Channel {
+    pub const NAMES: &'static [&'static str] = &["stable", "beta"];
+}
+
+impl std::str::FromStr for Channel {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Channel::Stable),
+            "beta" => Ok(Channel::Beta),
+            _ => Err(format!("unknown channel: {s}").into()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Release {
+    tag: String,
+    /// Asset names and download URLs
+    assets: Vec<(String, String)>,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Result<&str, BoxError> {
+        self.assets
+            .iter()
+            .find(|(asset, _)| asset == name)
+            .map(|(_, url)| url.as_str())
+            .ok_or_else(|| format!("release {} has no {name}", self.tag).into())
+    }
+}
+
+/// `key` is a minisign public key file, needed when the build has no key embedded
+pub fn self_update(channel: Channel, check: bool, key: Option<&Path>) -> Result<(), BoxError> {
+    let current = env!("CARGO_PKG_VERSION");
+    let feed = http::get(RELEASES_URL, &[("Accept", "application/vnd.github+json")])?;
+    let release = latest(&feed, channel)?.ok_or("no releases found")?;
+    if compare_versions(&release.tag, current) != Ordering::Greater {
+        eprintln!("enw {current} is up to date");
+        return Ok(());
+    }
+    if check {
+        println!("enw {} is available, this is {current}", release.tag);
+        return Ok(());
+    }
+    let key_arg = match (key, RELEASE_KEY) {
+        (Some(path), _) => vec!["-p".to_owned(), path.to_string_lossy().into_owned()],
+        (None, Some(key)) => vec!["-P".to_owned(), key.to_owned()],
+        (None, None) => {
+            return Err("this build has no release key embedded, pass --key FILE".into());
+        }
+    };
+    let asset_name = format!("enw-{}-{}", env::consts::ARCH, env::consts::OS);
+    let opts = http::Options::default();
+    let sums = http::get_bytes_with(release.asset(SUMS_FILE_NAME)?, &[], &opts)?;
+    let sig_name = format!("{SUMS_FILE_NAME}.minisig");
+    let sig = http::get_bytes_with(release.asset(&sig_name)?, &[], &opts)?;
+    let binary = http::get_bytes_with(release.asset(&asset_name)?, &[], &opts)?;
+
+    let exe = env::current_exe()?.canonicalize()?;
+    let dir = exe
+        .parent()
+        .ok_or("could not find the executable's directory")?;
+    // Staged next to the executable, so that the final rename stays on one file system
+    let staged = |suffix: &str| dir.join(format!(".enw-update-{}{suffix}", std::process::id()));
+    let (sums_path, sig_path, new_exe) = (staged(".sums"), staged(".sums.minisig"), staged(""));
+    let result = (|| -> Result<(), BoxError> {
+        fs::write(&sums_path, &sums)?;
+        fs::write(&sig_path, &sig)?;
+        let output = Command::new("minisign")
+            .arg("-Vq")
+            .arg("-m")
+            .arg(&sums_path)
+            .arg("-x")
+            .arg(&sig_path)
+            .args(&key_arg)
+            .output()
+            .map_err(|e| format!("could not run minisign: {e}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{SUMS_FILE_NAME} signature verification failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let expected = checksum(&String::from_utf8(sums.clone())?, &asset_name)?;
+        if sha256::hex_digest(&binary) != expected {
+            return Err(format!("{asset_name} does not match its checksum").into());
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o755)
+            .open(&new_exe)?
+            .write_all(&binary)?;
+        fs::rename(&new_exe, &exe)?;
+        Ok(())
+    })();
+    for path in [&sums_path, &sig_path, &new_exe] {
+        let _ = fs::remove_file(path);
+    }
+    result?;
+    eprintln!("updated enw {current} to {}", release.tag);
+    Ok(())
+}
+
+/// The highest versioned release on the channel, drafts excluded
+fn latest(feed: &str, channel: Channel) -> Result<Option<Release>, BoxError> {
+    let doc = json::parse(feed)?;
+    let releases = doc.as_array().ok_or("unexpected release feed")?;
+    Ok(releases
+        .iter()
+        .filter(|release| release.get("draft") != Some(&json::Value::Bool(true)))
+        .filter(|release| {
+            channel == Channel::Beta || release.get("prerelease") != Some(&json::Value::Bool(true))
+        })
+        .filter_map(|release| {
+            let tag = release.get("tag_name")?.as_str()?.to_owned();
+            let assets = release
+                .get("assets")?
+                .as_array()?
+                .iter()
+                .filter_map(|asset| {
+                    let name = asset.get("name")?.as_str()?;
+                    let url = asset.get("browser_download_url")?.as_str()?;
+                    Some((name.to_owned(), url.to_owned()))
+                })
+                .collect();
+            Some(Release { tag, assets })
+        })
+        .max_by(|a, b| compare_versions(&a.tag, &b.tag)))
+}
+
+/// The digest of `name` in a `sha256sum` style listing
+fn checksum(sums: &str, name: &str) -> Result<String, BoxError> {
+    sums.lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim_start().trim_start_matches('*') == name)
+        .map(|(digest, _)| digest.to_lowercase())
+        .ok_or_else(|| format!("{SUMS_FILE_NAME} has no entry for {name}").into())
+}
+
+/// Semantic versions, with or without a leading `v`. A pre-release sorts before its release.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| {
+        let v = v.trim_start_matches('v');
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_owned())),
+            None => (v, None),
+        };
+        let numbers: Vec<u64> = core.split('.').map(|n| n.parse().unwrap_or(0)).collect();
+        (numbers, pre)
+    };
+    let (a_numbers, a_pre) = parse(a);
+    let (b_numbers, b_pre) = parse(b);
+    a_numbers.cmp(&b_numbers).then(match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(&b),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const FEED: &str = r#"[
+        {"tag_name": "v0.8.0-beta.1", "prerelease": true, "draft": false, "assets": []},
+        {"tag_name": "v0.9.0", "prerelease": false, "draft": true, "assets": []},
+        {"tag_name": "v0.7.0", "prerelease": false, "draft": false, "assets": [
+            {"name": "SHA256SUMS", "browser_download_url": "https://example.com/sums"}
+        ]},
+        {"tag_name": "v0.6.0", "prerelease": false, "draft": false, "assets": []}
+    ]"#;
+
+    #[test]
+    fn test_latest() {
+        let stable = latest(FEED, Channel::Stable).unwrap().unwrap();
+        assert_eq!(stable.tag, "v0.7.0");
+        assert_eq!(
+            stable.asset("SHA256SUMS").unwrap(),
+            "https://example.com/sums"
+        );
+        let beta = latest(FEED, Channel::Beta).unwrap().unwrap();
+        assert_eq!(beta.tag, "v0.8.0-beta.1");
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("v0.10.0", "0.9.1"), Ordering::Greater);
+        assert_eq!(compare_versions("0.7.0-rc.1", "0.7.0"), Ordering::Less);
+        assert_eq!(compare_versions("v0.6.0", "0.6.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let sums = "ABC123  enw-x86_64-linux\ndef456 *enw-aarch64-macos\n";
+        assert_eq!(checksum(sums, "enw-x86_64-linux").unwrap(), "abc123");
+        assert_eq!(checksum(sums, "enw-aarch64-macos").unwrap(), "def456");
+        assert!(checksum(sums, "enw-x86_64-windows").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Detached signatures for shared env files. A file is signed with either
+//!
+//! * `ssh-keygen -Y sign -f KEY -n enw FILE`, producing `FILE.sig`, checked against an
+//!   allowed signers file, or
+//! * `minisign -S -m FILE`, producing `FILE.minisig`, checked against a minisign public key.
+//!
+//! Keys default to `allowed_signers` and `minisign.pub` in the enw config directory.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+use crate::{config_dir, BoxError};
+
+const SSH_NAMESPACE: &str = "enw";
+
+#[derive(Clone, Debug, Default)]
+pub struct Keys {
+    pub allowed_signers: Option<PathBuf>,
+    pub minisign_key: Option<PathBuf>,
+}
+
+impl Keys {
+    fn allowed_signers(&self) -> Option<PathBuf> {
+        self.allowed_signers
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join("allowed_signers")))
+            .filter(|path| path.is_file())
+    }
+
+    fn minisign_key(&self) -> Option<PathBuf> {
+        self.minisign_key
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join("minisign.pub")))
+            .filter(|path| path.is_file())
+    }
+}
+
+/// Check that `content`, the bytes read from `path` before any decryption, carries a valid
+/// signature from a trusted key
+pub fn verify(path: &Path, content: &[u8], keys: &Keys) -> Result<(), BoxError> {
+    let name = path.to_string_lossy();
+    let ssh_sig = with_suffix(path, ".sig");
+    let minisig = with_suffix(path, ".minisig");
+    if ssh_sig.is_file() {
+        let signers = keys.allowed_signers().ok_or_else(|| {
+            format!("{name}: no allowed signers file to verify {SSH_NAMESPACE} signature with")
+        })?;
+        verify_ssh(&ssh_sig, &signers, content)
+            .map_err(|e| format!("{name}: signature verification failed: {e}").into())
+    } else if minisig.is_file() {
+        let key = keys
+            .minisign_key()
+            .ok_or_else(|| format!("{name}: no minisign public key to verify signature with"))?;
+        verify_minisign(content, &minisig, &key)
+            .map_err(|e| format!("{name}: signature verification failed: {e}").into())
+    } else {
+        Err(format!("{name}: no signature found ({name}.sig or {name}.minisig)").into())
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+fn verify_ssh(sig: &Path, signers: &Path, content: &[u8]) -> Result<(), BoxError> {
+    let output = Command::new("ssh-keygen")
+        .args(["-Y", "find-principals", "-s"])
+        .arg(sig)
+        .arg("-f")
+        .arg(signers)
+        .output()
+        .map_err(|e| format!("could not run ssh-keygen: {e}"))?;
+    let principals = String::from_utf8_lossy(&output.stdout);
+    let principal = principals
+        .lines()
+        .next()
+        .filter(|_| output.status.success())
+        .ok_or("signed by a key that is not in the allowed signers")?;
+    let mut child = Command::new("ssh-keygen")
+        .args(["-Y", "verify", "-n", SSH_NAMESPACE, "-I", principal, "-s"])
+        .arg(sig)
+        .arg("-f")
+        .arg(signers)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("could not open ssh-keygen stdin")?
+        .write_all(content)?;
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_owned()
+            .into())
+    }
+}
+
+/// minisign only reads messages from files, so `content` is written to a private one, rather
+/// than letting minisign read the env file again, which may have changed since
+fn verify_minisign(content: &[u8], sig: &Path, key: &Path) -> Result<(), BoxError> {
+    let message = env::temp_dir().join(format!("enw-verify-{}", process::id()));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&message)
+        .map_err(|e| format!("{}: {e}", message.to_string_lossy()))?;
+    let output = file.write_all(content).and_then(|()| {
+        Command::new("minisign")
+            .arg("-Vq")
+            .arg("-m")
+            .arg(&message)
+            .arg("-x")
+            .arg(sig)
+            .arg("-p")
+            .arg(key)
+            .output()
+    });
+    fs::remove_file(&message)?;
+    let output = output.map_err(|e| format!("could not run minisign: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_owned()
+            .into())
+    }
+}
@@ -0,0 +1,337 @@
+//! `enw --watch COMMAND` supervises the command, restarting it with a freshly loaded
+//! environment whenever one of the loaded env files, or another watched path, changes. Watched
+//! directories are walked, skipping paths matching a `--watch-ignore` pattern. Files are
+//! polled, and a change only counts once the files have stopped changing, so that an editor
+//! writing a file in several steps causes a single restart.
+//!
+//! `--watch-strategy` picks what a change does while the command runs: `restart` it, send it
+//! `--watch-signal` and let it reload by itself, or `queue` the restart until it exits.
+//! `--on-change HOOK` instead runs HOOK with the new environment, leaving the command be.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Child,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::ArgMatches;
+
+use crate::{
+    color, exit_code, forward::Forwarding, glob, pipeline::Pipeline, prepare_command,
+    print_warnings, resolve, secret_file, signal, BoxError, OptionsBuilder,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long files must stay unchanged before the command is restarted
+const SETTLE_TIME: Duration = Duration::from_millis(400);
+/// How long to wait for the command to exit after SIGTERM, unless --kill-timeout is given
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Stamps = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+/// What to do when files change while the command runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Strategy {
+    /// Stop the command and start it again
+    Restart,
+    /// Send the command a signal, and keep it running
+    Signal(libc::c_int),
+    /// Start the command again once it exits
+    Queue,
+    /// Run a shell command with the new environment, and keep the command running
+    Hook(String),
+}
+
+pub const STRATEGIES: &[&str] = &["restart", "signal", "queue"];
+
+impl Strategy {
+    fn from_matches(matches: &ArgMatches) -> Result<Self, BoxError> {
+        if let Some(hook) = matches.value_of("on_change") {
+            return Ok(Strategy::Hook(hook.to_owned()));
+        }
+        Ok(
+            match matches.value_of("watch_strategy").unwrap_or_default() {
+                "signal" => Strategy::Signal(signal::name(
+                    matches.value_of("watch_signal").unwrap_or_default(),
+                )?),
+                "queue" => Strategy::Queue,
+                _ => Strategy::Restart,
+            },
+        )
+    }
+}
+
+/// Run the command until enw is signalled, returning its last exit code
+pub fn run(matches: &ArgMatches<'static>, pipeline: &Pipeline) -> Result<i32, BoxError> {
+    let extra: Vec<PathBuf> = matches
+        .values_of_os("watch_path")
+        .map(|paths| paths.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    let ignore: Vec<String> = matches.values_of_lossy("watch_ignore").unwrap_or_default();
+    let strategy = Strategy::from_matches(matches)?;
+    // Checked here, as a command that fails to start is retried once the env files change
+    if matches
+        .values_of_os("rest")
+        .is_some_and(|mut rest| rest.any(|arg| arg == "--and"))
+    {
+        return Err("--and cannot be used with --watch".into());
+    }
+    let kill_timeout = matches
+        .value_of("kill_timeout")
+        .map(str::parse::<u32>)
+        .transpose()?;
+    let color: color::Choice = matches.value_of("color").unwrap_or_default().parse()?;
+    let warn = |message: String| print_warnings(!matches.is_present("quiet"), color, vec![message]);
+    let forwarding = Forwarding::install(kill_timeout)?;
+    let _secret_files = secret_file::Cleanup;
+    'restart: loop {
+        let mut watched = extra.clone();
+        match start(matches, pipeline, &forwarding, &mut watched, &ignore) {
+            Ok((mut child, mut initial)) => {
+                let mut queued = false;
+                let status = loop {
+                    if let Some(status) = child.try_wait()? {
+                        break status;
+                    }
+                    if stamps(&watched, &ignore) != initial {
+                        settle(&watched, &ignore);
+                        match &strategy {
+                            Strategy::Restart => {
+                                warn("env files changed, restarting".to_owned());
+                                stop(&mut child, kill_timeout)?;
+                                forwarding.detach();
+                                // Secrets may have been renamed or removed from the env files
+                                secret_file::remove_files()?;
+                                continue 'restart;
+                            }
+                            Strategy::Signal(signal) => {
+                                warn("env files changed, signalling the command".to_owned());
+                                // SAFETY: no preconditions
+                                unsafe { libc::kill(child.id() as i32, *signal) };
+                            }
+                            Strategy::Queue if !queued => {
+                                warn(
+                                    "env files changed, restarting once the command exits"
+                                        .to_owned(),
+                                );
+                                queued = true;
+                            }
+                            Strategy::Queue => {}
+                            Strategy::Hook(hook) => {
+                                match run_hook(matches, pipeline, hook, &mut watched) {
+                                    Ok(0) => {}
+                                    Ok(code) => warn(format!("{hook} exited with {code}")),
+                                    Err(e) => warn(e.to_string()),
+                                }
+                            }
+                        }
+                        initial = stamps(&watched, &ignore);
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                };
+                forwarding.detach();
+                if forwarding.received().is_some() {
+                    return Ok(exit_code(status));
+                }
+                if queued {
+                    secret_file::remove_files()?;
+                    continue 'restart;
+                }
+                warn(format!(
+                    "command exited with {}, waiting for changes",
+                    exit_code(status)
+                ));
+            }
+            // A broken env file is reported, and fixing it restarts the command
+            Err(e) => warn(e.to_string()),
+        }
+        if let Some(signal) = wait_for_change(&watched, &ignore, &forwarding) {
+            return Ok(128 + signal);
+        }
+    }
+}
+
+/// Wait until a file changes, or enw is signalled, returning the signal
+fn wait_for_change(paths: &[PathBuf], ignore: &[String], forwarding: &Forwarding) -> Option<i32> {
+    let initial = stamps(paths, ignore);
+    while stamps(paths, ignore) == initial {
+        if let Some(signal) = forwarding.received() {
+            return Some(signal);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    settle(paths, ignore);
+    None
+}
+
+/// Load the environment and spawn the command, adding the loaded files to `watched`. Their
+/// stamps are taken before the command starts, so changes made once it runs are not missed.
+fn start(
+    matches: &ArgMatches<'static>,
+    pipeline: &Pipeline,
+    forwarding: &Forwarding,
+    watched: &mut Vec<PathBuf>,
+    ignore: &[String],
+) -> Result<(Child, Stamps), BoxError> {
+    let opt_builder = OptionsBuilder::with_arg_matches(matches.clone())?;
+    // Files that fail to load are watched too
+    watched.extend(opt_builder.env_files.iter().map(|file| file.path.clone()));
+    let ctx = resolve(opt_builder, pipeline)?;
+    watched.extend(ctx.files.iter().cloned());
+    let command = ctx.opts.command.clone().ok_or("--watch needs a command")?;
+    let mut cmd = prepare_command(
+        &ctx.opts,
+        &command,
+        &ctx.opts.args,
+        &ctx.vars,
+        ctx.unset.clone(),
+    )?;
+    let initial = stamps(watched, ignore);
+    let child = cmd
+        .spawn()
+        .map_err(|e| format!("{}: {e}", command.to_string_lossy()))?;
+    forwarding.attach(&child);
+    Ok((child, initial))
+}
+
+/// Run `hook` with `sh -c` in a freshly loaded environment, returning its exit code. Files it
+/// loads are watched from now on.
+fn run_hook(
+    matches: &ArgMatches<'static>,
+    pipeline: &Pipeline,
+    hook: &str,
+    watched: &mut Vec<PathBuf>,
+) -> Result<i32, BoxError> {
+    let ctx = resolve(OptionsBuilder::with_arg_matches(matches.clone())?, pipeline)?;
+    for file in &ctx.files {
+        if !watched.contains(file) {
+            watched.push(file.clone());
+        }
+    }
+    let args = [OsString::from("-c"), OsString::from(hook)];
+    let mut cmd = prepare_command(
+        &ctx.opts,
+        OsStr::new("sh"),
+        &args,
+        &ctx.vars,
+        ctx.unset.clone(),
+    )?;
+    Ok(exit_code(cmd.status()?))
+}
+
+fn stamps(paths: &[PathBuf], ignore: &[String]) -> Stamps {
+    let mut out = Vec::new();
+    for path in paths {
+        collect(path, ignore, true, &mut out);
+    }
+    out
+}
+
+/// Stamp the file at `path`, or every file under it if it is a directory. Symbolic links are
+/// followed only for the paths given, so that links cannot make the walk loop.
+fn collect(path: &Path, ignore: &[String], follow: bool, out: &mut Stamps) {
+    if is_ignored(path, ignore) {
+        return;
+    }
+    let metadata = match follow {
+        true => fs::metadata(path),
+        false => fs::symlink_metadata(path),
+    };
+    match metadata {
+        Ok(metadata) if metadata.is_dir() => {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .collect();
+            entries.sort();
+            for entry in entries {
+                collect(&entry, ignore, false, out);
+            }
+        }
+        metadata => {
+            let stamp = metadata
+                .ok()
+                .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+            out.push((path.to_owned(), stamp));
+        }
+    }
+}
+
+/// Whether a pattern matches the file name, or the whole path
+fn is_ignored(path: &Path, ignore: &[String]) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy());
+    let path = path.to_string_lossy();
+    ignore.iter().any(|pattern| {
+        name.as_deref()
+            .is_some_and(|name| glob::matches(pattern, name))
+            || glob::matches(pattern, &path)
+    })
+}
+
+/// Wait until the files have not changed for a while
+fn settle(paths: &[PathBuf], ignore: &[String]) {
+    let mut last = stamps(paths, ignore);
+    let mut since = Instant::now();
+    while since.elapsed() < SETTLE_TIME {
+        thread::sleep(POLL_INTERVAL / 2);
+        let current = stamps(paths, ignore);
+        if current != last {
+            last = current;
+            since = Instant::now();
+        }
+    }
+}
+
+/// Ask the command to exit with SIGTERM, killing it if it has not after the timeout
+fn stop(child: &mut Child, kill_timeout: Option<u32>) -> io::Result<()> {
+    // SAFETY: no preconditions
+    unsafe { libc::kill(child.id() as i32, libc::SIGTERM) };
+    let timeout = kill_timeout.map_or(DEFAULT_STOP_TIMEOUT, |secs| {
+        Duration::from_secs(secs.into())
+    });
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL / 4);
+    }
+    child.kill()?;
+    child.wait().map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_stamps() {
+        let dir = std::env::temp_dir().join(format!("enw-watch-stamps-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/target")).unwrap();
+        for file in ["src/main.rs", "src/main.rs.tmp", "src/target/out", ".env"] {
+            fs::write(dir.join(file), "x").unwrap();
+        }
+        let ignore = ["*.tmp".to_owned(), "target".to_owned()];
+        let paths = [dir.join(".env"), dir.join("src"), dir.join("missing")];
+        let watched: Vec<_> = stamps(&paths, &ignore)
+            .into_iter()
+            .map(|(path, stamp)| (path, stamp.is_some()))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            watched,
+            vec![
+                (dir.join(".env"), true),
+                (dir.join("src/main.rs"), true),
+                (dir.join("missing"), false),
+            ]
+        );
+    }
+}
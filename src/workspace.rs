@@ -0,0 +1,82 @@
+//! Monorepo support: inside a cargo, npm, yarn or pnpm workspace the .env file of the
+//! workspace root is loaded beneath the one of the package being worked on.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Workspace {
+    pub root: PathBuf,
+    /// The nearest package directory, at or above the starting directory
+    pub package: PathBuf,
+}
+
+/// Find the workspace `dir` is in, if any
+pub fn find(dir: &Path) -> Option<Workspace> {
+    let package = dir.ancestors().find(|dir| is_package(dir))?;
+    let root = package.ancestors().find(|dir| is_workspace_root(dir))?;
+    Some(Workspace {
+        root: root.to_owned(),
+        package: package.to_owned(),
+    })
+}
+
+fn is_package(dir: &Path) -> bool {
+    dir.join("package.json").is_file() || has_table(&dir.join("Cargo.toml"), "[package]")
+}
+
+fn is_workspace_root(dir: &Path) -> bool {
+    has_table(&dir.join("Cargo.toml"), "[workspace]")
+        || dir.join("pnpm-workspace.yaml").is_file()
+        || fs::read_to_string(dir.join("package.json"))
+            .is_ok_and(|text| text.contains("\"workspaces\""))
+}
+
+fn has_table(path: &Path, header: &str) -> bool {
+    fs::read_to_string(path).is_ok_and(|text| text.lines().any(|line| line.trim() == header))
+}
+
+/// The directories whose .env files are loaded implicitly from `dir`, lowest precedence
+/// first: the workspace root, the package and `dir` itself
+pub fn env_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(Workspace { root, package }) = find(dir) {
+        dirs.push(root);
+        dirs.push(package);
+    }
+    dirs.push(dir.to_owned());
+    dirs.dedup();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_env_dirs() {
+        let root = env::temp_dir().join(format!("enw-workspace-test-{}", std::process::id()));
+        let package = root.join("crates/app");
+        fs::create_dir_all(package.join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .unwrap();
+        fs::write(package.join("Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+
+        assert_eq!(
+            env_dirs(&package.join("src")),
+            vec![root.clone(), package.clone(), package.join("src")]
+        );
+        assert_eq!(env_dirs(&package), vec![root.clone(), package.clone()]);
+        assert_eq!(env_dirs(&root), vec![root.clone()]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+//! A YAML subset, enough for configuration files: block mappings and lists nested by
+//! indentation, plain and quoted scalars, `|` literal blocks and comments. Flow collections,
+//! anchors and multiple documents are not supported. Scalars are not typed, apart from `null`
+//! and `~`, as they end up as environment variables anyway.
+
+use std::collections::BTreeMap;
+
+use crate::{json::Value, BoxError};
+
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+pub fn parse(text: &str) -> Result<Value, BoxError> {
+    let mut lines = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let content = raw.trim_start_matches(' ');
+        if raw.starts_with('\t') {
+            return Err(format!("line {}: tabs can not be used for indentation", i + 1).into());
+        }
+        if content.trim().is_empty() || content.starts_with('#') || content == "---" {
+            // Blank lines are kept for literal blocks
+            lines.push(Line {
+                number: i + 1,
+                indent: usize::MAX,
+                text: "",
+            });
+            continue;
+        }
+        lines.push(Line {
+            number: i + 1,
+            indent: raw.len() - content.len(),
+            text: content.trim_end(),
+        });
+    }
+    let mut pos = 0;
+    skip_blank(&lines, &mut pos);
+    if pos == lines.len() {
+        return Ok(Value::Object(BTreeMap::new()));
+    }
+    let indent = lines[pos].indent;
+    let value = parse_block(&lines, &mut pos, indent)?;
+    skip_blank(&lines, &mut pos);
+    match lines.get(pos) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.number).into()),
+        None => Ok(value),
+    }
+}
+
+fn skip_blank(lines: &[Line], pos: &mut usize) {
+    while lines
+        .get(*pos)
+        .is_some_and(|line| line.indent == usize::MAX)
+    {
+        *pos += 1;
+    }
+}
+
+/// A mapping or sequence whose entries are indented by `indent`
+fn parse_block(lines: &[Line], pos: &mut usize, indent: usize) -> Result<Value, BoxError> {
+    let is_sequence = lines[*pos].text == "-" || lines[*pos].text.starts_with("- ");
+    let mut map = BTreeMap::new();
+    let mut items = Vec::new();
+    loop {
+        skip_blank(lines, pos);
+        let Some(line) = lines.get(*pos) else { break };
+        // A sequence indented as much as the key holding it ends at the next key
+        if line.indent < indent || (is_sequence && !line.text.starts_with('-')) {
+            break;
+        }
+        if line.indent > indent {
+            return Err(format!("line {}: unexpected indentation", line.number).into());
+        }
+        *pos += 1;
+        if is_sequence {
+            let rest = match line.text.strip_prefix('-') {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => rest.trim_start(),
+                _ => return Err(format!("line {}: expected a list item", line.number).into()),
+            };
+            items.push(parse_value(lines, pos, indent, line.number, rest)?);
+        } else {
+            let (key, rest) = split_key(line.text)
+                .ok_or_else(|| format!("line {}: expected KEY: VALUE", line.number))?;
+            let value = parse_value(lines, pos, indent, line.number, rest)?;
+            if map.insert(key.clone(), value).is_some() {
+                return Err(format!("line {}: duplicate key {key}", line.number).into());
+            }
+        }
+    }
+    Ok(if is_sequence {
+        Value::Array(items)
+    } else {
+        Value::Object(map)
+    })
+}
+
+/// The value after `key:` or `-`: inline, or a nested block on the following lines
+fn parse_value(
+    lines: &[Line],
+    pos: &mut usize,
+    indent: usize,
+    number: usize,
+    rest: &str,
+) -> Result<Value, BoxError> {
+    let rest = strip_comment(rest);
+    if rest == "|" || rest == "|-" {
+        return Ok(Value::String(literal_block(
+            lines,
+            pos,
+            indent,
+            rest == "|",
+        )));
+    }
+    if !rest.is_empty() {
+        return scalar(rest).map_err(|e| format!("line {number}: {e}").into());
+    }
+    let mut next = *pos;
+    skip_blank(lines, &mut next);
+    match lines.get(next) {
+        // A sequence may be indented as much as the key holding it
+        Some(line)
+            if line.indent > indent
+                || (line.indent == indent
+                    && line.text.starts_with("- ")
+                    && !is_item(lines, *pos - 1)) =>
+        {
+            *pos = next;
+            parse_block(lines, pos, line.indent)
+        }
+        _ => Ok(Value::Null),
+    }
+}
+
+fn is_item(lines: &[Line], pos: usize) -> bool {
+    lines[pos].text.starts_with('-')
+}
+
+fn literal_block(lines: &[Line], pos: &mut usize, indent: usize, keep_newline: bool) -> String {
+    let mut block_indent = None;
+    let mut out = String::new();
+    let raw_lines: Vec<&Line> = lines[*pos..].iter().collect();
+    for line in raw_lines {
+        if line.indent == usize::MAX {
+            out.push('\n');
+            *pos += 1;
+            continue;
+        }
+        if line.indent <= indent {
+            break;
+        }
+        let block_indent = *block_indent.get_or_insert(line.indent);
+        out.push_str(&" ".repeat(line.indent.saturating_sub(block_indent)));
+        out.push_str(line.text);
+        out.push('\n');
+        *pos += 1;
+    }
+    let trimmed = out.trim_end_matches('\n').len();
+    out.truncate(trimmed);
+    if keep_newline && !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Split `key: value`, the key possibly quoted
+fn split_key(text: &str) -> Option<(String, &str)> {
+    if text.starts_with(['"', '\'']) {
+        let (key, len) = quoted(text).ok()?;
+        let rest = text[len..].strip_prefix(':')?;
+        return (rest.is_empty() || rest.starts_with(' ')).then(|| (key, rest.trim_start()));
+    }
+    let at = text
+        .match_indices(':')
+        .map(|(at, _)| at)
+        .find(|&at| text[at + 1..].is_empty() || text[at + 1..].starts_with(' '))?;
+    Some((
+        text[..at].trim_end().to_owned(),
+        text[at + 1..].trim_start(),
+    ))
+}
+
+fn strip_comment(text: &str) -> &str {
+    if text.starts_with(['"', '\'']) {
+        return text;
+    }
+    match text.find(" #") {
+        Some(at) => text[..at].trim_end(),
+        None if text.starts_with('#') => "",
+        None => text,
+    }
+}
+
+fn scalar(text: &str) -> Result<Value, BoxError> {
+    if text.starts_with(['"', '\'']) {
+        let (value, len) = quoted(text)?;
+        let rest = text[len..].trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!("unexpected text after quoted value: {rest}").into());
+        }
+        return Ok(Value::String(value));
+    }
+    if text.starts_with(['[', '{', '&', '*', '!']) {
+        return Err(format!("unsupported YAML syntax: {text}").into());
+    }
+    Ok(match text {
+        "null" | "~" => Value::Null,
+        _ => Value::String(text.to_owned()),
+    })
+}
+
+/// A quoted string at the start of `text` and the length it takes up
+fn quoted(text: &str) -> Result<(String, usize), BoxError> {
+    let quote = text.chars().next().unwrap_or('"');
+    let mut out = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((at, c)) = chars.next() {
+        match (quote, c) {
+            ('\'', '\'') if text[at + 1..].starts_with('\'') => {
+                chars.next();
+                out.push('\'');
+            }
+            (q, c) if c == q => return Ok((out, at + 1)),
+            ('"', '\\') => match chars.next().map(|(_, c)| c) {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some(c @ ('"' | '\\' | '/')) => out.push(c),
+                Some(c) => return Err(format!("invalid escape: \\{c}").into()),
+                None => break,
+            },
+            (_, c) => out.push(c),
+        }
+    }
+    Err("unterminated quoted value".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_parse() {
+        let yaml = r#"
+# service config
+name: api   # trailing comment
+"quoted key": 'it''s'
+database:
+  url: "postgres://localhost/db"
+  pool:
+    size: 5
+empty:
+hosts:
+- a.example.com
+- "b.example.com"
+cert: |
+  -----BEGIN-----
+    abc
+
+  -----END-----
+"#;
+        let expected = json::parse(
+            r#"{
+                "name": "api",
+                "quoted key": "it's",
+                "database": {"url": "postgres://localhost/db", "pool": {"size": "5"}},
+                "empty": null,
+                "hosts": ["a.example.com", "b.example.com"],
+                "cert": "-----BEGIN-----\n  abc\n\n-----END-----\n"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(parse(yaml).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "a: 1\n  b: 2",
+            "a: [1, 2]",
+            "a: 'open",
+            "a: 1\na: 2",
+            "\ta: 1",
+        ] {
+            assert!(parse(input).is_err(), "expected err: {input:?}");
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Runs the doctests of the Python bindings against the C library
+#![cfg(feature = "ffi")]
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn test_python_doctests() {
+    if Command::new("python3").arg("--version").output().is_err() {
+        eprintln!("python3 not found, skipping the Python binding doctests");
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A target directory of its own, so that this build does not wait on the one running the
+    // tests, or replace its artifacts
+    let target = root.join("target").join("python");
+    let status = Command::new(env!("CARGO"))
+        .args(["rustc", "--lib", "--features", "ffi", "--crate-type", "cdylib"])
+        .arg("--target-dir")
+        .arg(&target)
+        .current_dir(root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = Command::new("python3")
+        .args(["-m", "doctest", "-v", "python/enw.py"])
+        .env("ENW_LIBRARY", target.join("debug").join("libenw.so"))
+        .env("PYTHONPATH", root.join("python"))
+        .current_dir(root)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("3 passed and 0 failed"), "{stdout}");
+}
@@ -1,4 +1,9 @@
-use std::{env, path::Path, process::Command};
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
 
 use pretty_assertions::assert_eq;
 
@@ -104,6 +109,854 @@ fn test_cli() -> Result<(), BoxError> {
         Ok(())
     })?;
 
+    // Test that `--` ends NAME=VALUE parsing
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-i", "-n", "a=b", "--", "env", "c=d"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(actual.status.success());
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        assert_eq!(stdout, "a=b\nc=d\n");
+        Ok(())
+    })?;
+
+    // Test that a lone `-` clears the environment, like `-i`
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "-", "a=b", "env"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(actual.status.success());
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        assert_eq!(stdout, "a=b\n");
+        Ok(())
+    })?;
+
+    // Test that arguments after `--`, `-` or the command are the command's, even when they look like enw's options
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for args in [
+            vec!["-i", "-n", "--", "echo", "--file", "x", "-i"],
+            vec!["-n", "-", "--", "echo", "--file", "x", "-i"],
+            vec!["-i", "-n", "a=b", "echo", "--file", "x", "-i"],
+            vec!["-i", "-n", "--", "a=b", "--", "echo", "--file", "x", "-i"],
+        ] {
+            let actual = Command::new("../target/debug/enw").args(args).output()?;
+            assert_eq!(String::from_utf8(actual.stdout)?, "--file x -i\n");
+        }
+        Ok(())
+    })?;
+
+    // Test that variables are printed and passed on sorted by key, whatever the input order
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for command in &[None, Some("env")] {
+            let mut args = vec!["-i", "-n", "c=3", "B=2", "a=1"];
+            args.extend(command);
+            let actual = Command::new("../target/debug/enw").args(args).output()?;
+            assert!(actual.status.success());
+            let stdout = String::from_utf8_lossy(&actual.stdout);
+            assert_eq!(stdout, "B=2\na=1\nc=3\n");
+        }
+        Ok(())
+    })?;
+
+    // Test that --env takes values literally
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-i", "-n", "-e", "a=-x 'y'", "--env=b=", "c=d", "env"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(actual.status.success());
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        assert_eq!(stdout, "a=-x 'y'\nb=\nc=d\n");
+        Ok(())
+    })?;
+
+    // Test that compare lists differing variables and fails
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec![
+            "compare",
+            "--lhs",
+            "-n a=1 b=2 API_TOKEN=x",
+            "--rhs",
+            "-n a=1 b='2 3' c=4 API_TOKEN=y",
+        ]
+        .into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(!actual.status.success());
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        assert_eq!(stdout, "- API_TOKEN=****\n+ API_TOKEN=****\n- b=2\n+ b=2 3\n+ c=4\n");
+        Ok(())
+    })?;
+
+    // Test that the command starts with the requested signal dispositions
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "--default-signal", "--ignore-signal=INT", "--block-signal=HUP", "grep", "-E", "^Sig(Blk|Ign)", "/proc/self/status"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(actual.status.success());
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        // Signals 32 and up are reserved by glibc, only compare the standard ones
+        let masks: Vec<_> = stdout
+            .lines()
+            .map(|line| u64::from_str_radix(&line[8..], 16).map(|mask| mask & 0x7fff_ffff))
+            .collect::<Result<_, _>>()?;
+        // HUP blocked, INT ignored
+        assert_eq!(masks, vec![0x1, 0x2]);
+        Ok(())
+    })?;
+
+    // Test that a failed lookup names the directories searched
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "--search-path", "/nonexistent", "true"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(!actual.status.success());
+        let stderr = String::from_utf8_lossy(&actual.stderr);
+        assert!(stderr.contains("true: command not found, searched: /nonexistent"));
+        Ok(())
+    })?;
+
+    // Test that enw shell marks the environment for prompts
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let mut child = Command::new("../target/debug/enw").args(["--preset", "django", "shell"]).env("SHELL", "/bin/sh").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        child.stdin.take().unwrap().write_all(b"../target/debug/enw prompt; ../target/debug/enw prompt --format '{files}:{profile}'")?;
+        let actual = child.wait_with_output()?;
+        assert!(actual.status.success());
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "enw:.env\n.env:\n");
+        let actual = Command::new("../target/debug/enw").arg("prompt").env_remove("ENW_ACTIVE").output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "");
+        Ok(())
+    })?;
+
+    // Test that --print writes the command's environment without running one
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-i", "-n", "--print=export", "--env", "A=it's"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(actual.status.success());
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "export A='it'\\''s'\n");
+        Ok(())
+    })?;
+
+    // Test that enw exits with the command's status, signals as 128+N
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for (script, code) in [("exit 7", 7), ("kill -TERM $$", 143)] {
+            let args = vec!["--report", "/dev/null", "sh", "-c", script].into_iter();
+            let actual = Command::new("../target/debug/enw").args(args).output()?;
+            assert_eq!(actual.status.code(), Some(code));
+        }
+        Ok(())
+    })?;
+
+    // Test that -C runs the command elsewhere, loading .env from the original directory unless --chdir-first
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-C", "/", "sh", "-c", "echo $PWD $XYZZY"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "/ 123\n");
+        let args = vec!["-C", "/", "--chdir-first", "sh", "-c", "echo $PWD $XYZZY"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "/\n");
+        Ok(())
+    })?;
+
+    // Test that -u removes variables whether inherited or set in .env
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-u", "XYZZY", "--unset", "HOME", "sh", "-c", "echo ${XYZZY-none} ${HOME-none} $MY_URL"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "none none localhost\n");
+        Ok(())
+    })?;
+
+    // Test that --no-override and ENW_OVERRIDE=0 keep the parent's value, --override wins over ENW_OVERRIDE
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for (args, override_var, expected) in [
+            (vec![], None, "123"),
+            (vec!["--no-override"], None, "outer"),
+            (vec![], Some("0"), "outer"),
+            (vec!["--override"], Some("0"), "123"),
+            (vec!["--no-override", "XYZZY=arg"], None, "arg"),
+        ] {
+            let mut cmd = Command::new("../target/debug/enw");
+            cmd.args(args).args(["sh", "-c", "echo $XYZZY"]).env("XYZZY", "outer");
+            if let Some(value) = override_var {
+                cmd.env("ENW_OVERRIDE", value);
+            }
+            let actual = cmd.output()?;
+            assert_eq!(String::from_utf8_lossy(&actual.stdout), format!("{expected}\n"));
+        }
+        Ok(())
+    })?;
+
+    // Test that --watch restarts the command when an env file changes, and exits when signalled
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-watch-test-{}.env", std::process::id()));
+        std::fs::write(&path, "V=one\n")?;
+        let mut child = Command::new("../target/debug/enw")
+            .args(["-n", "--watch", "-f"])
+            .arg(&path)
+            .args(["sh", "-c", "echo $V; exec sleep 30"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        assert_eq!(lines.next().unwrap()?, "one");
+        std::fs::write(&path, "V=two\n")?;
+        assert_eq!(lines.next().unwrap()?, "two");
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        assert_eq!(child.wait()?.code(), Some(143));
+        std::fs::remove_file(path)?;
+        Ok(())
+    })?;
+
+    // Test that --watch rewrites secret files on restart, and removes them when it exits
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-watch-secret-test-{}.env", std::process::id()));
+        std::fs::write(&path, "TOKEN=one\n")?;
+        let mut child = Command::new("../target/debug/enw")
+            .args(["-n", "--watch", "--secret-via-file", "TOKEN", "-f"])
+            .arg(&path)
+            .args(["sh", "-c", "cat $TOKEN_FILE; echo; echo $TOKEN_FILE; exec sleep 30"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        assert_eq!(lines.next().unwrap()?, "one");
+        let secret_file = lines.next().unwrap()?;
+        std::fs::write(&path, "TOKEN=two\n")?;
+        assert_eq!(lines.next().unwrap()?, "two");
+        assert_eq!(lines.next().unwrap()?, secret_file);
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        assert_eq!(child.wait()?.code(), Some(143));
+        assert!(!std::path::Path::new(&secret_file).exists());
+        std::fs::remove_file(path)?;
+        Ok(())
+    })?;
+
+    // Test that --watch-strategy signal signals the command instead of restarting it, and queue waits for it to exit
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-watch-strategy-test-{}.env", std::process::id()));
+        std::fs::write(&path, "V=one\n")?;
+        let mut child = Command::new("../target/debug/enw")
+            .args(["-n", "--watch", "--watch-strategy", "signal", "--watch-signal", "USR1", "-f"])
+            .arg(&path)
+            .args(["sh", "-c", "trap 'echo reload $V' USR1; echo $V; while :; do sleep 0.1; done"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        assert_eq!(lines.next().unwrap()?, "one");
+        std::fs::write(&path, "V=two\n")?;
+        assert_eq!(lines.next().unwrap()?, "reload one");
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        child.wait()?;
+
+        std::fs::write(&path, "V=one\n")?;
+        let mut child = Command::new("../target/debug/enw")
+            .args(["-n", "--watch", "--watch-strategy", "queue", "-f"])
+            .arg(&path)
+            .args(["sh", "-c", "echo $V; read line; echo done"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        assert_eq!(lines.next().unwrap()?, "one");
+        std::fs::write(&path, "V=two\n")?;
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+        stdin.write_all(b"\n")?;
+        assert_eq!(lines.next().unwrap()?, "done");
+        assert_eq!(lines.next().unwrap()?, "two");
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        child.wait()?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    })?;
+
+    // Test that --watch rejects the options it can not honour
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for args in [
+            &["--timeout", "1", "true"][..],
+            &["--pty", "true"],
+            &["true", "--and", "true"],
+        ] {
+            let output = Command::new("../target/debug/enw")
+                .args(["-n", "--watch", "-f", "data/.env"])
+                .args(args)
+                .output()?;
+            assert!(!output.status.success(), "expected failure: {args:?}");
+            assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"), "{args:?}");
+        }
+        Ok(())
+    })?;
+
+    // Test that --on-change runs the hook with the new environment, leaving the command running
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-watch-hook-test-{}.env", std::process::id()));
+        std::fs::write(&path, "V=one\n")?;
+        let mut child = Command::new("../target/debug/enw")
+            .args(["-n", "--watch", "--on-change", "echo hook $V", "-f"])
+            .arg(&path)
+            .args(["sh", "-c", "echo $V; exec sleep 30"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        assert_eq!(lines.next().unwrap()?, "one");
+        std::fs::write(&path, "V=two\n")?;
+        assert_eq!(lines.next().unwrap()?, "hook two");
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        assert_eq!(child.wait()?.code(), Some(143));
+        std::fs::remove_file(path)?;
+        Ok(())
+    })?;
+
+    // Test that --explain names the file and line a variable comes from
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["--explain", "MY_URL"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        let stdout = String::from_utf8_lossy(&actual.stdout);
+        assert!(stdout.starts_with("MY_URL=localhost ("), "{stdout}");
+        assert!(stdout.ends_with(".env:2)\n"), "{stdout}");
+        let args = vec!["--explain", "XYZZY", "XYZZY=1"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "XYZZY=1 (command line)\n");
+        Ok(())
+    })?;
+
+    // Test that render substitutes the resolved environment into a template
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let mut child = Command::new("../target/debug/enw").args(["--env", "PORT=80", "render", "-"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        child.stdin.take().unwrap().write_all(b"http://$MY_URL:${PORT}/$XYZZY$NOPE\n")?;
+        let actual = child.wait_with_output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "http://localhost:80/123\n");
+        Ok(())
+    })?;
+
+    // Test that the shell hook applies the nearest .env once, and reverts it when leaving
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let script = "eval \"$(../target/debug/enw hook bash)\"; _enw_hook; echo $XYZZY; _enw_hook; cd /; _enw_hook; echo ${XYZZY-unset}";
+        let actual = Command::new("bash").args(["-c", script]).env_remove("XYZZY").env_remove("ENW_HOOK_STATE").output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "123\nunset\n");
+        assert_eq!(String::from_utf8_lossy(&actual.stderr).matches("enw: loading").count(), 1);
+        Ok(())
+    })?;
+
+    // Test that enw check and --require-schema list what .env.example declares but is missing
+    in_directory(&env::current_dir()?.join("tests/data/conf.d"), || {
+        std::fs::write(".env.example", "A=\nMISSING=\n")?;
+        let actual = Command::new("../../../target/debug/enw").args(["-f", "10-base.env", "check"]).output()?;
+        assert!(!actual.status.success());
+        assert!(String::from_utf8_lossy(&actual.stderr).contains("MISSING is missing"));
+        let actual = Command::new("../../../target/debug/enw").args(["--require-schema", "-f", "10-base.env", "MISSING=1", "true"]).output()?;
+        std::fs::remove_file(".env.example")?;
+        assert!(actual.status.success());
+        Ok(())
+    })?;
+
+    // Test that the command replaces enw, keeping its PID, unless --fork
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for (fork, same_pid) in [(false, true), (true, false)] {
+            let mut args = vec!["sh", "-c", "echo $$"];
+            if fork {
+                args.insert(0, "--fork");
+            }
+            let child = Command::new("../target/debug/enw").args(args).stdout(Stdio::piped()).spawn()?;
+            let pid = child.id();
+            let actual = child.wait_with_output()?;
+            assert!(actual.status.success());
+            assert_eq!(String::from_utf8_lossy(&actual.stdout).trim() == pid.to_string(), same_pid);
+        }
+        Ok(())
+    })?;
+
+    // Test that $(command) in values runs once per command with --allow-exec, and is kept as is otherwise
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-exec-test-{}.env", std::process::id()));
+        std::fs::write(&path, "A=$(echo x >&2; echo one)\nB=\"$(echo x >&2; echo one) two\"\n")?;
+        let args = vec!["-i", "-n", "--allow-exec", "-f", path.to_str().unwrap()].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "A=one\nB=\"one two\"\n");
+        assert_eq!(String::from_utf8_lossy(&actual.stderr), "x\n");
+        let args = vec!["-i", "-n", "-f", path.to_str().unwrap()].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(String::from_utf8_lossy(&actual.stdout).starts_with("A=\"$(echo x"));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    })?;
+
+    // Test that enw exits like GNU env when it fails: 125 for its own errors, 126 and 127 for the command
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for (args, code) in [
+            (vec!["--no-such-option"], 125),
+            (vec!["-f", "/nonexistent/*.env", "true"], 125),
+            (vec!["./data"], 126),
+            (vec!["no-such-command"], 127),
+            (vec!["--fork", "./no-such-command"], 127),
+        ] {
+            let actual = Command::new("../target/debug/enw").args(args).output()?;
+            assert_eq!(actual.status.code(), Some(code));
+        }
+        Ok(())
+    })?;
+
+    // Test that -0 reads NUL delimited pairs from --stdin and prints NUL terminated ones
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for args in [vec!["-i", "-n", "-0", "--stdin"], vec!["-i", "-n", "-0", "--stdin", "--print"]] {
+            let mut child = Command::new("../target/debug/enw")
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            child.stdin.take().unwrap().write_all(b"A=two\nlines\0B=x\0")?;
+            let actual = child.wait_with_output()?;
+            assert!(actual.status.success());
+            assert_eq!(actual.stdout, b"A=two\nlines\0B=x\0");
+        }
+        let actual = Command::new("../target/debug/enw").args(vec!["-0", "true"]).output()?;
+        assert_eq!(actual.status.code(), Some(125));
+        Ok(())
+    })?;
+
+    // Test that -f - and --stdin read an env file from stdin
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for args in [vec!["-i", "-n", "-f", "-"], vec!["-i", "-n", "--stdin"]] {
+            let mut child = Command::new("../target/debug/enw")
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            child.stdin.take().unwrap().write_all(b"# decrypted\nA='it is'\nB=$A\n")?;
+            let actual = child.wait_with_output()?;
+            assert!(actual.status.success());
+            assert_eq!(String::from_utf8_lossy(&actual.stdout), "A=\"it is\"\nB=\"it is\"\n");
+        }
+        Ok(())
+    })?;
+
+    // Test that --only and --except narrow down the whole environment, inherited variables included
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "--only", "AWS_RE*", "--only", "AWS_SE*", "--only", "HOME", "--except", "*_SECRET*", "--print"].into_iter();
+        let actual = Command::new("../target/debug/enw")
+            .args(args)
+            .env("AWS_REGION", "eu-west-1")
+            .env("AWS_SECRET_ACCESS_KEY", "hunter2")
+            .env("HOME", "/home/me")
+            .output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "AWS_REGION=eu-west-1\nHOME=/home/me\n");
+        Ok(())
+    })?;
+
+    // Test that --prefix loads only the prefixed variables, and --strip-prefix renames them
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-prefix-test-{}.env", std::process::id()));
+        std::fs::write(&path, "API_PORT=80\nWORKER_PORT=81\nAPI_HOST=$WORKER_PORT\n")?;
+        let file = path.to_str().unwrap();
+        let args = vec!["-i", "-n", "-f", file, "--prefix", "API_"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "API_HOST=81\nAPI_PORT=80\n");
+        let args = vec!["-i", "-n", "-f", file, "--prefix", "API_", "--strip-prefix", "-e", "PORT=1"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "HOST=81\nPORT=1\n");
+        std::fs::remove_file(&path)?;
+        Ok(())
+    })?;
+
+    // Test that --dialect follows the rules of the tool named
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for (dialect, expected) in [("compose", Some("A=\"b#c x\"\n")), ("dotenv", Some("A=b\n")), ("shell", None)] {
+            let mut child = Command::new("../target/debug/enw")
+                .args(["-i", "-n", "--dialect", dialect, "-f", "-"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            child.stdin.take().unwrap().write_all(b"A=b#c x\n")?;
+            let actual = child.wait_with_output()?;
+            match expected {
+                Some(expected) => assert_eq!(String::from_utf8_lossy(&actual.stdout), expected),
+                None => assert!(!actual.status.success()),
+            }
+        }
+        Ok(())
+    })?;
+
+    // Test that diff lists the variables that differ between two env files, and exits 1 if any do
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let paths: Vec<_> = ["a", "b"].iter().map(|side| env::temp_dir().join(format!("enw-diff-test-{}-{side}.env", std::process::id()))).collect();
+        std::fs::write(&paths[0], "SAME=1\nOLD=2\nAPI_TOKEN=x\n")?;
+        std::fs::write(&paths[1], "SAME=1\nNEW=3\nAPI_TOKEN=y\n")?;
+        let (a, b) = (paths[0].to_str().unwrap(), paths[1].to_str().unwrap());
+        let actual = Command::new("../target/debug/enw").args(["diff", "--file", a, "--file", b]).output()?;
+        assert_eq!(actual.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "- API_TOKEN=****\n+ API_TOKEN=****\n+ NEW=3\n- OLD=2\n");
+        let actual = Command::new("../target/debug/enw").args(["diff", "--file", a, "--file", a, "--format", "table"]).output()?;
+        assert_eq!(actual.status.code(), Some(0));
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "status  key  old  new\n");
+        for path in paths {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    })?;
+
+    // Test that --timeout stops a command running too long and exits with 124, like GNU timeout
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let actual = Command::new("../target/debug/enw").args(["--timeout", "0.2", "sleep", "5"]).output()?;
+        assert_eq!(actual.status.code(), Some(124));
+        let actual = Command::new("../target/debug/enw").args(["--timeout", "1m", "sh", "-c", "exit 3"]).output()?;
+        assert_eq!(actual.status.code(), Some(3));
+        Ok(())
+    })?;
+
+    // Test that values, arguments and inherited variables that are not UTF-8 are passed on as they are
+    in_directory(&env::current_dir()?.join("tests"), || {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let latin1 = OsStr::from_bytes(b"caf\xe9");
+        let pair = OsStr::from_bytes(b"A=caf\xe9");
+        let actual = Command::new("../target/debug/enw")
+            .env("INHERITED", latin1)
+            .args([OsStr::new("-n"), pair, OsStr::new("sh"), OsStr::new("-c"), OsStr::new("printf '%s|%s|%s' \"$A\" \"$INHERITED\" \"$1\""), OsStr::new("sh"), latin1])
+            .output()?;
+        assert!(actual.status.success());
+        assert_eq!(actual.stdout, b"caf\xe9|caf\xe9|caf\xe9");
+        Ok(())
+    })?;
+
+    // Test that completions prints a script, and the names the env files set for the script to complete
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let actual = Command::new("../target/debug/enw").args(["completions", "bash"]).output()?;
+        assert!(actual.status.success());
+        assert!(String::from_utf8(actual.stdout)?.contains("complete -F _enw_dynamic"));
+        let actual = Command::new("../target/debug/enw").args(["-f", "data/conf.d/*.env", "completions", "--names"]).output()?;
+        assert!(String::from_utf8(actual.stdout)?.starts_with("A\nB\n"));
+        Ok(())
+    })?;
+
+    // Test that the user config sets defaults, which flags override
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let enw = || {
+            let mut command = Command::new("../target/debug/enw");
+            command.env("ENW_CONFIG", "data/config/config.toml");
+            command
+        };
+        let actual = enw().args(["-i", "--show-secrets"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "A=base\nB=base\n");
+        let actual = enw().args(["-i", "--show-secrets", "-f", "data/conf.d/20-override.env"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "A=base\nB=override\n");
+        let actual = enw().args(["-i", "--explain", "B"]).output()?;
+        assert!(String::from_utf8(actual.stdout)?.starts_with("B=**** "));
+        Ok(())
+    })?;
+
+    // Test that --print masks secrets, unless --show-secrets is given, while the command gets them
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "--print", "A=1", "DB_PASSWORD=hunter2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "A=1\nDB_PASSWORD=****\n");
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "--print", "--show-secrets", "DB_PASSWORD=hunter2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "DB_PASSWORD=hunter2\n");
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "DB_PASSWORD=hunter2", "printenv", "DB_PASSWORD"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "hunter2\n");
+        Ok(())
+    })?;
+
+    // Test that the listing masks secrets, and output for programs refuses to, unless --show-secrets is given
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "A=1", "DB_PASSWORD=hunter2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "A=1\nDB_PASSWORD=\"****\"\n");
+        for args in [&["-0"][..], &["--print=export"], &["--print=json"]] {
+            let actual = Command::new("../target/debug/enw").args(["-i", "-n"]).args(args).arg("DB_PASSWORD=hunter2").output()?;
+            assert!(!actual.status.success());
+            assert!(actual.stdout.is_empty());
+            assert!(String::from_utf8(actual.stderr)?.contains("refusing to print secrets (DB_PASSWORD) masked"));
+        }
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "--print=export", "--show-secrets", "DB_PASSWORD=hunter2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "export DB_PASSWORD=hunter2\n");
+        let actual = Command::new("../target/debug/enw").args(["-i", "-n", "-0", "--show-secrets", "DB_PASSWORD=hunter2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "DB_PASSWORD=hunter2\0");
+        Ok(())
+    })?;
+
+    // Test that --and runs commands one after the other in the same environment, stopping at the first to fail
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "A=1", "--", "sh", "-c", "echo one $A", "--and", "sh", "-c", "echo two $A; exit 3", "--and", "echo", "three"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(actual.status.code(), Some(3));
+        assert_eq!(String::from_utf8(actual.stdout)?, "one 1\ntwo 1\n");
+        let args = vec!["-n", "--parallel", "sh", "-c", "exit 2", "--and", "true"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(actual.status.code(), Some(2));
+        // A single argument is a command name, spaces and all, not a command line to split
+        let actual = Command::new("../target/debug/enw").args(["-n", "echo one", "--and", "true"]).output()?;
+        assert_eq!(actual.status.code(), Some(127));
+        let actual = Command::new("../target/debug/enw").args(["-n", "--secret-via-fd", "A", "A=1", "true", "--and", "true"]).output()?;
+        assert!(String::from_utf8(actual.stderr)?.contains("--and cannot be used with --secret-via-fd"));
+        Ok(())
+    })?;
+
+    // Test that signals to enw are passed on to every command run with --parallel
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let mut child = Command::new("../target/debug/enw").args(["-n", "--parallel", "sleep", "30", "--and", "sleep", "30"]).stderr(Stdio::null()).spawn()?;
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        Command::new("kill").arg(child.id().to_string()).status()?;
+        let started = std::time::Instant::now();
+        assert_eq!(child.wait()?.code(), Some(143));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        Ok(())
+    })?;
+
+    // Test that -S splits a #! line into arguments, and --shell runs the command with $SHELL -c
+    in_directory(&env::current_dir()?.join("tests"), || {
+        use std::os::unix::fs::PermissionsExt;
+        let path = env::temp_dir().join(format!("enw-shebang-test-{}", std::process::id()));
+        let enw = env::current_dir()?.join("../target/debug/enw").canonicalize()?;
+        std::fs::write(&path, format!("#!{} -S -i -n A='x y' sh\necho \"$A\"\n", enw.display()))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        let actual = Command::new(&path).output();
+        std::fs::remove_file(&path)?;
+        assert_eq!(String::from_utf8(actual?.stdout)?, "x y\n");
+        let actual = Command::new("../target/debug/enw").env("SHELL", "/bin/sh").args(["-n", "--shell", "A=1", "echo $A | tr 1 2"]).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "2\n");
+        Ok(())
+    })?;
+
+    // Test that --from-snapshot gives the command the environment saved by snapshot save, in place of the inherited one
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let state = env::temp_dir().join(format!("enw-snapshot-test-{}", std::process::id()));
+        let enw = || {
+            let mut command = Command::new("../target/debug/enw");
+            command.env("XDG_STATE_HOME", &state);
+            command
+        };
+        let saved = enw().args(["-i", "-n", "--env", "A=1", "--env", "B=x\ny", "snapshot", "save", "ci"]).status()?;
+        let actual = enw().env("INHERITED", "1").args(["--from-snapshot", "ci", "C=2", "env"]).output();
+        let listed = enw().args(["snapshot", "list"]).output();
+        std::fs::remove_dir_all(&state)?;
+        assert!(saved.success());
+        assert_eq!(String::from_utf8(actual?.stdout)?, "A=1\nB=x\ny\nC=2\n");
+        assert_eq!(String::from_utf8(listed?.stdout)?, "ci\n");
+        Ok(())
+    })?;
+
+    // Test that --warn-duplicates names where a key is defined more than once, and --strict-duplicates fails
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let paths: Vec<_> = ["a", "b"].iter().map(|name| env::temp_dir().join(format!("enw-duplicates-test-{}-{name}.env", std::process::id()))).collect();
+        std::fs::write(&paths[0], "A=1\nB=2\nA=3\n")?;
+        std::fs::write(&paths[1], "B=4\n")?;
+        let run = |flag: &str| Command::new("../target/debug/enw").args(["-i", "-n", flag, "-f"]).arg(&paths[0]).arg("-f").arg(&paths[1]).output();
+        let warned = run("--warn-duplicates");
+        let failed = run("--strict-duplicates");
+        for path in &paths {
+            std::fs::remove_file(path)?;
+        }
+        let (warned, failed) = (warned?, failed?);
+        let (a, b) = (paths[0].display(), paths[1].display());
+        assert_eq!(String::from_utf8(warned.stdout)?, "A=3\nB=4\n");
+        assert_eq!(
+            String::from_utf8(warned.stderr)?,
+            format!("warning: A is defined more than once: {a}:1, {a}:3\nwarning: B is defined more than once: {a}:2, {b}:1\n")
+        );
+        assert_eq!(failed.status.code(), Some(125));
+        Ok(())
+    })?;
+
+    // Test that only arguments starting with a valid name and = are NAME=VALUE pairs, and the command keeps arguments with =
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-i", "-n", "A=1", "sh", "-c", "echo \"$A $*\"", "a=b", "--opt=x"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8(actual.stdout)?, "1 --opt=x\n");
+        let args = vec!["-i", "-n", "A=1", "./not a name=x"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(actual.status.code(), Some(127));
+        Ok(())
+    })?;
+
+    // Test that k8s splits secrets into a Secret with base64 data
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let output = Command::new("../target/debug/enw")
+            .args(["-i", "-n", "--env", "PORT=80", "--env", "API_TOKEN=abc", "k8s", "--name", "web", "--namespace", "prod"])
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("kind: ConfigMap\nmetadata:\n  name: web\n  namespace: prod\ndata:\n  PORT: \"80\"\n"));
+        assert!(stdout.contains("---\napiVersion: v1\nkind: Secret\n"));
+        assert!(stdout.contains("  API_TOKEN: YWJj\n"));
+
+        let output = Command::new("../target/debug/enw")
+            .args(["-i", "-n", "--env", "PORT=80", "k8s", "--name", "web", "--secret", "--configmap"])
+            .output()?;
+        assert!(!output.status.success());
+        Ok(())
+    })?;
+
+    // Test that --pty gives the command a terminal when the output is piped
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let output = Command::new("../target/debug/enw")
+            .args(["-i", "--pty", "sh", "-c", "test -t 0 && test -t 1 && echo tty; exit 3"])
+            .stdin(Stdio::null())
+            .output()?;
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8(output.stdout)?, "tty\r\n");
+
+        let output = Command::new("../target/debug/enw")
+            .args(["-i", "--pty", "sh", "-c", "test -t 1 || echo piped"])
+            .output()?;
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        Ok(())
+    })?;
+
+    // Test that secret files are removed once the command exits, when enw stays its parent
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let output = Command::new("../target/debug/enw")
+            .args(["-n", "--fork", "--secret-via-file", "TOKEN", "--env", "TOKEN=hunter2", "sh", "-c", "echo $TOKEN_FILE; cat $TOKEN_FILE"])
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        let (path, secret) = stdout.split_once('\n').unwrap();
+        assert_eq!(secret, "hunter2");
+        assert!(!std::path::Path::new(path).exists());
+        Ok(())
+    })?;
+
+    // Test that the rails preset lets .env.local override .env.development, and the node preset the reverse
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let enw = env::current_dir()?.join("../target/debug/enw");
+        let dir = env::temp_dir().join(format!("enw-preset-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(".env.local"), "WHERE=local\n")?;
+        std::fs::write(dir.join(".env.development"), "WHERE=development\n")?;
+        let run = |preset: &str| {
+            Command::new(&enw).current_dir(&dir).env_remove("RAILS_ENV").env_remove("RACK_ENV").env_remove("NODE_ENV").args(["-i", "--preset", preset]).output()
+        };
+        let (rails, node) = (run("rails"), run("node"));
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(String::from_utf8(rails?.stdout)?, "WHERE=local\n");
+        assert_eq!(String::from_utf8(node?.stdout)?, "WHERE=development\n");
+        Ok(())
+    })?;
+
+    // Test that --stdin is read once and every bench iteration gets its variables
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let out = env::temp_dir().join(format!("enw-bench-stdin-test-{}", std::process::id()));
+        let mut child = Command::new("../target/debug/enw").args(["-i", "--stdin", "bench", "-n", "3", "--", "sh", "-c", "echo \"$A\" >> \"$OUT\""]).stdin(Stdio::piped()).stdout(Stdio::null()).spawn()?;
+        child.stdin.take().unwrap().write_all(format!("A=1\nOUT={}\n", out.display()).as_bytes())?;
+        assert!(child.wait()?.success());
+        let lines = std::fs::read_to_string(&out);
+        let _ = std::fs::remove_file(&out);
+        assert_eq!(lines?, "1\n1\n1\n");
+        Ok(())
+    })?;
+
+    // Test that export takes its format as an argument or with --format
+    in_directory(&env::current_dir()?.join("tests"), || {
+        for args in [&["export", "systemd"][..], &["export", "--format", "systemd"]] {
+            let output = Command::new("../target/debug/enw").args(["-i", "-n", "--env", "A=a b"]).args(args).output()?;
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8(output.stdout)?, "A=\"a b\"\n");
+        }
+        Ok(())
+    })?;
+
+    // Test that a profile in an untrusted .enw.toml can not set dangerous variables, until it is trusted
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let enw = env::current_dir()?.join("../target/debug/enw");
+        let dir = env::temp_dir().join(format!("enw-profile-danger-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(".enw.toml"), "[defaults]\nprofile = \"x\"\n\n[profiles.x]\nvars.LD_PRELOAD = \"/tmp/evil.so\"\n")?;
+        let run = |args: &[&str]| Command::new(&enw).current_dir(&dir).env("XDG_CONFIG_HOME", dir.join("config")).args(args).output();
+        let denied = run(&["-i", "--dangerous-vars", "deny"]);
+        let trusted = run(&["trust", ".enw.toml"]).and_then(|_| run(&["-i", "--dangerous-vars", "deny"]));
+        std::fs::remove_dir_all(&dir)?;
+        let denied = denied?;
+        assert!(!denied.status.success());
+        assert!(String::from_utf8(denied.stderr)?.contains(".enw.toml sets LD_PRELOAD in profile x"));
+        assert_eq!(String::from_utf8(trusted?.stdout)?, "LD_PRELOAD=\"/tmp/evil.so\"\n");
+        Ok(())
+    })?;
+
+    // Test that --verify checks the signature of an encrypted file against its ciphertext, as signed
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let enw = env::current_dir()?.join("../target/debug/enw");
+        let dir = env::temp_dir().join(format!("enw-verify-encrypted-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("bin"))?;
+        // Stands in for age, decrypting anything to the same variables
+        std::fs::write(dir.join("bin/age"), "#!/bin/sh\ncat >/dev/null\nprintf 'GREETING=hello\\n'\n")?;
+        Command::new("chmod").arg("+x").arg(dir.join("bin/age")).status()?;
+        std::fs::write(dir.join("identity"), "")?;
+        std::fs::write(dir.join(".env.enc"), "age-encryption.org/v1\nciphertext\n")?;
+        Command::new("ssh-keygen").args(["-q", "-t", "ed25519", "-N", "", "-f"]).arg(dir.join("key")).status()?;
+        Command::new("ssh-keygen").args(["-q", "-Y", "sign", "-n", "enw", "-f"]).arg(dir.join("key")).arg(dir.join(".env.enc")).status()?;
+        std::fs::write(dir.join("signers"), format!("test@enw {}", std::fs::read_to_string(dir.join("key.pub"))?))?;
+        let path = format!("{}:{}", dir.join("bin").display(), env::var("PATH")?);
+        let run = || Command::new(&enw).current_dir(&dir).env("PATH", &path).env("ENW_AGE_IDENTITY", dir.join("identity")).args(["-i", "-n", "--verify", "--allowed-signers", "signers", "-f", ".env.enc"]).output();
+        let signed = run();
+        let tampered = std::fs::write(dir.join(".env.enc"), "age-encryption.org/v1\ntampered\n").and_then(|()| run());
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(String::from_utf8(signed?.stdout)?, "GREETING=hello\n");
+        assert!(!tampered?.status.success());
+        Ok(())
+    })?;
+
+    // Test that sops decrypts the bytes enw read, given on stdin
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let dir = env::temp_dir().join(format!("enw-sops-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("bin"))?;
+        // Stands in for sops, dropping its metadata from the file named last
+        std::fs::write(dir.join("bin/sops"), "#!/bin/sh\nfor last; do :; done\n[ \"$last\" = /dev/stdin ] || exit 1\ngrep -v '^sops_' \"$last\"\n")?;
+        Command::new("chmod").arg("+x").arg(dir.join("bin/sops")).status()?;
+        std::fs::write(dir.join(".env.enc"), "GREETING=ENC[AES256_GCM,data:aGk=]\nsops_mac=ENC[AES256_GCM,data:bWFj]\n")?;
+        let output = Command::new("../target/debug/enw")
+            .env("PATH", format!("{}:{}", dir.join("bin").display(), env::var("PATH")?))
+            .args(["-i", "-n", "-f"])
+            .arg(dir.join(".env.enc"))
+            .output();
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(String::from_utf8(output?.stdout)?, "GREETING=\"ENC[AES256_GCM,data:aGk=]\"\n");
+        Ok(())
+    })?;
+
+    // Test that --strict-secrets refuses tracked env files holding secrets named in the config
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let dir = env::temp_dir().join(format!("enw-strict-secrets-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("config.toml"), "secrets = [\"*_PIN\"]\n")?;
+        std::fs::write(dir.join(".env"), "CARD_PIN=1234\n")?;
+        for args in [&["init", "-q"][..], &["add", ".env"]] {
+            assert!(Command::new("git").args(args).current_dir(&dir).status()?.success());
+        }
+        let output = Command::new("../target/debug/enw")
+            .args(["--strict-secrets", "-f"])
+            .arg(dir.join(".env"))
+            .arg("true")
+            .env("ENW_CONFIG", dir.join("config.toml"))
+            .output()?;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("holds secrets (CARD_PIN) and is tracked by git"));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    })?;
+
+    // Test that @file: references are only read with --allow-file-refs
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let path = env::temp_dir().join(format!("enw-file-refs-test-{}.env", std::process::id()));
+        std::fs::write(&path, "KEY=@file:data/.env\n")?;
+        let output = Command::new("../target/debug/enw").arg("-f").arg(&path).args(["printenv", "KEY"]).output()?;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("not allowed without --allow-file-refs"));
+        let output = Command::new("../target/debug/enw")
+            .args(["--allow-file-refs", "-f"])
+            .arg(&path)
+            .args(["printenv", "KEY"])
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?.trim(), std::fs::read_to_string("data/.env")?.trim());
+        std::fs::remove_file(path)?;
+        Ok(())
+    })?;
+
+    // Test that -f with a pattern loads the matching files in lexicographic order
+    in_directory(&env::current_dir()?.join("tests"), || {
+        let args = vec!["-n", "-i", "-f", "data/conf.d/*.env"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert_eq!(String::from_utf8_lossy(&actual.stdout), "A=base\nB=override\n");
+        let args = vec!["-n", "-f", "data/conf.d/*.yaml"].into_iter();
+        let actual = Command::new("../target/debug/enw").args(args).output()?;
+        assert!(!actual.status.success());
+        Ok(())
+    })?;
+
     Ok(())
 }
 
@@ -0,0 +1,84 @@
+//! Preloaded by `enw trace-usage`, this library interposes `getenv` and `secure_getenv` and
+//! appends the name of every variable looked up to the file named by `ENW_TRACE_FILE`.
+//!
+//! Only lookups going through the C library are seen. Programs that read the environment
+//! block directly, like Python's `os.environ` or Go binaries, are invisible to it.
+
+use std::{
+    ffi::CStr,
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use libc::{c_char, c_void};
+
+const TRACE_FILE_VAR: &CStr = c"ENW_TRACE_FILE";
+
+type Getenv = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+
+static REAL_GETENV: AtomicUsize = AtomicUsize::new(0);
+static REAL_SECURE_GETENV: AtomicUsize = AtomicUsize::new(0);
+
+/// Look up the next definition of `symbol`, the one in the C library
+unsafe fn real(cache: &AtomicUsize, symbol: &CStr) -> Option<Getenv> {
+    let mut address = cache.load(Ordering::Relaxed);
+    if address == 0 {
+        address = libc::dlsym(libc::RTLD_NEXT, symbol.as_ptr()) as usize;
+        cache.store(address, Ordering::Relaxed);
+    }
+    (address != 0).then(|| mem::transmute::<usize, Getenv>(address))
+}
+
+/// Append `name` and a newline to the trace file, without allocating
+unsafe fn record(name: *const c_char, getenv: Getenv) {
+    if name.is_null() {
+        return;
+    }
+    let path = getenv(TRACE_FILE_VAR.as_ptr());
+    if path.is_null() {
+        return;
+    }
+    let name = CStr::from_ptr(name).to_bytes();
+    let mut line = [0u8; 256];
+    if name.len() >= line.len() {
+        return;
+    }
+    line[..name.len()].copy_from_slice(name);
+    line[name.len()] = b'\n';
+    let fd = libc::open(path, libc::O_WRONLY | libc::O_APPEND | libc::O_CLOEXEC);
+    if fd >= 0 {
+        libc::write(fd, line.as_ptr() as *const c_void, name.len() + 1);
+        libc::close(fd);
+    }
+}
+
+/// # Safety
+///
+/// Same contract as the C library's `getenv`.
+#[no_mangle]
+pub unsafe extern "C" fn getenv(name: *const c_char) -> *mut c_char {
+    match real(&REAL_GETENV, c"getenv") {
+        Some(getenv) => {
+            record(name, getenv);
+            getenv(name)
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// Same contract as the C library's `secure_getenv`.
+#[no_mangle]
+pub unsafe extern "C" fn secure_getenv(name: *const c_char) -> *mut c_char {
+    match (
+        real(&REAL_GETENV, c"getenv"),
+        real(&REAL_SECURE_GETENV, c"secure_getenv"),
+    ) {
+        (Some(getenv), Some(secure_getenv)) => {
+            record(name, getenv);
+            secure_getenv(name)
+        }
+        _ => std::ptr::null_mut(),
+    }
+}